@@ -1,172 +1,44 @@
-use std::marker::PhantomData;
-
 use halo2_proofs::{
     arithmetic::Field,
-    circuit::{Cell, Layouter, SimpleFloorPlanner, Value},
+    circuit::{Layouter, SimpleFloorPlanner, Value},
     halo2curves::bn256::Fr,
-    plonk::{Advice, Assigned, Circuit, Column, ConstraintSystem, Error, Fixed, Instance},
-    poly::Rotation,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
 };
 use serde::{Deserialize, Serialize};
 
-trait ArithmeticInstructions<F: Field> {
-    fn raw_multiply<FM>(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        f: FM,
-    ) -> Result<(Cell, Cell, Cell), Error>
-    where
-        FM: FnMut() -> Value<(Assigned<F>, Assigned<F>, Assigned<F>)>;
-
-    fn raw_add<FM>(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        f: FM,
-    ) -> Result<(Cell, Cell, Cell), Error>
-    where
-        FM: FnMut() -> Value<(Assigned<F>, Assigned<F>, Assigned<F>)>;
-
-    fn copy(&self, layouter: &mut impl Layouter<F>, a: Cell, b: Cell) -> Result<(), Error>;
-
-    fn expose_public(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        cell: Cell,
-        row: usize,
-    ) -> Result<(), Error>;
-}
+use crate::{
+    error::ProverError,
+    registry::DemoCircuit,
+    standard_plonk::{StandardPlonkChip, StandardPlonkConfig, StandardPlonkInstructions},
+    utils::{parse_encoding, FieldElement},
+};
 
 #[derive(Serialize, Deserialize)]
 pub struct ArithmeticInput {
-    pub x: u64,
-    pub y: u64,
-    pub constant: u64,
-    pub z: Option<u64>,
+    pub x: FieldElement,
+    pub y: FieldElement,
+    pub constant: FieldElement,
+    pub z: Option<FieldElement>,
 }
 
-#[derive(Debug, Clone)]
-pub struct ArithmeticConfig {
-    pub l: Column<Advice>,
-    pub r: Column<Advice>,
-    pub o: Column<Advice>,
-    pub sl: Column<Fixed>,
-    pub sr: Column<Fixed>,
-    pub so: Column<Fixed>,
-    pub sm: Column<Fixed>,
-    pub sc: Column<Fixed>,
-    pub PI: Column<Instance>,
-}
-
-pub struct ArithmeticChip<F: Field> {
-    config: ArithmeticConfig,
-    marker: PhantomData<F>,
+/// Verifier-side input of [`ArithmeticDemo`]: the constant and the claimed `z`, without
+/// `x` and `y`.
+#[derive(Serialize, Deserialize)]
+pub struct ArithmeticClaim {
+    pub constant: FieldElement,
+    pub z: FieldElement,
 }
 
-impl<F: Field> ArithmeticChip<F> {
-    fn new(config: ArithmeticConfig) -> Self {
-        Self {
-            config,
-            marker: PhantomData,
-        }
-    }
+/// The fields of [`ArithmeticInput`] that are baked into the circuit's fixed columns.
+#[derive(Serialize, Deserialize)]
+pub struct ArithmeticShape {
+    pub constant: FieldElement,
 }
 
-impl<F: Field> ArithmeticInstructions<F> for ArithmeticChip<F> {
-    fn raw_multiply<FM>(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        mut f: FM,
-    ) -> Result<(Cell, Cell, Cell), Error>
-    where
-        FM: FnMut() -> Value<(Assigned<F>, Assigned<F>, Assigned<F>)>,
-    {
-        layouter.assign_region(
-            || "mul",
-            |mut region| {
-                let values = Some(f());
-                let lhs = region.assign_advice(
-                    || "lhs",
-                    self.config.l,
-                    0,
-                    || values.unwrap().map(|v| v.0),
-                )?;
-
-                let rhs = region.assign_advice(
-                    || "rhs",
-                    self.config.r,
-                    0,
-                    || values.unwrap().map(|v| v.1),
-                )?;
-
-                let out = region.assign_advice(
-                    || "out",
-                    self.config.o,
-                    0,
-                    || values.unwrap().map(|v| v.2),
-                )?;
-
-                region.assign_fixed(|| "m", self.config.sm, 0, || Value::known(F::ONE))?;
-                region.assign_fixed(|| "o", self.config.so, 0, || Value::known(F::ONE))?;
-
-                Ok((lhs.cell(), rhs.cell(), out.cell()))
-            },
-        )
-    }
-
-    fn raw_add<FM>(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        mut f: FM,
-    ) -> Result<(Cell, Cell, Cell), Error>
-    where
-        FM: FnMut() -> Value<(Assigned<F>, Assigned<F>, Assigned<F>)>,
-    {
-        layouter.assign_region(
-            || "add",
-            |mut region| {
-                let values = Some(f());
-
-                let lhs = region.assign_advice(
-                    || "lhs",
-                    self.config.l,
-                    0,
-                    || values.unwrap().map(|v| v.0),
-                )?;
-
-                let rhs = region.assign_advice(
-                    || "rhs",
-                    self.config.r,
-                    0,
-                    || values.unwrap().map(|v| v.1),
-                )?;
-                let out = region.assign_advice(
-                    || "out",
-                    self.config.o,
-                    0,
-                    || values.unwrap().map(|v| v.2),
-                )?;
-
-                region.assign_fixed(|| "l", self.config.sl, 0, || Value::known(F::ONE))?;
-                region.assign_fixed(|| "r", self.config.sr, 0, || Value::known(F::ONE))?;
-                region.assign_fixed(|| "o", self.config.so, 0, || Value::known(F::ONE))?;
-
-                Ok((lhs.cell(), rhs.cell(), out.cell()))
-            },
-        )
-    }
-
-    fn copy(&self, layouter: &mut impl Layouter<F>, a: Cell, b: Cell) -> Result<(), Error> {
-        layouter.assign_region(|| "copy", |mut region| region.constrain_equal(a, b))
-    }
-
-    fn expose_public(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        cell: Cell,
-        row: usize,
-    ) -> Result<(), Error> {
-        layouter.constrain_instance(cell, self.config.PI, row)
-    }
+#[derive(Debug, Clone)]
+pub struct ArithmeticConfig {
+    pub plonk: StandardPlonkConfig,
+    pub instance: Column<Instance>,
 }
 
 #[derive(Default)]
@@ -185,48 +57,13 @@ impl<F: Field> Circuit<F> for ArithmeticCircuit<F> {
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let l = meta.advice_column();
-        let r = meta.advice_column();
-        let o = meta.advice_column();
-
-        meta.enable_equality(l);
-        meta.enable_equality(r);
-        meta.enable_equality(o);
-
-        let sm = meta.fixed_column();
-        let sl = meta.fixed_column();
-        let sr = meta.fixed_column();
-        let so = meta.fixed_column();
-        let sc = meta.fixed_column();
-
-        let PI = meta.instance_column();
-        meta.enable_equality(PI);
-
-        meta.create_gate("plonk", |meta| {
-            let l = meta.query_advice(l, Rotation::cur());
-            let r = meta.query_advice(r, Rotation::cur());
-            let o = meta.query_advice(o, Rotation::cur());
-
-            let sl = meta.query_fixed(sl, Rotation::cur());
-            let sr = meta.query_fixed(sr, Rotation::cur());
-            let so = meta.query_fixed(so, Rotation::cur());
-            let sm = meta.query_fixed(sm, Rotation::cur());
-            let sc = meta.query_fixed(sc, Rotation::cur());
-
-            vec![l.clone() * sl + r.clone() * sr + l * r * sm + (o * so * (-F::ONE)) + sc]
-        });
-
-        ArithmeticConfig {
-            l,
-            r,
-            o,
-            sl,
-            sr,
-            so,
-            sm,
-            sc,
-            PI,
-        }
+        let advice = [(); 3].map(|_| meta.advice_column());
+        let plonk = StandardPlonkChip::configure(meta, advice);
+
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+
+        ArithmeticConfig { plonk, instance }
     }
 
     fn synthesize(
@@ -234,77 +71,119 @@ impl<F: Field> Circuit<F> for ArithmeticCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let cs = ArithmeticChip::new(config);
+        let chip = StandardPlonkChip::construct(config.plonk);
+        let layouter = &mut layouter;
+
+        let x = chip.load_private(layouter, self.x)?;
+        let y = chip.load_private(layouter, self.y)?;
+        let x2 = chip.mul(layouter, &x, &x)?;
+        let y2 = chip.mul(layouter, &y, &y)?;
+        let x2y2 = chip.mul(layouter, &x2, &y2)?;
+        let z = chip.add_constant(layouter, &x2y2, self.constant)?;
+
+        // The constant is also public, so that a proof states which one it was made for.
+        let constant = chip.load_constant(layouter, self.constant)?;
+        layouter.constrain_instance(constant.cell(), config.instance, 0)?;
+        layouter.constrain_instance(z.cell(), config.instance, 1)
+    }
+}
 
-        let x: Value<Assigned<_>> = self.x.into();
-        let y: Value<Assigned<_>> = self.y.into();
-        let consty = Assigned::from(self.constant);
+pub fn create_circuit(x: Fr, y: Fr, constant: Fr) -> ArithmeticCircuit<Fr> {
+    ArithmeticCircuit {
+        x: Value::known(x),
+        y: Value::known(y),
+        constant,
+    }
+}
 
-        let (a0, b0, c0) = cs.raw_multiply(&mut layouter, || x.map(|x| (x, x, x * x)))?;
-        cs.copy(&mut layouter, a0, b0)?;
+pub fn empty_circuit(constant: Fr) -> ArithmeticCircuit<Fr> {
+    ArithmeticCircuit {
+        x: Value::unknown(),
+        y: Value::unknown(),
+        constant,
+    }
+}
 
-        let (a1, b1, c1) = cs.raw_multiply(&mut layouter, || y.map(|y| (y, y, y * y)))?;
-        cs.copy(&mut layouter, a1, b1)?;
+pub fn parse_string(s: &str) -> Result<ArithmeticInput, ProverError> {
+    Ok(serde_json::from_str(s)?)
+}
 
-        let (a2, b2, c2) = cs.raw_multiply(&mut layouter, || {
-            x.zip(y).map(|(x, y)| (x * x, y * y, (x * x) * (y * y)))
-        })?;
+pub fn create_circuit_from_string(s: &str) -> Result<ArithmeticCircuit<Fr>, ProverError> {
+    let v = parse_string(s)?;
+    Ok(ArithmeticCircuit {
+        x: Value::known(v.x.0),
+        y: Value::known(v.y.0),
+        constant: v.constant.0,
+    })
+}
 
-        cs.copy(&mut layouter, c0, a2)?;
-        cs.copy(&mut layouter, c1, b2)?;
+/// Computes `x^2 * y^2 + constant` in the field, written in the requested encoding.
+pub fn simulate_circuit(s: &str) -> Result<String, ProverError> {
+    let inp = parse_string(s)?;
+    let xy = inp.x.0 * inp.y.0;
+    Ok(parse_encoding(s)?.encode(&(xy.square() + inp.constant.0)))
+}
 
-        let (a3, b3, c3) = cs.raw_add(&mut layouter, || {
-            x.zip(y)
-                .map(|(x, y)| ((x * x) * (y * y), consty, (x * x) * (y * y) + consty))
-        })?;
+pub struct ArithmeticDemo;
 
-        cs.copy(&mut layouter, c2, a3)?;
+impl DemoCircuit for ArithmeticDemo {
+    type Circuit = ArithmeticCircuit<Fr>;
 
-        cs.expose_public(&mut layouter, b3, 0)?;
-        cs.expose_public(&mut layouter, c3, 1)?;
+    fn slug(&self) -> &'static str {
+        "arithmetic_circuit"
+    }
 
-        Ok(())
+    fn name(&self) -> &'static str {
+        "Arithmetic"
     }
-}
 
-pub fn create_circuit(x: u64, y: u64, constant: u64) -> ArithmeticCircuit<Fr> {
-    let x = Value::known(Fr::from(x));
-    let y = Value::known(Fr::from(y));
-    let constant = Fr::from(constant);
+    fn description(&self) -> &'static str {
+        "Proves knowledge of x and y such that x^2 * y^2 + constant = z in the field"
+    }
 
-    ArithmeticCircuit { x, y, constant }
-}
+    fn input_schema(&self) -> &'static str {
+        r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#
+    }
 
-pub fn empty_circuit(constant: u64) -> ArithmeticCircuit<Fr> {
-    ArithmeticCircuit {
-        x: Value::unknown(),
-        y: Value::unknown(),
-        constant: Fr::from(constant),
+    fn k(&self) -> u32 {
+        4
     }
-}
 
-pub fn parse_string(s: &str) -> ArithmeticInput {
-    serde_json::from_str(s).unwrap()
-}
+    fn create_from_json(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        create_circuit_from_string(s)
+    }
 
-pub fn create_circuit_from_string(s: &str) -> ArithmeticCircuit<Fr> {
-    let v = parse_string(s);
-    let x = v.x;
-    let y = v.y;
-    let constant = v.constant;
-    create_circuit(x, y, constant)
-}
+    fn empty(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        let shape: ArithmeticShape = serde_json::from_str(s)?;
+        Ok(ArithmeticCircuit {
+            constant: shape.constant.0,
+            ..Default::default()
+        })
+    }
 
-pub fn simulate_circuit(s: &str) -> String {
-    let inp = parse_string(s);
-    ((inp.x * inp.x) * (inp.y * inp.y) + inp.constant).to_string()
+    fn shape(&self, s: &str) -> Result<String, ProverError> {
+        let shape: ArithmeticShape = serde_json::from_str(s)?;
+        Ok(serde_json::to_string(&shape)?)
+    }
+
+    fn public_inputs(&self, s: &str) -> Result<Vec<Fr>, ProverError> {
+        let claim: ArithmeticClaim = serde_json::from_str(s)?;
+        Ok(vec![claim.constant.0, claim.z.0])
+    }
+
+    fn simulate(&self, s: &str) -> Result<String, ProverError> {
+        simulate_circuit(s)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::ArithmeticCircuit;
+    use super::{simulate_circuit, ArithmeticCircuit, ArithmeticDemo};
     use crate::arithmetic_circuit::{create_circuit, empty_circuit};
-    use crate::utils::{generate_keys, generate_proof_with_instance, verify_with_instance};
+    use crate::registry::DemoCircuit;
+    use crate::transcript::TranscriptHash;
+    use crate::utils::{fr_to_decimal, generate_keys, prove, verify, MultiOpenScheme};
+    use halo2_proofs::arithmetic::Field;
     use halo2_proofs::circuit::Value;
     use halo2_proofs::dev::MockProver;
     use halo2_proofs::halo2curves::bn256::{Bn256, Fr};
@@ -333,20 +212,60 @@ mod test {
     #[test]
     fn test_ecc() {
         let k = 8;
-        let constant = 7;
-        let x = 6;
-        let y = 9;
+        let constant = Fr::from(7);
+        let x = Fr::from(6);
+        let y = Fr::from(9);
         let z = Fr::from(36 * 81 + 7);
         let circuit = create_circuit(x, y, constant);
-        let public_input = [Fr::from(constant), z];
+        let public_input = [constant, z];
 
         let params = ParamsKZG::<Bn256>::new(k);
 
         let empty_circuit: ArithmeticCircuit<Fr> = empty_circuit(constant);
         let (pk, vk) = generate_keys(&params, &empty_circuit);
-        let proof = generate_proof_with_instance(&params, &pk, circuit, &public_input);
-
-        let is_valid = verify_with_instance(&params, &pk, &proof, &public_input).unwrap();
+        let proof = prove(
+            &params,
+            &pk,
+            circuit,
+            &[&public_input],
+            MultiOpenScheme::default(),
+            TranscriptHash::default(),
+        )
+        .unwrap();
+
+        let is_valid = verify(
+            &params,
+            &vk,
+            &proof,
+            &[&public_input],
+            MultiOpenScheme::default(),
+            TranscriptHash::default(),
+        )
+        .unwrap();
         assert_eq!(is_valid, ());
     }
+
+    #[test]
+    fn inputs_are_field_elements() {
+        let minus_one = fr_to_decimal(&-Fr::ONE);
+        let s = format!(
+            r#"{{ "x": "{}", "y": "0x10000000000000000", "constant": "Bw==", "encoding": "hex" }}"#,
+            minus_one
+        );
+        let y = Fr::from(u64::MAX) + Fr::ONE;
+        let z = y.square() + Fr::from(7);
+        assert_eq!(simulate_circuit(&s).unwrap(), format!("{:?}", z));
+
+        let circuit = ArithmeticDemo.create_from_json(&s).unwrap();
+        let prover = MockProver::run(4, &circuit, vec![vec![Fr::from(7), z]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let claim = format!(r#"{{ "constant": 7, "z": "{:?}" }}"#, z);
+        assert_eq!(
+            ArithmeticDemo.public_inputs(&claim),
+            Ok(vec![Fr::from(7), z])
+        );
+        // Without `z`, the prover's input claims nothing.
+        assert!(ArithmeticDemo.public_inputs(&s).is_err());
+    }
 }
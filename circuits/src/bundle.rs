@@ -0,0 +1,349 @@
+use std::io::{self, Read};
+
+use base64::{engine::general_purpose, Engine as _};
+use halo2_proofs::{
+    halo2curves::{
+        bn256::{Bn256, Fr},
+        ff::PrimeField,
+    },
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ProverError,
+    registry::{find_circuit, CircuitRunner},
+    transcript::TranscriptHash,
+    utils::MultiOpenScheme,
+};
+
+/// Leading bytes of an encoded [`ProofBundle`].
+pub const BUNDLE_MAGIC: [u8; 4] = *b"h2pb";
+
+/// Version of the encoding written by [`ProofBundle::to_bytes`] and
+/// [`ProofBundle::to_json`].
+pub const BUNDLE_VERSION: u8 = 1;
+
+/// A proof together with everything needed to verify it, given only the params.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofBundle {
+    /// Id of the circuit in the registry.
+    pub circuit: usize,
+    pub k: u32,
+    pub scheme: MultiOpenScheme,
+    pub transcript: TranscriptHash,
+    /// `transcript_repr` of the verifying key the proof was created with.
+    pub vk_hash: Fr,
+    /// Shape of the proven input, from which the verifying key is regenerated.
+    pub shape: String,
+    /// Public inputs, one entry per instance column.
+    pub instances: Vec<Vec<Fr>>,
+    pub proof: Vec<u8>,
+}
+
+/// JSON form of a [`ProofBundle`]. Field elements are big-endian hex strings and the proof
+/// is base64.
+#[derive(Serialize, Deserialize)]
+struct BundleJson {
+    version: u8,
+    circuit: usize,
+    k: u32,
+    scheme: u8,
+    transcript: u8,
+    vk_hash: String,
+    shape: String,
+    instances: Vec<Vec<String>>,
+    proof: String,
+}
+
+impl ProofBundle {
+    pub fn instance_columns(&self) -> Vec<&[Fr]> {
+        self.instances.iter().map(Vec::as_slice).collect()
+    }
+
+    /// Binary encoding: magic, version, then every field in declaration order. Integers are
+    /// little-endian `u32`, lengths prefix variable-sized fields.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = BUNDLE_MAGIC.to_vec();
+        bytes.push(BUNDLE_VERSION);
+        write_u32(&mut bytes, self.circuit as u32);
+        write_u32(&mut bytes, self.k);
+        bytes.push(self.scheme as u8);
+        bytes.push(self.transcript as u8);
+        bytes.extend_from_slice(self.vk_hash.to_repr().as_ref());
+        write_bytes(&mut bytes, self.shape.as_bytes());
+        write_u32(&mut bytes, self.instances.len() as u32);
+        for column in &self.instances {
+            write_u32(&mut bytes, column.len() as u32);
+            for value in column {
+                bytes.extend_from_slice(value.to_repr().as_ref());
+            }
+        }
+        write_bytes(&mut bytes, &self.proof);
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> io::Result<Self> {
+        let reader = &mut bytes;
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != BUNDLE_MAGIC {
+            return Err(invalid_data("not a proof bundle"));
+        }
+        check_version(read_u8(reader)?)?;
+
+        let circuit = read_u32(reader)? as usize;
+        let k = read_u32(reader)?;
+        let scheme = MultiOpenScheme::from_u8(read_u8(reader)?)
+            .ok_or_else(|| invalid_data("unknown multi-open scheme"))?;
+        let transcript = TranscriptHash::from_u8(read_u8(reader)?)
+            .ok_or_else(|| invalid_data("unknown transcript"))?;
+        let vk_hash = read_fr(reader)?;
+        let shape = String::from_utf8(read_bytes(reader)?)
+            .map_err(|_| invalid_data("shape is not valid UTF-8"))?;
+        let mut instances = vec![];
+        for _ in 0..read_u32(reader)? {
+            let column = (0..read_u32(reader)?)
+                .map(|_| read_fr(reader))
+                .collect::<io::Result<_>>()?;
+            instances.push(column);
+        }
+        let proof = read_bytes(reader)?;
+        if !reader.is_empty() {
+            return Err(invalid_data("trailing bytes after proof bundle"));
+        }
+
+        Ok(ProofBundle {
+            circuit,
+            k,
+            scheme,
+            transcript,
+            vk_hash,
+            shape,
+            instances,
+            proof,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let json = BundleJson {
+            version: BUNDLE_VERSION,
+            circuit: self.circuit,
+            k: self.k,
+            scheme: self.scheme as u8,
+            transcript: self.transcript as u8,
+            vk_hash: format!("{:?}", self.vk_hash),
+            shape: self.shape.clone(),
+            instances: self
+                .instances
+                .iter()
+                .map(|column| column.iter().map(|v| format!("{:?}", v)).collect())
+                .collect(),
+            proof: general_purpose::STANDARD.encode(&self.proof),
+        };
+        serde_json::to_string(&json).expect("proof bundle serializes")
+    }
+
+    pub fn from_json(s: &str) -> io::Result<Self> {
+        let json: BundleJson = serde_json::from_str(s)?;
+        check_version(json.version)?;
+
+        Ok(ProofBundle {
+            circuit: json.circuit,
+            k: json.k,
+            scheme: MultiOpenScheme::from_u8(json.scheme)
+                .ok_or_else(|| invalid_data("unknown multi-open scheme"))?,
+            transcript: TranscriptHash::from_u8(json.transcript)
+                .ok_or_else(|| invalid_data("unknown transcript"))?,
+            vk_hash: fr_from_hex(&json.vk_hash)?,
+            shape: json.shape,
+            instances: json
+                .instances
+                .iter()
+                .map(|column| column.iter().map(|v| fr_from_hex(v)).collect())
+                .collect::<io::Result<_>>()?,
+            proof: general_purpose::STANDARD
+                .decode(json.proof)
+                .map_err(|_| invalid_data("proof is not valid base64"))?,
+        })
+    }
+}
+
+/// Verifies `bundle` with a verifying key regenerated from its circuit id and shape.
+pub fn verify_bundle(params: &ParamsKZG<Bn256>, bundle: &ProofBundle) -> Result<(), ProverError> {
+    let circuit = bundle_circuit(params, bundle)?;
+    let vk = circuit.keygen_vk(params, &bundle.shape)?;
+    circuit.verify_bundle(params, &vk, bundle)
+}
+
+/// The circuit `bundle` was created for, provided it exists and `params` match its `k`.
+/// Checked before generating keys for the bundle, which would fail less clearly.
+pub(crate) fn bundle_circuit(
+    params: &ParamsKZG<Bn256>,
+    bundle: &ProofBundle,
+) -> Result<&'static dyn CircuitRunner, ProverError> {
+    if params.k() != bundle.k {
+        return Err(ProverError::InvalidParams(format!(
+            "proof was created with k = {}, params have k = {}",
+            bundle.k,
+            params.k()
+        )));
+    }
+    find_circuit(bundle.circuit)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn check_version(version: u8) -> io::Result<()> {
+    if version != BUNDLE_VERSION {
+        return Err(invalid_data("unsupported proof bundle version"));
+    }
+    Ok(())
+}
+
+fn fr_from_hex(s: &str) -> io::Result<Fr> {
+    let hex = s.trim_start_matches("0x");
+    if hex.len() > 64 || !hex.is_ascii() {
+        return Err(invalid_data("invalid field element"));
+    }
+    let hex = format!("{:0>64}", hex);
+
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    for (byte, chunk) in repr.as_mut().iter_mut().rev().zip(hex.as_bytes().chunks(2)) {
+        let chunk = std::str::from_utf8(chunk).expect("ascii");
+        *byte = u8::from_str_radix(chunk, 16).map_err(|_| invalid_data("invalid field element"))?;
+    }
+    Option::from(Fr::from_repr(repr)).ok_or_else(|| invalid_data("invalid field element"))
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    write_u32(bytes, data.len() as u32);
+    bytes.extend_from_slice(data);
+}
+
+fn read_u8(reader: &mut &[u8]) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut &[u8]) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_bytes(reader: &mut &[u8]) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    if len > reader.len() {
+        return Err(invalid_data("length exceeds proof bundle"));
+    }
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_fr(reader: &mut &[u8]) -> io::Result<Fr> {
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    reader.read_exact(repr.as_mut())?;
+    Option::from(Fr::from_repr(repr)).ok_or_else(|| invalid_data("invalid field element"))
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::halo2curves::{
+        bn256::{Bn256, Fr},
+        ff::Field,
+    };
+    use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+
+    use super::{verify_bundle, ProofBundle, BUNDLE_VERSION};
+    use crate::{
+        error::ProverError,
+        registry::{circuit_id, get_circuit},
+        transcript::TranscriptHash,
+        utils::{generate_params, MultiOpenScheme},
+    };
+
+    fn arithmetic_bundle(params: &ParamsKZG<Bn256>) -> ProofBundle {
+        let s = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#;
+        let id = circuit_id("arithmetic_circuit").unwrap();
+        let circuit = get_circuit(id).unwrap();
+
+        let vk = circuit.keygen_vk(params, s).unwrap();
+        let pk = circuit.keygen_pk(params, vk, s).unwrap();
+        circuit
+            .prove_json(
+                id,
+                params,
+                &pk,
+                s,
+                MultiOpenScheme::Gwc,
+                TranscriptHash::Poseidon,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let bundle = ProofBundle {
+            circuit: 1,
+            k: 4,
+            scheme: MultiOpenScheme::Gwc,
+            transcript: TranscriptHash::Keccak256,
+            vk_hash: -Fr::from(3),
+            shape: r#"{"constant":7}"#.to_string(),
+            instances: vec![vec![Fr::from(7), Fr::from(2923)], vec![]],
+            proof: vec![1, 2, 3],
+        };
+
+        let bytes = bundle.to_bytes();
+        assert_eq!(ProofBundle::from_bytes(&bytes).unwrap(), bundle);
+        assert_eq!(ProofBundle::from_json(&bundle.to_json()).unwrap(), bundle);
+
+        assert!(ProofBundle::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ProofBundle::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] ^= 1;
+        assert!(ProofBundle::from_bytes(&wrong_magic).is_err());
+        let mut wrong_version = bytes;
+        wrong_version[4] = BUNDLE_VERSION + 1;
+        assert!(ProofBundle::from_bytes(&wrong_version).is_err());
+    }
+
+    #[test]
+    fn verify_with_params_only() {
+        let params = generate_params(4);
+        let bundle = arithmetic_bundle(&params);
+        assert!(verify_bundle(&params, &bundle).is_ok());
+
+        let decoded = ProofBundle::from_bytes(&bundle.to_bytes()).unwrap();
+        assert!(verify_bundle(&params, &decoded).is_ok());
+
+        let mut wrong_instance = bundle.clone();
+        wrong_instance.instances[0][1] += Fr::ONE;
+        assert!(verify_bundle(&params, &wrong_instance).is_err());
+
+        let mut wrong_shape = bundle.clone();
+        wrong_shape.shape = r#"{"constant":8}"#.to_string();
+        assert!(verify_bundle(&params, &wrong_shape).is_err());
+
+        assert!(matches!(
+            verify_bundle(&generate_params(5), &bundle),
+            Err(ProverError::InvalidParams(_))
+        ));
+
+        let mut unknown_circuit = bundle;
+        unknown_circuit.circuit = usize::MAX;
+        assert!(matches!(
+            verify_bundle(&params, &unknown_circuit),
+            Err(ProverError::UnknownCircuit(_))
+        ));
+    }
+}
@@ -0,0 +1,616 @@
+//! Proves circom circuits from the `.r1cs` file written by the circom compiler and the
+//! `.wtns` witness computed by its witness generator.
+//!
+//! Every wire of the R1CS is loaded into a cell of the [`StandardPlonkChip`], and each
+//! constraint `A·w * B·w = C·w` is laid out as its three linear combinations, one row per
+//! term, followed by a multiplication. The public signals, outputs first and then public
+//! inputs as in snarkjs's `public.json`, are the instance column.
+
+use std::sync::Arc;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::{
+        bn256::{Bn256, Fr},
+        ff::{Field, PrimeField},
+    },
+    plonk::{keygen_pk, keygen_vk, Circuit, Column, ConstraintSystem, Error, Instance},
+    poly::kzg::commitment::{ParamsKZG, ParamsVerifierKZG},
+};
+use num::{BigUint, Num};
+
+use crate::{
+    error::ProverError,
+    standard_plonk::{StandardPlonkChip, StandardPlonkConfig, StandardPlonkInstructions},
+    transcript::TranscriptHash,
+    utils::{prove, verify, MultiOpenScheme},
+};
+
+/// Terms `(wire, coefficient)` of a linear combination of the wires.
+pub type LinearCombination = Vec<(usize, Fr)>;
+
+/// A constraint `a·w * b·w = c·w` on the wires `w`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Constraint {
+    pub a: LinearCombination,
+    pub b: LinearCombination,
+    pub c: LinearCombination,
+}
+
+/// The constraint system of a circom circuit. Wire 0 is the constant 1, followed by the
+/// public outputs, the public inputs and the private inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct R1cs {
+    pub num_wires: usize,
+    pub num_public_outputs: usize,
+    pub num_public_inputs: usize,
+    pub num_private_inputs: usize,
+    pub constraints: Vec<Constraint>,
+}
+
+/// Reads the little-endian fields of circom's binary files.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    file: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, message: &str) -> ProverError {
+        ProverError::InvalidInput(format!("invalid {} file: {}", self.file, message))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProverError> {
+        if self.bytes.len() < n {
+            return Err(self.error("unexpected end of file"));
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, ProverError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProverError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, ProverError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn fr(&mut self) -> Result<Fr, ProverError> {
+        let repr = self.take(32)?.try_into().unwrap();
+        Option::from(Fr::from_repr(repr)).ok_or_else(|| self.error("non-canonical field element"))
+    }
+
+    /// Checks the field size and prime of a header, which must be those of [`Fr`].
+    fn field(&mut self) -> Result<(), ProverError> {
+        let n8 = self.usize()?;
+        let prime = BigUint::from_bytes_le(self.take(n8)?);
+        let modulus = BigUint::from_str_radix(Fr::MODULUS.trim_start_matches("0x"), 16).unwrap();
+        if n8 != 32 || prime != modulus {
+            return Err(self.error("the prime is not the BN254 scalar field"));
+        }
+        Ok(())
+    }
+
+    /// Checks the magic number and version, and returns the sections by type.
+    fn sections(
+        mut self,
+        magic: &[u8; 4],
+        version: u32,
+    ) -> Result<Vec<(u32, Reader<'a>)>, ProverError> {
+        if self.take(4)? != magic {
+            return Err(self.error("wrong magic number"));
+        }
+        if self.u32()? != version {
+            return Err(self.error(&format!("expected version {}", version)));
+        }
+        let count = self.u32()?;
+        (0..count)
+            .map(|_| {
+                let kind = self.u32()?;
+                let size = self.u64()?;
+                let size = usize::try_from(size).map_err(|_| self.error("section too large"))?;
+                let bytes = self.take(size)?;
+                Ok((
+                    kind,
+                    Reader {
+                        bytes,
+                        file: self.file,
+                    },
+                ))
+            })
+            .collect()
+    }
+}
+
+fn section<'a>(
+    sections: &mut Vec<(u32, Reader<'a>)>,
+    kind: u32,
+    file: &'static str,
+) -> Result<Reader<'a>, ProverError> {
+    let i = sections
+        .iter()
+        .position(|(k, _)| *k == kind)
+        .ok_or_else(|| {
+            ProverError::InvalidInput(format!("{} file without section {}", file, kind))
+        })?;
+    Ok(sections.swap_remove(i).1)
+}
+
+impl R1cs {
+    /// Reads a `.r1cs` file.
+    pub fn read(bytes: &[u8]) -> Result<Self, ProverError> {
+        let file = "r1cs";
+        let mut sections = Reader { bytes, file }.sections(b"r1cs", 1)?;
+
+        let mut header = section(&mut sections, 1, file)?;
+        header.field()?;
+        let num_wires = header.usize()?;
+        let num_public_outputs = header.usize()?;
+        let num_public_inputs = header.usize()?;
+        let num_private_inputs = header.usize()?;
+        let _num_labels = header.u64()?;
+        let num_constraints = header.usize()?;
+        if num_wires < 1 + num_public_outputs + num_public_inputs + num_private_inputs {
+            return Err(header.error("fewer wires than signals"));
+        }
+
+        let mut body = section(&mut sections, 2, file)?;
+        let mut linear_combination = || -> Result<LinearCombination, ProverError> {
+            (0..body.u32()?)
+                .map(|_| {
+                    let wire = body.usize()?;
+                    if wire >= num_wires {
+                        return Err(body.error(&format!("wire {} out of range", wire)));
+                    }
+                    Ok((wire, body.fr()?))
+                })
+                .collect()
+        };
+        let constraints = (0..num_constraints)
+            .map(|_| {
+                Ok(Constraint {
+                    a: linear_combination()?,
+                    b: linear_combination()?,
+                    c: linear_combination()?,
+                })
+            })
+            .collect::<Result<_, ProverError>>()?;
+
+        Ok(R1cs {
+            num_wires,
+            num_public_outputs,
+            num_public_inputs,
+            num_private_inputs,
+            constraints,
+        })
+    }
+
+    /// Number of public signals, the outputs and the public inputs.
+    pub fn num_public(&self) -> usize {
+        self.num_public_outputs + self.num_public_inputs
+    }
+
+    /// The public signals of `witness`.
+    pub fn public_inputs(&self, witness: &[Fr]) -> Vec<Fr> {
+        witness[1..=self.num_public()].to_vec()
+    }
+
+    /// Checks that `witness` assigns every wire and satisfies every constraint.
+    pub fn check_witness(&self, witness: &[Fr]) -> Result<(), ProverError> {
+        if witness.len() != self.num_wires {
+            return Err(ProverError::InvalidInput(format!(
+                "expected a witness of {} wires, got {}",
+                self.num_wires,
+                witness.len()
+            )));
+        }
+        if witness[0] != Fr::ONE {
+            return Err(ProverError::InvalidInput(
+                "wire 0 of the witness must be 1".to_string(),
+            ));
+        }
+
+        let eval = |lc: &LinearCombination| -> Fr {
+            lc.iter().map(|&(wire, coeff)| witness[wire] * coeff).sum()
+        };
+        match self
+            .constraints
+            .iter()
+            .position(|c| eval(&c.a) * eval(&c.b) != eval(&c.c))
+        {
+            Some(i) => Err(ProverError::InvalidInput(format!(
+                "the witness does not satisfy constraint {}",
+                i
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of rows of [`CircomCircuit`], as laid out by its `synthesize`.
+    fn rows(&self) -> usize {
+        let lc_rows = |lc: &LinearCombination| match lc.first() {
+            None => 0,
+            Some(&(_, coeff)) => lc.len() - usize::from(coeff == Fr::ONE),
+        };
+        let constraint_rows = |c: &Constraint| {
+            let product = !c.a.is_empty() && !c.b.is_empty();
+            lc_rows(&c.a)
+                + lc_rows(&c.b)
+                + lc_rows(&c.c)
+                + usize::from(product)
+                + usize::from(product == c.c.is_empty())
+        };
+        self.num_wires + self.constraints.iter().map(constraint_rows).sum::<usize>()
+    }
+}
+
+/// Reads a `.wtns` file, returning the value of every wire.
+pub fn read_witness(bytes: &[u8]) -> Result<Vec<Fr>, ProverError> {
+    let file = "wtns";
+    let mut sections = Reader { bytes, file }.sections(b"wtns", 2)?;
+
+    let mut header = section(&mut sections, 1, file)?;
+    header.field()?;
+    let num_wires = header.usize()?;
+
+    let mut values = section(&mut sections, 2, file)?;
+    (0..num_wires).map(|_| values.fr()).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct CircomConfig {
+    plonk: StandardPlonkConfig,
+    instance: Column<Instance>,
+}
+
+#[derive(Clone)]
+pub struct CircomCircuit {
+    r1cs: Arc<R1cs>,
+    witness: Vec<Value<Fr>>,
+}
+
+impl CircomCircuit {
+    /// The circuit of `r1cs` with `witness`, which must satisfy it.
+    pub fn new(r1cs: Arc<R1cs>, witness: &[Fr]) -> Result<Self, ProverError> {
+        r1cs.check_witness(witness)?;
+        Ok(CircomCircuit {
+            witness: witness.iter().copied().map(Value::known).collect(),
+            r1cs,
+        })
+    }
+
+    /// The circuit of `r1cs` without a witness, for key generation.
+    pub fn empty(r1cs: Arc<R1cs>) -> Self {
+        CircomCircuit {
+            witness: vec![Value::unknown(); r1cs.num_wires],
+            r1cs,
+        }
+    }
+}
+
+/// `lc·w`, or `None` if `lc` is empty.
+fn linear_combination(
+    chip: &StandardPlonkChip<Fr>,
+    layouter: &mut impl Layouter<Fr>,
+    wires: &[AssignedCell<Fr, Fr>],
+    lc: &LinearCombination,
+) -> Result<Option<AssignedCell<Fr, Fr>>, Error> {
+    let mut terms = lc.iter();
+    let mut acc = match terms.next() {
+        None => return Ok(None),
+        Some(&(wire, coeff)) if coeff == Fr::ONE => wires[wire].clone(),
+        Some(&(wire, coeff)) => chip.mul_constant(layouter, &wires[wire], coeff)?,
+    };
+    for &(wire, coeff) in terms {
+        acc = chip.add_scaled(layouter, &acc, &wires[wire], coeff)?;
+    }
+    Ok(Some(acc))
+}
+
+impl Circuit<Fr> for CircomCircuit {
+    type Config = CircomConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::empty(self.r1cs.clone())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let advice = [(); 3].map(|_| meta.advice_column());
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+
+        CircomConfig {
+            plonk: StandardPlonkChip::configure(meta, advice),
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let chip = StandardPlonkChip::construct(config.plonk);
+        let layouter = &mut layouter;
+
+        let mut wires = vec![chip.load_constant(layouter, Fr::ONE)?];
+        for value in &self.witness[1..] {
+            wires.push(chip.load_private(layouter, *value)?);
+        }
+
+        for constraint in &self.r1cs.constraints {
+            let a = linear_combination(&chip, layouter, &wires, &constraint.a)?;
+            let b = linear_combination(&chip, layouter, &wires, &constraint.b)?;
+            let c = linear_combination(&chip, layouter, &wires, &constraint.c)?;
+            let product = match (a, b) {
+                (Some(a), Some(b)) => Some(chip.mul(layouter, &a, &b)?),
+                _ => None,
+            };
+            match (product, c) {
+                (Some(product), Some(c)) => chip.assert_equal(layouter, &product, &c)?,
+                (Some(zero), None) | (None, Some(zero)) => chip.assert_zero(layouter, &zero)?,
+                (None, None) => {}
+            }
+        }
+
+        for (row, wire) in wires[1..=self.r1cs.num_public()].iter().enumerate() {
+            layouter.constrain_instance(wire.cell(), config.instance, row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Smallest `k` whose params fit the circuit of `r1cs`.
+pub fn circom_k(r1cs: &R1cs) -> u32 {
+    let mut meta = ConstraintSystem::<Fr>::default();
+    CircomCircuit::configure(&mut meta);
+    let rows = r1cs.rows() + meta.minimum_rows();
+    rows.next_power_of_two().trailing_zeros()
+}
+
+/// Proves that `witness` satisfies `r1cs`, generating the keys from `params`. The public
+/// inputs of the proof are [`R1cs::public_inputs`].
+pub fn prove_circom(
+    params: &ParamsKZG<Bn256>,
+    r1cs: Arc<R1cs>,
+    witness: &[Fr],
+    scheme: MultiOpenScheme,
+    transcript: TranscriptHash,
+) -> Result<Vec<u8>, ProverError> {
+    let circuit = CircomCircuit::new(r1cs.clone(), witness)?;
+    let public_inputs = r1cs.public_inputs(witness);
+
+    let keygen_failed = |e: Error| ProverError::KeygenFailed(e.to_string());
+    let empty = circuit.without_witnesses();
+    let vk = keygen_vk(params, &empty).map_err(keygen_failed)?;
+    let pk = keygen_pk(params, vk, &empty).map_err(keygen_failed)?;
+    prove(params, &pk, circuit, &[&public_inputs], scheme, transcript)
+}
+
+/// Verifies a proof of [`prove_circom`] against the public signals of `r1cs`.
+pub fn verify_circom(
+    params: &ParamsVerifierKZG<Bn256>,
+    r1cs: Arc<R1cs>,
+    proof: &[u8],
+    public_inputs: &[Fr],
+    scheme: MultiOpenScheme,
+    transcript: TranscriptHash,
+) -> Result<(), ProverError> {
+    if public_inputs.len() != r1cs.num_public() {
+        return Err(ProverError::InvalidInput(format!(
+            "expected {} public signals, got {}",
+            r1cs.num_public(),
+            public_inputs.len()
+        )));
+    }
+    let vk = keygen_vk(params, &CircomCircuit::empty(r1cs))
+        .map_err(|e| ProverError::KeygenFailed(e.to_string()))?;
+    verify(params, &vk, proof, &[public_inputs], scheme, transcript)
+        .map_err(|e| ProverError::VerificationFailed(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::dev::MockProver;
+
+    use super::*;
+    use crate::utils::generate_params;
+
+    fn section(kind: u32, content: Vec<u8>) -> Vec<u8> {
+        let mut bytes = kind.to_le_bytes().to_vec();
+        bytes.extend((content.len() as u64).to_le_bytes());
+        bytes.extend(content);
+        bytes
+    }
+
+    fn file(magic: &[u8; 4], version: u32, sections: Vec<Vec<u8>>) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend((sections.len() as u32).to_le_bytes());
+        bytes.extend(sections.concat());
+        bytes
+    }
+
+    fn field() -> Vec<u8> {
+        let mut bytes = 32u32.to_le_bytes().to_vec();
+        let modulus = BigUint::from_str_radix(Fr::MODULUS.trim_start_matches("0x"), 16).unwrap();
+        bytes.extend(modulus.to_bytes_le());
+        bytes
+    }
+
+    fn write_r1cs(r1cs: &R1cs) -> Vec<u8> {
+        let mut header = field();
+        for n in [
+            r1cs.num_wires,
+            r1cs.num_public_outputs,
+            r1cs.num_public_inputs,
+            r1cs.num_private_inputs,
+        ] {
+            header.extend((n as u32).to_le_bytes());
+        }
+        header.extend((r1cs.num_wires as u64).to_le_bytes());
+        header.extend((r1cs.constraints.len() as u32).to_le_bytes());
+
+        let mut constraints = vec![];
+        for c in &r1cs.constraints {
+            for lc in [&c.a, &c.b, &c.c] {
+                constraints.extend((lc.len() as u32).to_le_bytes());
+                for (wire, coeff) in lc {
+                    constraints.extend((*wire as u32).to_le_bytes());
+                    constraints.extend(coeff.to_repr().as_ref());
+                }
+            }
+        }
+
+        let labels = (0..r1cs.num_wires as u64)
+            .flat_map(u64::to_le_bytes)
+            .collect();
+        file(
+            b"r1cs",
+            1,
+            vec![
+                section(1, header),
+                section(3, labels),
+                section(2, constraints),
+            ],
+        )
+    }
+
+    fn write_witness(witness: &[Fr]) -> Vec<u8> {
+        let mut header = field();
+        header.extend((witness.len() as u32).to_le_bytes());
+        let values = witness.iter().flat_map(|w| w.to_repr()).collect();
+        file(b"wtns", 2, vec![section(1, header), section(2, values)])
+    }
+
+    /// `out = a·b + c` with `3a = 9`, on the wires `[1, out, a, b, c, a·b]` where `out` is
+    /// the public output and `a` the public input.
+    fn example() -> (R1cs, Vec<Fr>) {
+        let one = Fr::ONE;
+        let r1cs = R1cs {
+            num_wires: 6,
+            num_public_outputs: 1,
+            num_public_inputs: 1,
+            num_private_inputs: 2,
+            constraints: vec![
+                Constraint {
+                    a: vec![(2, one)],
+                    b: vec![(3, one)],
+                    c: vec![(5, one)],
+                },
+                Constraint {
+                    a: vec![(5, one), (4, one)],
+                    b: vec![(0, one)],
+                    c: vec![(1, one)],
+                },
+                Constraint {
+                    a: vec![],
+                    b: vec![],
+                    c: vec![(2, Fr::from(3)), (0, -Fr::from(9))],
+                },
+            ],
+        };
+        let witness = [1, 17, 3, 4, 5, 12].map(Fr::from).to_vec();
+        (r1cs, witness)
+    }
+
+    #[test]
+    fn reads_circom_files() {
+        let (r1cs, witness) = example();
+        assert_eq!(R1cs::read(&write_r1cs(&r1cs)).unwrap(), r1cs);
+        assert_eq!(read_witness(&write_witness(&witness)).unwrap(), witness);
+        assert!(r1cs.check_witness(&witness).is_ok());
+        assert_eq!(r1cs.public_inputs(&witness), [17, 3].map(Fr::from));
+
+        let bytes = write_r1cs(&r1cs);
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'x';
+        let mut out_of_range = r1cs.clone();
+        out_of_range.constraints[0].a[0].0 = 6;
+        for invalid in [
+            bytes[..bytes.len() - 1].to_vec(),
+            wrong_magic,
+            write_r1cs(&out_of_range),
+            write_witness(&witness),
+        ] {
+            assert!(matches!(
+                R1cs::read(&invalid),
+                Err(ProverError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn unsatisfying_witness_is_an_error() {
+        let (r1cs, witness) = example();
+        for (wire, value) in [(0, 2), (1, 18), (2, 4)] {
+            let mut wrong = witness.clone();
+            wrong[wire] = Fr::from(value);
+            assert!(matches!(
+                r1cs.check_witness(&wrong),
+                Err(ProverError::InvalidInput(_))
+            ));
+        }
+        assert!(r1cs.check_witness(&witness[1..]).is_err());
+    }
+
+    #[test]
+    fn circuit_matches_r1cs() {
+        let (r1cs, witness) = example();
+        let k = circom_k(&r1cs);
+        let public_inputs = r1cs.public_inputs(&witness);
+        let circuit = CircomCircuit::new(Arc::new(r1cs), &witness).unwrap();
+
+        MockProver::run(k, &circuit, vec![public_inputs.clone()])
+            .unwrap()
+            .assert_satisfied();
+        for i in 0..public_inputs.len() {
+            let mut wrong = public_inputs.clone();
+            wrong[i] += Fr::ONE;
+            let prover = MockProver::run(k, &circuit, vec![wrong]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn prove_and_verify() {
+        let (r1cs, witness) = example();
+        let r1cs = Arc::new(R1cs::read(&write_r1cs(&r1cs)).unwrap());
+        let witness = read_witness(&write_witness(&witness)).unwrap();
+        let params = generate_params(circom_k(&r1cs));
+
+        let (scheme, transcript) = (MultiOpenScheme::Gwc, TranscriptHash::Poseidon);
+        let proof = prove_circom(&params, r1cs.clone(), &witness, scheme, transcript).unwrap();
+        let public_inputs = r1cs.public_inputs(&witness);
+        assert!(verify_circom(
+            &params,
+            r1cs.clone(),
+            &proof,
+            &public_inputs,
+            scheme,
+            transcript
+        )
+        .is_ok());
+        assert!(verify_circom(
+            &params,
+            r1cs.clone(),
+            &proof,
+            &public_inputs,
+            MultiOpenScheme::Shplonk,
+            transcript
+        )
+        .is_err());
+
+        let other = [Fr::from(18), Fr::from(3)];
+        assert!(matches!(
+            verify_circom(&params, r1cs, &proof, &other, scheme, transcript),
+            Err(ProverError::VerificationFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::{bn256::Fr, ff::Field},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use std::convert::TryInto;
+
+use crate::poseidon::{
+    primitives::{generate_constants, Mds, Spec},
+    PoseidonInstructions, Pow5Chip, Pow5Config,
+};
+
+/// Partial rounds of circomlib's Poseidon, indexed by `t - 2`.
+pub(crate) const N_ROUNDS_P: [usize; 16] = [
+    56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
+];
+
+/// circomlib's Poseidon over BN254 with width `T` in `2..=17`, hashing `RATE = T - 1`
+/// inputs.
+///
+/// circomlib generated its round constants and MDS matrices with the Grain LFSR of the
+/// reference implementation, taking the first MDS matrix, which is what
+/// [`generate_constants`] reproduces with `secure_mds() == 0`.
+#[derive(Debug, Clone, Copy)]
+pub struct CircomPoseidonSpec<const T: usize, const RATE: usize>;
+
+impl<const T: usize, const RATE: usize> Spec<Fr, T, RATE> for CircomPoseidonSpec<T, RATE> {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        N_ROUNDS_P[T - 2]
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+
+    fn constants() -> (Vec<[Fr; T]>, Mds<Fr, T>, Mds<Fr, T>) {
+        generate_constants::<_, Self, T, RATE>()
+    }
+}
+
+/// circomlib's `poseidon(message)`: the first word of the permutation of `[0, message..]`.
+///
+/// This is not the halo2 [`ConstantLength`](crate::poseidon::primitives::ConstantLength)
+/// hash, which puts the capacity word last and sets it to a domain tag.
+pub fn circom_poseidon<const T: usize, const RATE: usize>(message: [Fr; RATE]) -> Fr {
+    let (round_constants, mds, _) = CircomPoseidonSpec::<T, RATE>::constants();
+    let half_full = CircomPoseidonSpec::<T, RATE>::full_rounds() / 2;
+    let partial = CircomPoseidonSpec::<T, RATE>::partial_rounds();
+    let sbox = CircomPoseidonSpec::<T, RATE>::sbox;
+
+    let mut state = [Fr::ZERO; T];
+    state[1..].copy_from_slice(&message);
+    for (round, rc) in round_constants.iter().enumerate() {
+        for (word, c) in state.iter_mut().zip(rc) {
+            *word += c;
+        }
+        if round < half_full || round >= half_full + partial {
+            state.iter_mut().for_each(|word| *word = sbox(*word));
+        } else {
+            state[0] = sbox(state[0]);
+        }
+
+        let mut mixed = [Fr::ZERO; T];
+        for (out, row) in mixed.iter_mut().zip(mds.iter()) {
+            *out = row.iter().zip(state.iter()).map(|(m, s)| *m * s).sum();
+        }
+        state = mixed;
+    }
+    state[0]
+}
+
+#[derive(Debug, Clone)]
+pub struct CircomPoseidonConfig<const T: usize, const RATE: usize> {
+    state: [Column<Advice>; T],
+    poseidon_config: Pow5Config<Fr, T, RATE>,
+}
+
+impl<const T: usize, const RATE: usize> CircomPoseidonConfig<T, RATE> {
+    pub fn configure(meta: &mut ConstraintSystem<Fr>) -> Self {
+        let state: [Column<Advice>; T] = (0..T)
+            .map(|_| meta.advice_column())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let partial_sbox = meta.advice_column();
+
+        let rc_a = (0..T).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+        let rc_b = (0..T).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+
+        // Fixes the capacity word to zero.
+        meta.enable_constant(rc_b[0]);
+
+        Self {
+            state,
+            poseidon_config: Pow5Chip::configure::<CircomPoseidonSpec<T, RATE>>(
+                meta,
+                state,
+                partial_sbox,
+                rc_a.try_into().unwrap(),
+                rc_b.try_into().unwrap(),
+            ),
+        }
+    }
+
+    /// Columns of the message, usable to load it.
+    pub fn message_columns(&self) -> &[Column<Advice>] {
+        &self.state[1..]
+    }
+}
+
+/// Constrains circomlib's Poseidon hash of assigned words.
+pub struct CircomPoseidonChip<const T: usize, const RATE: usize> {
+    config: CircomPoseidonConfig<T, RATE>,
+}
+
+impl<const T: usize, const RATE: usize> CircomPoseidonChip<T, RATE> {
+    pub fn construct(config: CircomPoseidonConfig<T, RATE>) -> Self {
+        Self { config }
+    }
+
+    pub fn hash(
+        &self,
+        mut layouter: impl Layouter<Fr>,
+        message: [AssignedCell<Fr, Fr>; RATE],
+    ) -> Result<AssignedCell<Fr, Fr>, Error> {
+        let initial_state: [AssignedCell<Fr, Fr>; T] = layouter.assign_region(
+            || "initial state",
+            |mut region| {
+                let capacity = region.assign_advice_from_constant(
+                    || "capacity",
+                    self.config.state[0],
+                    0,
+                    Fr::ZERO,
+                )?;
+                let mut state = vec![capacity];
+                for (i, word) in message.iter().enumerate() {
+                    let word = word.copy_advice(
+                        || format!("message_{}", i),
+                        &mut region,
+                        self.config.state[i + 1],
+                        0,
+                    )?;
+                    state.push(word);
+                }
+                Ok(state.try_into().unwrap())
+            },
+        )?;
+
+        let chip = Pow5Chip::construct(self.config.poseidon_config.clone());
+        let state = <Pow5Chip<Fr, T, RATE> as PoseidonInstructions<
+            Fr,
+            CircomPoseidonSpec<T, RATE>,
+            T,
+            RATE,
+        >>::permute(
+            &chip,
+            &mut layouter.namespace(|| "permute"),
+            &initial_state.map(Into::into),
+        )?;
+        Ok(state[0].clone().into())
+    }
+}
+
+/// Proves knowledge of a message whose circomlib Poseidon hash is the public input.
+#[derive(Clone, Copy)]
+pub struct CircomPoseidonCircuit<const T: usize, const RATE: usize> {
+    pub message: Value<[Fr; RATE]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircomPoseidonCircuitConfig<const T: usize, const RATE: usize> {
+    hash: CircomPoseidonConfig<T, RATE>,
+    expected: Column<Instance>,
+}
+
+impl<const T: usize, const RATE: usize> Circuit<Fr> for CircomPoseidonCircuit<T, RATE> {
+    type Config = CircomPoseidonCircuitConfig<T, RATE>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            message: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let expected = meta.instance_column();
+        meta.enable_equality(expected);
+        Self::Config {
+            hash: CircomPoseidonConfig::configure(meta),
+            expected,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let message = layouter.assign_region(
+            || "load message",
+            |mut region| {
+                let message_word = |i: usize| {
+                    region.assign_advice(
+                        || format!("load message_{}", i),
+                        config.hash.message_columns()[i],
+                        0,
+                        || self.message.map(|message| message[i]),
+                    )
+                };
+                let message: Result<Vec<_>, Error> = (0..RATE).map(message_word).collect();
+                Ok(message?.try_into().unwrap())
+            },
+        )?;
+
+        let chip = CircomPoseidonChip::construct(config.hash);
+        let output = chip.hash(layouter.namespace(|| "hash"), message)?;
+        layouter.constrain_instance(output.cell(), config.expected, 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{circom_poseidon, CircomPoseidonCircuit, CircomPoseidonSpec, N_ROUNDS_P};
+    use crate::{
+        constants::constants,
+        poseidon::primitives::{round_numbers, Spec},
+        unstringify::unstringifyHex,
+        utils::hex_to_fr,
+    };
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{
+            bn256::Fr,
+            ff::{Field, PrimeField},
+        },
+    };
+
+    /// Outputs of circomlibjs' `poseidon`.
+    fn circomlibjs_vectors() -> [(Vec<u64>, &'static str); 3] {
+        [
+            (
+                vec![1],
+                "0x29176100eaa962bdc1fe6c654d6a3c130e96a4d1168b33848b897dc502820133",
+            ),
+            (
+                vec![1, 2],
+                "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a",
+            ),
+            (
+                vec![1, 2, 3, 4],
+                "0x299c867db6c1fdd79dcefa40e4510b9837e60ebb1ce0663dbaa525df65250465",
+            ),
+        ]
+    }
+
+    fn message<const RATE: usize>(words: &[u64]) -> [Fr; RATE] {
+        let message: Vec<Fr> = words.iter().map(|word| Fr::from(*word)).collect();
+        message.try_into().unwrap()
+    }
+
+    #[test]
+    fn constants_match_circomlib() {
+        let (c, m) = constants();
+        let (round_constants, mds, _) = CircomPoseidonSpec::<2, 1>::constants();
+        let from_circomlib =
+            |s: &&str| Fr::from_str_vartime(&unstringifyHex(s)).expect("constant is in the field");
+
+        let expected: Vec<Fr> = c.iter().map(from_circomlib).collect();
+        assert_eq!(round_constants.concat(), expected);
+        for (row, expected) in mds.iter().zip(m.iter()) {
+            let expected: Vec<Fr> = expected.iter().map(from_circomlib).collect();
+            assert_eq!(row.to_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn partial_rounds_match_security_formulas() {
+        // circomlib rounds the partial rounds up to a multiple of the width.
+        for (i, partial_rounds) in N_ROUNDS_P.iter().enumerate() {
+            let t = i + 2;
+            let (r_f, r_p) = round_numbers(Fr::NUM_BITS, t, 5, 128);
+            assert_eq!(r_f, 8);
+            assert_eq!(*partial_rounds, (r_p + t - 1) / t * t);
+        }
+    }
+
+    #[test]
+    fn native_hash_matches_circomlibjs() {
+        let [(one, one_hash), (two, two_hash), (four, four_hash)] = circomlibjs_vectors();
+        assert_eq!(
+            circom_poseidon::<2, 1>(message(&one)),
+            hex_to_fr(one_hash).unwrap()
+        );
+        assert_eq!(
+            circom_poseidon::<3, 2>(message(&two)),
+            hex_to_fr(two_hash).unwrap()
+        );
+        assert_eq!(
+            circom_poseidon::<5, 4>(message(&four)),
+            hex_to_fr(four_hash).unwrap()
+        );
+    }
+
+    #[test]
+    fn circuit_matches_circomlibjs() {
+        let [_, (two, two_hash), (four, four_hash)] = circomlibjs_vectors();
+
+        // 57 partial rounds.
+        assert_eq!(CircomPoseidonSpec::<3, 2>::partial_rounds() % 2, 1);
+        let circuit = CircomPoseidonCircuit::<3, 2> {
+            message: Value::known(message(&two)),
+        };
+        let expected = hex_to_fr(two_hash).unwrap();
+        MockProver::run(7, &circuit, vec![vec![expected]])
+            .unwrap()
+            .assert_satisfied();
+        let prover = MockProver::run(7, &circuit, vec![vec![expected + Fr::ONE]]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let circuit = CircomPoseidonCircuit::<5, 4> {
+            message: Value::known(message(&four)),
+        };
+        MockProver::run(7, &circuit, vec![vec![hex_to_fr(four_hash).unwrap()]])
+            .unwrap()
+            .assert_satisfied();
+    }
+}
@@ -1,172 +1,338 @@
 use halo2_proofs::{
-    circuit::{AssignedCell, Cell, Layouter, SimpleFloorPlanner, Value},
-    halo2curves::{bn256::Fr, ff::PrimeField},
-    plonk::{Advice, Assigned, Circuit, Column, ConstraintSystem, Error, Expression, Selector},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::{
+        bn256::Fr,
+        ff::{Field, PrimeField},
+    },
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector,
+        TableColumn,
+    },
     poly::Rotation,
 };
-use serde::{Deserialize, Serialize};
-use std::marker::PhantomData;
+use num::{BigUint, Integer, One};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{array, marker::PhantomData};
+
+use crate::{
+    error::ProverError,
+    registry::DemoCircuit,
+    utils::{parse_encoding, FieldElement},
+};
+
+/// Bits of each limb of `x / 2`, range checked with a lookup. Limbs are the bytes of the
+/// little-endian repr.
+const LIMB_BITS: usize = 8;
+
+/// Limbs of the circuit over `u64` starting numbers.
+pub const DEFAULT_LIMBS: usize = 8;
+
+/// Limbs of the circuit over 128-bit starting numbers, whose trajectories can grow well
+/// past 128 bits.
+pub const WIDE_LIMBS: usize = 31;
+
+/// Bit-width that entries are range checked to, with `NUM_LIMBS` limbs for `x / 2`.
+pub const fn entry_bits(num_limbs: usize) -> usize {
+    LIMB_BITS * num_limbs + 1
+}
 
 #[derive(Clone, Copy, Debug)]
-pub struct CollatzConfig {
+pub struct CollatzConfig<const NUM_LIMBS: usize> {
     witness: Column<Advice>,
-    // Normally, you would use `Selector` instead
     is_odd: Column<Advice>,
+    /// Limbs of `(x - is_odd) / 2`, least significant first.
+    limbs: [Column<Advice>; NUM_LIMBS],
     is_one: Column<Advice>,
+    /// Inverse of `x - 1`, or 0 when `x` is 1.
+    inv: Column<Advice>,
+    /// Steps left before the sequence reaches 1.
+    steps_left: Column<Advice>,
+    /// The starting number followed by the number of steps.
+    instance: Column<Instance>,
+    /// All values of a limb.
+    table: TableColumn,
     selector: Selector,
     final_entry: Selector,
 }
 
+/// Steps proven when the input does not set `max_steps`. Every start below 10000 reaches 1
+/// within this many steps.
+pub const DEFAULT_MAX_STEPS: usize = 261;
+
+fn default_max_steps() -> usize {
+    DEFAULT_MAX_STEPS
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CollatzInput {
-    pub x: Vec<u64>,
+    /// Starting number of the sequence, as a JSON number or a string read as a field element.
+    #[serde(
+        serialize_with = "serialize_biguint",
+        deserialize_with = "deserialize_biguint"
+    )]
+    pub n: BigUint,
+    /// Number of steps before the sequence reaches 1. Computed from `n` when missing;
+    /// a verifier sets it to the claimed step count.
+    #[serde(default)]
+    pub steps: Option<usize>,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: usize,
+}
+
+fn serialize_biguint<S: Serializer>(n: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&n.to_string())
+}
+
+/// JSON numbers cannot hold 128-bit values, so larger numbers are passed as strings.
+fn deserialize_biguint<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+    let FieldElement(n) = FieldElement::deserialize(deserializer)?;
+    Ok(BigUint::from_bytes_le(n.to_repr().as_ref()))
+}
+
+/// The fields of [`CollatzInput`] that determine the number of rows of the circuit.
+#[derive(Serialize, Deserialize)]
+pub struct CollatzShape {
+    #[serde(default = "default_max_steps")]
+    pub max_steps: usize,
 }
 
-impl CollatzConfig {
+impl<const NUM_LIMBS: usize> CollatzConfig<NUM_LIMBS> {
     pub fn configure<F: PrimeField>(meta: &mut ConstraintSystem<F>) -> Self {
+        // 3x + 1 must not wrap around the field.
+        assert!(entry_bits(NUM_LIMBS) + 2 < F::NUM_BITS as usize);
+
         // create witness column
         let witness = meta.advice_column();
         let is_odd = meta.advice_column();
+        let limbs = array::from_fn(|_| meta.advice_column());
         let is_one = meta.advice_column();
+        let inv = meta.advice_column();
+        let steps_left = meta.advice_column();
+        let instance = meta.instance_column();
+        let table = meta.lookup_table_column();
         let final_entry = meta.selector();
-        let selector = meta.selector();
+        let selector = meta.complex_selector();
 
         meta.enable_equality(witness);
+        meta.enable_equality(steps_left);
+        meta.enable_equality(instance);
+
+        for limb in limbs {
+            meta.lookup("limb range", |meta| {
+                let limb = meta.query_advice(limb, Rotation::cur());
+                let sel = meta.query_selector(selector);
+                vec![(sel * limb, table)]
+            });
+        }
 
-        meta.create_gate("is_even", |meta| {
-            let x = meta.query_advice(witness, Rotation::cur());
-            let y = meta.query_advice(witness, Rotation::next());
-
-            let is_odd = meta.query_advice(is_odd, Rotation::cur());
-            let sel = meta.query_selector(selector);
-
-            vec![
-                sel * ((Expression::Constant(F::ONE) - is_odd)
-                    * (x - Expression::Constant(F::from(2)) * y)),
-            ]
-        });
-
-        meta.create_gate("is_odd", |meta| {
+        meta.create_gate("collatz step", |meta| {
             let x = meta.query_advice(witness, Rotation::cur());
             let y = meta.query_advice(witness, Rotation::next());
-
             let is_odd = meta.query_advice(is_odd, Rotation::cur());
             let is_one = meta.query_advice(is_one, Rotation::cur());
+            let inv = meta.query_advice(inv, Rotation::cur());
             let sel = meta.query_selector(selector);
 
+            // (x - is_odd) / 2, bounded by the range check of its limbs.
+            let half = limbs
+                .iter()
+                .rev()
+                .fold(Expression::Constant(F::ZERO), |acc, limb| {
+                    acc * Expression::Constant(F::from(1 << LIMB_BITS))
+                        + meta.query_advice(*limb, Rotation::cur())
+                });
+            let one = Expression::Constant(F::ONE);
+            let x_minus_one = x.clone() - one.clone();
+            let next = is_odd.clone()
+                * (Expression::Constant(F::from(3)) * x.clone() + one.clone())
+                + (one.clone() - is_odd.clone()) * half.clone();
+
             vec![
-                sel * (Expression::Constant(F::ONE) - is_one)
-                    * (is_odd
-                        * (Expression::Constant(F::from(3)) * x
-                            + Expression::Constant(F::from(1))
-                            - y)),
+                // is_odd is the parity of x
+                is_odd.clone() * (one.clone() - is_odd.clone()),
+                x.clone() - Expression::Constant(F::from(2)) * half - is_odd,
+                // is_one is 1 exactly when x == 1
+                is_one.clone() * (one.clone() - is_one.clone()),
+                is_one.clone() - (one.clone() - x_minus_one.clone() * inv),
+                x_minus_one * is_one.clone(),
+                // y is the next entry, or 1 again once the sequence has reached 1
+                (one - is_one.clone()) * (y.clone() - next),
+                is_one * (y - x),
             ]
+            .into_iter()
+            .map(|constraint| sel.clone() * constraint)
+            .collect::<Vec<_>>()
         });
 
-        meta.create_gate("is_one", |meta| {
-            let x = meta.query_advice(witness, Rotation::cur());
-            let y = meta.query_advice(witness, Rotation::next());
+        // Every entry other than 1 counts as a step.
+        meta.create_gate("count_steps", |meta| {
+            let left = meta.query_advice(steps_left, Rotation::cur());
+            let left_next = meta.query_advice(steps_left, Rotation::next());
 
             let is_one = meta.query_advice(is_one, Rotation::cur());
             let sel = meta.query_selector(selector);
-            vec![sel * is_one * ((x.clone() - y) + (x.clone() - Expression::Constant(F::ONE)))]
+            vec![sel * (left - left_next - (Expression::Constant(F::ONE) - is_one))]
         });
 
         meta.create_gate("final_element", |meta| {
             let x = meta.query_advice(witness, Rotation::cur());
+            let left = meta.query_advice(steps_left, Rotation::cur());
             let sel = meta.query_selector(final_entry);
-            vec![sel * (Expression::Constant(F::from(1)) - x)]
+            vec![
+                sel.clone() * (Expression::Constant(F::from(1)) - x),
+                sel * left,
+            ]
         });
 
         Self {
             witness,
             is_odd,
+            limbs,
             is_one,
+            inv,
+            steps_left,
+            instance,
+            table,
             selector,
             final_entry,
         }
     }
 }
-pub struct CollatzChip<F: PrimeField> {
-    config: CollatzConfig,
+
+/// Witness of one entry of the sequence.
+#[derive(Clone, Copy, Debug)]
+struct CollatzStep<F, const NUM_LIMBS: usize> {
+    x: Value<F>,
+    is_odd: Value<F>,
+    limbs: [Value<F>; NUM_LIMBS],
+    is_one: Value<F>,
+    inv: Value<F>,
+}
+
+impl<F: PrimeField, const NUM_LIMBS: usize> CollatzStep<F, NUM_LIMBS> {
+    fn new(x: Value<F>) -> Self {
+        let is_odd = x.map(|x| F::from(x.is_odd().unwrap_u8() as u64));
+        let half = (x - is_odd).map(|h| h * F::TWO_INV);
+        let limbs = array::from_fn(|i| half.map(|h| F::from(h.to_repr().as_ref()[i] as u64)));
+        Self {
+            x,
+            is_odd,
+            limbs,
+            is_one: x.map(|x| F::from((x - F::ONE).is_zero().unwrap_u8() as u64)),
+            inv: x.map(|x| (x - F::ONE).invert().unwrap_or(F::ZERO)),
+        }
+    }
+}
+
+pub struct CollatzChip<F: PrimeField, const NUM_LIMBS: usize> {
+    config: CollatzConfig<NUM_LIMBS>,
     marker: PhantomData<F>,
 }
 
-impl<F: PrimeField> CollatzChip<F> {
-    pub fn new(config: CollatzConfig) -> Self {
+impl<F: PrimeField, const NUM_LIMBS: usize> CollatzChip<F, NUM_LIMBS> {
+    pub fn new(config: CollatzConfig<NUM_LIMBS>) -> Self {
         Self {
             config,
             marker: PhantomData,
         }
     }
 
+    fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "limb values",
+            |mut table| {
+                for value in 0..1 << LIMB_BITS {
+                    table.assign_cell(
+                        || "limb",
+                        self.config.table,
+                        value,
+                        || Value::known(F::from(value as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Assigns the whole sequence in one region, so that each entry is both the `y` of
+    /// the previous step and the `x` of the next one.
+    ///
+    /// Returns the cells of the starting number and of the number of steps.
     fn assign(
         &self,
         mut layouter: impl Layouter<F>,
-        row: usize,
-        entry: Value<Assigned<F>>,
-        next: Value<Assigned<F>>,
-        is_odd: Value<Assigned<F>>,
-        is_one: Value<Assigned<F>>,
-    ) -> Result<
-        (
-            AssignedCell<Assigned<F>, F>,
-            AssignedCell<Assigned<F>, F>,
-            AssignedCell<Assigned<F>, F>,
-        ),
-        halo2_proofs::plonk::Error,
-    > {
+        steps: &[CollatzStep<F, NUM_LIMBS>],
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let last = steps.len() - 1;
+        let mut steps_left = vec![Value::known(F::ZERO); steps.len()];
+        for row in (0..last).rev() {
+            steps_left[row] = steps_left[row + 1] + steps[row].is_one.map(|b| F::ONE - b);
+        }
+
         layouter.assign_region(
-            || format!("entry_{}", row),
+            || "collatz sequence",
             |mut region| {
-                self.config.selector.enable(&mut region, row)?;
-
-                let x = region.assign_advice(|| "x", self.config.witness, row, || entry)?;
-                let y = region.assign_advice(|| "y", self.config.witness, row + 1, || next)?;
-
-                let is_odd_cell =
-                    region.assign_advice(|| "sel", self.config.is_odd, row, || is_odd)?;
-                let is_one_cell =
-                    region.assign_advice(|| "sel", self.config.is_one, row, || is_one)?;
-
-                Ok((x, y, is_odd_cell))
+                let mut start = None;
+                let mut count = None;
+                for (row, step) in steps.iter().enumerate() {
+                    if row == last {
+                        self.config.final_entry.enable(&mut region, row)?;
+                    } else {
+                        self.config.selector.enable(&mut region, row)?;
+                    }
+
+                    let x = region.assign_advice(|| "x", self.config.witness, row, || step.x)?;
+                    region.assign_advice(|| "is_odd", self.config.is_odd, row, || step.is_odd)?;
+                    for (column, limb) in self.config.limbs.iter().zip(step.limbs) {
+                        region.assign_advice(|| "limb", *column, row, || limb)?;
+                    }
+                    region.assign_advice(|| "is_one", self.config.is_one, row, || step.is_one)?;
+                    region.assign_advice(|| "inv", self.config.inv, row, || step.inv)?;
+                    let left = region.assign_advice(
+                        || "steps_left",
+                        self.config.steps_left,
+                        row,
+                        || steps_left[row],
+                    )?;
+                    if row == 0 {
+                        start = Some(x);
+                        count = Some(left);
+                    }
+                }
+                Ok((start.unwrap(), count.unwrap()))
             },
         )
     }
 
-    fn assign_last(
-        &mut self,
+    fn expose_public(
+        &self,
         mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
         row: usize,
-        entry: Value<Assigned<F>>,
-    ) -> Result<Cell, Error> {
-        layouter.assign_region(
-            || "final output",
-            |mut region| {
-                let a = region.assign_advice(|| "out", self.config.witness, row, || entry)?;
-                let _ = self.config.final_entry.enable(&mut region, row);
-
-                Ok(a.cell())
-            },
-        )
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
     }
 }
 
+/// A Collatz sequence of `max_steps + 1` entries, padded with 1s once it reaches 1. Every
+/// entry is range checked to [`entry_bits`]`(NUM_LIMBS)` bits.
 #[derive(Clone, Default)]
-pub struct CollatzCircuit<F: PrimeField> {
-    pub x: [Value<F>; 32],
+pub struct CollatzCircuit<F: PrimeField, const NUM_LIMBS: usize = DEFAULT_LIMBS> {
+    pub x: Vec<Value<F>>,
 }
 
-impl<F: PrimeField> Circuit<F> for CollatzCircuit<F> {
-    type Config = CollatzConfig;
+impl<F: PrimeField, const NUM_LIMBS: usize> Circuit<F> for CollatzCircuit<F, NUM_LIMBS> {
+    type Config = CollatzConfig<NUM_LIMBS>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            x: vec![Value::unknown(); self.x.len()],
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        CollatzConfig::configure::<F>(meta)
+        CollatzConfig::configure(meta)
     }
 
     fn synthesize(
@@ -174,113 +340,420 @@ impl<F: PrimeField> Circuit<F> for CollatzCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let mut chip: CollatzChip<F> = CollatzChip::new(config);
-        let nrows = self.x.len();
-
-        for row in 0..(nrows - 1) {
-            let s = format!("Collatz({})", row);
-            let is_odd: Value<Assigned<F>> = self.x[row]
-                .map(|k| F::from(k.is_odd().unwrap_u8() as u64))
-                .into();
-
-            let is_one: Value<Assigned<F>> = self.x[row]
-                .map(|k| F::from((k - F::ONE).is_zero().unwrap_u8() as u64))
-                .into();
-
-            let (_contents, next, is_odd) = chip.assign(
-                layouter.namespace(|| s),
-                row,
-                self.x[row].into(),
-                self.x[row + 1].into(),
-                is_odd,
-                is_one,
-            )?;
+        if self.x.is_empty() {
+            return Err(Error::Synthesis);
         }
-
-        let _ = chip.assign_last(
-            layouter.namespace(|| "out"),
-            nrows - 1,
-            self.x[nrows - 1].into(),
-        )?;
-
-        Ok(())
+        let chip = CollatzChip::new(config);
+        chip.load_table(layouter.namespace(|| "table"))?;
+        let steps: Vec<_> = self.x.iter().map(|x| CollatzStep::new(*x)).collect();
+        let (start, count) = chip.assign(layouter.namespace(|| "sequence"), &steps)?;
+        chip.expose_public(layouter.namespace(|| "start"), &start, 0)?;
+        chip.expose_public(layouter.namespace(|| "steps"), &count, 1)
     }
 }
 
 // Utils
 
-pub fn collatz_conjecture(mut n: u64) -> Vec<u64> {
-    let mut ans = vec![];
-    ans.push(n);
-    let mut i = 1;
+/// The Collatz sequence of `n`, stopping when it reaches 1 or after `max_steps` steps.
+pub fn collatz_conjecture(n: impl Into<BigUint>, max_steps: usize) -> Vec<BigUint> {
+    let mut n = n.into();
+    let mut ans = vec![n.clone()];
 
-    while n > 1 && i < 32 {
-        if n & 1 > 0 {
-            n = 3 * n + 1;
+    while n > BigUint::one() && ans.len() <= max_steps {
+        if n.is_odd() {
+            n = n * 3u32 + 1u32;
         } else {
-            n /= 2;
+            n >>= 1;
         }
-        ans.push(n);
-        i += 1;
+        ans.push(n.clone());
     }
-    ans.resize(32, 1);
     ans
 }
 
-pub fn create_circuit(a: Vec<u64>) -> CollatzCircuit<Fr> {
-    let x: [Value<Fr>; 32] = a
-        .clone()
-        .iter()
-        .map(|f| Value::known(Fr::from(*f)))
-        .collect::<Vec<Value<Fr>>>()
-        .try_into()
-        .unwrap();
+/// Smallest `k` whose params fit a sequence of `max_steps` steps and the limb table.
+pub fn collatz_k(max_steps: usize) -> u32 {
+    let mut meta = ConstraintSystem::<Fr>::default();
+    CollatzConfig::<DEFAULT_LIMBS>::configure(&mut meta);
+    let rows = (max_steps + 1).max(1 << LIMB_BITS) + meta.minimum_rows();
+    rows.next_power_of_two().trailing_zeros()
+}
 
-    CollatzCircuit { x }
+/// `n` as a field element, reduced modulo the field order.
+fn to_fr(n: &BigUint) -> Fr {
+    n.iter_u64_digits().rev().fold(Fr::ZERO, |acc, digit| {
+        acc * Fr::from_u128(1 << 64) + Fr::from(digit)
+    })
 }
 
-pub fn empty_circuit() -> CollatzCircuit<Fr> {
+/// Builds the circuit for `sequence`, padded with 1s to `max_steps + 1` entries.
+pub fn create_circuit<const NUM_LIMBS: usize>(
+    mut sequence: Vec<BigUint>,
+    max_steps: usize,
+) -> CollatzCircuit<Fr, NUM_LIMBS> {
+    sequence.resize(max_steps + 1, BigUint::one());
     CollatzCircuit {
-        x: [Value::unknown(); 32],
+        x: sequence.iter().map(|n| Value::known(to_fr(n))).collect(),
+    }
+}
+
+pub fn empty_circuit<const NUM_LIMBS: usize>(max_steps: usize) -> CollatzCircuit<Fr, NUM_LIMBS> {
+    CollatzCircuit {
+        x: vec![Value::unknown(); max_steps + 1],
+    }
+}
+
+pub fn parse_string(s: &str) -> Result<CollatzInput, ProverError> {
+    Ok(serde_json::from_str(s)?)
+}
+
+/// The sequence of the input, or an error if it does not reach 1 within `max_steps` steps,
+/// reaches 1 in a different number of steps than the input claims, or has an entry wider
+/// than `bits`.
+fn sequence(input: &CollatzInput, bits: usize) -> Result<Vec<BigUint>, ProverError> {
+    let sequence = collatz_conjecture(input.n.clone(), input.max_steps);
+    if !sequence.last().map_or(false, BigUint::is_one) {
+        return Err(ProverError::InvalidInput(format!(
+            "{} does not reach 1 within {} steps",
+            input.n, input.max_steps
+        )));
+    }
+    if let Some(steps) = input.steps.filter(|&steps| steps != sequence.len() - 1) {
+        return Err(ProverError::InvalidInput(format!(
+            "{} reaches 1 in {} steps, not {}",
+            input.n,
+            sequence.len() - 1,
+            steps
+        )));
     }
+    if let Some(entry) = sequence.iter().find(|n| n.bits() > bits as u64) {
+        return Err(ProverError::InvalidInput(format!(
+            "the sequence of {} reaches {}, which is wider than {} bits",
+            input.n, entry, bits
+        )));
+    }
+    Ok(sequence)
 }
 
-pub fn simulate_circuit() -> String {
-    "N/A".to_string()
+/// The starting number followed by the number of steps.
+pub fn public_inputs(n: &BigUint, steps: usize) -> Vec<Fr> {
+    vec![to_fr(n), Fr::from(steps as u64)]
 }
 
-pub fn parse_string(s: &str) -> CollatzInput {
-    serde_json::from_str(s).unwrap()
+/// The sequence of the input as strings in the requested encoding.
+pub fn simulate_circuit(s: &str, bits: usize) -> Result<String, ProverError> {
+    let sequence = sequence(&parse_string(s)?, bits)?;
+    let encoding = parse_encoding(s)?;
+    let entries: Vec<String> = sequence
+        .iter()
+        .map(|n| encoding.encode(&to_fr(n)))
+        .collect();
+    Ok(serde_json::to_string(&entries)?)
 }
 
-pub fn create_circuit_from_string(s: &str) -> CollatzCircuit<Fr> {
-    let v = parse_string(s);
-    let mut sequence = v.x;
-    sequence.resize(32, 1);
-    create_circuit(sequence)
+pub fn create_circuit_from_string<const NUM_LIMBS: usize>(
+    s: &str,
+) -> Result<CollatzCircuit<Fr, NUM_LIMBS>, ProverError> {
+    let input = parse_string(s)?;
+    let sequence = sequence(&input, entry_bits(NUM_LIMBS))?;
+    Ok(create_circuit(sequence, input.max_steps))
+}
+
+pub struct CollatzDemo<const NUM_LIMBS: usize> {
+    slug: &'static str,
+    name: &'static str,
+    description: &'static str,
+    input_schema: &'static str,
+}
+
+/// Collatz sequences of `u64` starting numbers.
+pub const COLLATZ: CollatzDemo<DEFAULT_LIMBS> = CollatzDemo {
+    slug: "collatz",
+    name: "Collatz",
+    description: "Proves that n reaches 1 in the given number of steps, at most max_steps",
+    input_schema: r#"{ "n": 27, "steps": 111, "max_steps": 128 }"#,
+};
+
+/// Collatz sequences of 128-bit starting numbers.
+pub const COLLATZ_128: CollatzDemo<WIDE_LIMBS> = CollatzDemo {
+    slug: "collatz_128",
+    name: "Collatz (128-bit)",
+    description: "Proves that a 128-bit n reaches 1 in the given number of steps",
+    input_schema: r#"{ "n": "170141183460469231731687303715884105729", "steps": 918, "max_steps": 1023 }"#,
+};
+
+impl<const NUM_LIMBS: usize> DemoCircuit for CollatzDemo<NUM_LIMBS> {
+    type Circuit = CollatzCircuit<Fr, NUM_LIMBS>;
+
+    fn slug(&self) -> &'static str {
+        self.slug
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn input_schema(&self) -> &'static str {
+        self.input_schema
+    }
+
+    fn k(&self) -> u32 {
+        self.k_for(self.input_schema)
+            .unwrap_or_else(|_| collatz_k(DEFAULT_MAX_STEPS))
+    }
+
+    fn k_for(&self, s: &str) -> Result<u32, ProverError> {
+        let shape: CollatzShape = serde_json::from_str(s)?;
+        Ok(collatz_k(shape.max_steps))
+    }
+
+    fn create_from_json(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        create_circuit_from_string(s)
+    }
+
+    fn empty(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        let shape: CollatzShape = serde_json::from_str(s)?;
+        Ok(empty_circuit(shape.max_steps))
+    }
+
+    fn shape(&self, s: &str) -> Result<String, ProverError> {
+        let shape: CollatzShape = serde_json::from_str(s)?;
+        Ok(serde_json::to_string(&shape)?)
+    }
+
+    /// Uses the claimed `steps` when present, so verifying does not run the sequence.
+    fn public_inputs(&self, s: &str) -> Result<Vec<Fr>, ProverError> {
+        let input = parse_string(s)?;
+        let steps = match input.steps {
+            Some(steps) => steps,
+            None => sequence(&input, entry_bits(NUM_LIMBS))?.len() - 1,
+        };
+        Ok(public_inputs(&input.n, steps))
+    }
+
+    fn simulate(&self, s: &str) -> Result<String, ProverError> {
+        simulate_circuit(s, entry_bits(NUM_LIMBS))
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::create_circuit;
-    use crate::collatz::collatz_conjecture;
-    use halo2_proofs::dev::MockProver;
+    use super::{
+        collatz_k, create_circuit, create_circuit_from_string, entry_bits, parse_string,
+        public_inputs, simulate_circuit, CollatzChip, CollatzConfig, CollatzStep, DEFAULT_LIMBS,
+        DEFAULT_MAX_STEPS, WIDE_LIMBS,
+    };
+    use crate::{collatz::collatz_conjecture, error::ProverError};
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::{
+            bn256::Fr,
+            ff::{Field, PrimeField},
+        },
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+    use num::{BigUint, One};
+    use std::array;
+
+    /// Runs the Collatz chip on a witness that is not generated from the sequence.
+    #[derive(Clone)]
+    struct ForgedCircuit(Vec<CollatzStep<Fr, DEFAULT_LIMBS>>);
+
+    impl Circuit<Fr> for ForgedCircuit {
+        type Config = CollatzConfig<DEFAULT_LIMBS>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            CollatzConfig::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let chip = CollatzChip::new(config);
+            chip.load_table(layouter.namespace(|| "table"))?;
+            let (start, count) = chip.assign(layouter.namespace(|| "sequence"), &self.0)?;
+            chip.expose_public(layouter.namespace(|| "start"), &start, 0)?;
+            chip.expose_public(layouter.namespace(|| "steps"), &count, 1)
+        }
+    }
+
+    fn honest_steps(sequence: &[u64]) -> Vec<CollatzStep<Fr, DEFAULT_LIMBS>> {
+        sequence
+            .iter()
+            .map(|x| CollatzStep::new(Value::known(Fr::from(*x))))
+            .collect()
+    }
+
+    /// Whether a proof that `n` reaches 1 in `steps.len() - 1` steps is rejected.
+    fn is_rejected(n: u64, steps: Vec<CollatzStep<Fr, DEFAULT_LIMBS>>) -> bool {
+        let k = collatz_k(steps.len());
+        let instance = public_inputs(&n.into(), steps.len() - 1);
+        let prover = MockProver::run(k, &ForgedCircuit(steps), vec![instance]).unwrap();
+        prover.verify().is_err()
+    }
 
     #[test]
     fn test_collatz() {
-        let k = 10;
-        let x = collatz_conjecture(9);
+        let k = collatz_k(DEFAULT_MAX_STEPS);
+        let x = collatz_conjecture(9u64, DEFAULT_MAX_STEPS);
+
+        assert_eq!(x.len(), 20);
+
+        let circuit = create_circuit::<DEFAULT_LIMBS>(x, DEFAULT_MAX_STEPS);
+
+        MockProver::run(k, &circuit, vec![public_inputs(&9u64.into(), 19)])
+            .unwrap()
+            .assert_satisfied();
+    }
+
+    #[test]
+    fn wrong_public_inputs_are_rejected() {
+        let k = collatz_k(DEFAULT_MAX_STEPS);
+        let circuit = create_circuit::<DEFAULT_LIMBS>(
+            collatz_conjecture(9u64, DEFAULT_MAX_STEPS),
+            DEFAULT_MAX_STEPS,
+        );
+
+        for instance in [
+            public_inputs(&9u64.into(), 18),
+            public_inputs(&7u64.into(), 19),
+        ] {
+            let prover = MockProver::run(k, &circuit, vec![instance]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn long_sequence_fits_derived_k() {
+        let x = collatz_conjecture(27u64, 111);
+        assert_eq!(x.len(), 112);
+        assert_eq!(x.last(), Some(&BigUint::one()));
+
+        let circuit =
+            create_circuit_from_string::<DEFAULT_LIMBS>(r#"{ "n": 27, "max_steps": 111 }"#)
+                .unwrap();
+        MockProver::run(
+            collatz_k(111),
+            &circuit,
+            vec![public_inputs(&27u64.into(), 111)],
+        )
+        .unwrap()
+        .assert_satisfied();
+    }
 
-        let circuit = create_circuit(x);
+    #[test]
+    fn sequence_must_reach_one_within_max_steps() {
+        assert!(matches!(
+            create_circuit_from_string::<DEFAULT_LIMBS>(r#"{ "n": 27, "max_steps": 110 }"#),
+            Err(ProverError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            create_circuit_from_string::<DEFAULT_LIMBS>(r#"{ "n": 0 }"#),
+            Err(ProverError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            create_circuit_from_string::<DEFAULT_LIMBS>(r#"{ "n": 27, "steps": 110 }"#),
+            Err(ProverError::InvalidInput(_))
+        ));
+    }
 
-        MockProver::run(k, &circuit, vec![])
+    #[test]
+    fn honest_witness_is_accepted() {
+        assert!(!is_rejected(
+            6,
+            honest_steps(&[6, 3, 10, 5, 16, 8, 4, 2, 1])
+        ));
+    }
+
+    #[test]
+    fn skipped_step_is_rejected() {
+        assert!(is_rejected(6, honest_steps(&[6, 3, 10, 5, 16, 4, 2, 1])));
+    }
+
+    #[test]
+    fn forged_is_one_is_rejected() {
+        // Claims that 6 is 1 so that the sequence may stay at 6.
+        let mut steps = honest_steps(&[6, 6, 3, 10, 5, 16, 8, 4, 2, 1]);
+        steps[0].is_one = Value::known(Fr::ONE);
+        assert!(is_rejected(6, steps.clone()));
+
+        steps[0].inv = Value::known(Fr::ZERO);
+        assert!(is_rejected(6, steps));
+    }
+
+    #[test]
+    fn forged_parity_is_rejected() {
+        // Claims that 4 is odd.
+        let mut steps = honest_steps(&[4, 13, 40, 20, 10, 5, 16, 8, 4, 2, 1]);
+        steps[0].is_odd = Value::known(Fr::ONE);
+        assert!(is_rejected(4, steps.clone()));
+
+        // Halves 3 in the field, which only satisfies the gates if `x / 2` may wrap around.
+        let half = Fr::from(3) * Fr::TWO_INV;
+        let mut steps = honest_steps(&[3, 1]);
+        steps[0].is_odd = Value::known(Fr::ZERO);
+        steps[0].limbs = array::from_fn(|i| Value::known(Fr::from(half.to_repr()[i] as u64)));
+        assert!(is_rejected(3, steps));
+    }
+
+    #[test]
+    fn encoded_starting_numbers() {
+        let decimal: Vec<String> =
+            serde_json::from_str(&simulate_circuit(r#"{ "n": 27 }"#, 65).unwrap()).unwrap();
+        assert_eq!(decimal.len(), 112);
+        for n in [r#""27""#, r#""0x1b""#, r#""Gw==""#] {
+            let s = format!(r#"{{ "n": {} }}"#, n);
+            assert_eq!(parse_string(&s).unwrap().n, BigUint::from(27u32));
+            assert_eq!(
+                simulate_circuit(&s, 65).unwrap(),
+                serde_json::to_string(&decimal).unwrap()
+            );
+        }
+
+        let hex: Vec<String> = serde_json::from_str(
+            &simulate_circuit(r#"{ "n": 27, "encoding": "hex" }"#, 65).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(hex[0], format!("0x{:064x}", 27));
+        assert_eq!(hex[111], format!("0x{:064x}", 1));
+    }
+
+    #[test]
+    fn wide_entries_are_range_checked() {
+        // Peaks at 204 bits.
+        let n = (BigUint::one() << 128) - 1u32;
+        let input = format!(r#"{{ "n": "{}", "max_steps": 1700 }}"#, n);
+        assert!(matches!(
+            create_circuit_from_string::<DEFAULT_LIMBS>(&input),
+            Err(ProverError::InvalidInput(_))
+        ));
+
+        let sequence = collatz_conjecture(n.clone(), 1700);
+        assert_eq!(sequence.len(), 1662);
+        assert!(sequence
+            .iter()
+            .all(|x| x.bits() <= entry_bits(WIDE_LIMBS) as u64));
+
+        let circuit = create_circuit_from_string::<WIDE_LIMBS>(&input).unwrap();
+        MockProver::run(collatz_k(1700), &circuit, vec![public_inputs(&n, 1661)])
             .unwrap()
             .assert_satisfied();
     }
 
     #[test]
     fn print_collatz() {
-        let n = 154;
-        println!("{:?}", collatz_conjecture(n));
+        let n = 154u64;
+        println!("{:?}", collatz_conjecture(n, DEFAULT_MAX_STEPS));
     }
 }
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+/// Errors of the prover API.
+///
+/// Surfaced to JavaScript as an `Error` whose `name` is the variant name, so the UI can
+/// tell them apart without parsing the message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProverError {
+    /// The JSON input of a circuit is malformed or inconsistent.
+    InvalidInput(String),
+    /// Serialized params could not be read, or do not match the proof.
+    InvalidParams(String),
+    /// A serialized proving or verifying key could not be read.
+    InvalidKey(String),
+    /// A proof bundle could not be decoded.
+    InvalidProof(String),
+    /// No circuit is registered under this id.
+    UnknownCircuit(usize),
+    KeygenFailed(String),
+    ProofFailed(String),
+    /// The proof does not verify against its statement.
+    VerificationFailed(String),
+}
+
+impl ProverError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProverError::InvalidInput(_) => "InvalidInput",
+            ProverError::InvalidParams(_) => "InvalidParams",
+            ProverError::InvalidKey(_) => "InvalidKey",
+            ProverError::InvalidProof(_) => "InvalidProof",
+            ProverError::UnknownCircuit(_) => "UnknownCircuit",
+            ProverError::KeygenFailed(_) => "KeygenFailed",
+            ProverError::ProofFailed(_) => "ProofFailed",
+            ProverError::VerificationFailed(_) => "VerificationFailed",
+        }
+    }
+}
+
+impl fmt::Display for ProverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProverError::InvalidInput(e) => write!(f, "invalid input: {}", e),
+            ProverError::InvalidParams(e) => write!(f, "invalid params: {}", e),
+            ProverError::InvalidKey(e) => write!(f, "invalid key: {}", e),
+            ProverError::InvalidProof(e) => write!(f, "invalid proof: {}", e),
+            ProverError::UnknownCircuit(id) => write!(f, "unknown circuit id {}", id),
+            ProverError::KeygenFailed(e) => write!(f, "key generation failed: {}", e),
+            ProverError::ProofFailed(e) => write!(f, "proof generation failed: {}", e),
+            ProverError::VerificationFailed(e) => write!(f, "verification failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+impl From<serde_json::Error> for ProverError {
+    fn from(e: serde_json::Error) -> Self {
+        ProverError::InvalidInput(e.to_string())
+    }
+}
+
+impl From<ProverError> for JsValue {
+    fn from(e: ProverError) -> Self {
+        let error = js_sys::Error::new(&e.to_string());
+        error.set_name(e.name());
+        error.into()
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::VerifyingKey,
+    poly::kzg::commitment::ParamsKZG,
+};
+use halo2_solidity_verifier::{BatchOpenScheme, SolidityGenerator};
+
+use crate::utils::MultiOpenScheme;
+
+/// Renders a Solidity contract verifying proofs created with `vk` and
+/// [`TranscriptHash::Evm`](crate::transcript::TranscriptHash::Evm). The verifying key is
+/// embedded in the contract.
+///
+/// The circuit may have at most one instance column, holding `num_instances` values.
+pub fn generate_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instances: usize,
+    scheme: MultiOpenScheme,
+) -> Result<String, fmt::Error> {
+    let scheme = match scheme {
+        MultiOpenScheme::Gwc => BatchOpenScheme::Gwc19,
+        MultiOpenScheme::Shplonk => BatchOpenScheme::Bdfg21,
+    };
+    SolidityGenerator::new(params, vk, scheme, num_instances).render()
+}
+
+/// Calldata calling the contract of [`generate_verifier`] with `proof` and `instances`.
+pub fn encode_calldata(proof: &[u8], instances: &[Fr]) -> Vec<u8> {
+    halo2_solidity_verifier::encode_calldata(None, proof, instances)
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::halo2curves::bn256::Fr;
+    use halo2_solidity_verifier::{compile_solidity, Evm};
+
+    use super::{encode_calldata, generate_verifier};
+    use crate::{
+        arithmetic_circuit::{create_circuit, empty_circuit},
+        registry::{circuit_id, get_circuit},
+        transcript::TranscriptHash,
+        utils::{generate_keys, generate_params, prove, MultiOpenScheme},
+    };
+
+    fn accepted() -> Vec<u8> {
+        [vec![0; 31], vec![1]].concat()
+    }
+
+    #[test]
+    fn arithmetic_verifier_runs_in_evm() {
+        let params = generate_params(4);
+        let (pk, vk) = generate_keys(&params, &empty_circuit(Fr::from(7)));
+        let public_input = [Fr::from(7), Fr::from(36 * 81 + 7)];
+        let proof = prove(
+            &params,
+            &pk,
+            create_circuit(Fr::from(6), Fr::from(9), Fr::from(7)),
+            &[&public_input],
+            MultiOpenScheme::default(),
+            TranscriptHash::Evm,
+        )
+        .unwrap();
+
+        let verifier =
+            generate_verifier(&params, &vk, public_input.len(), MultiOpenScheme::default())
+                .unwrap();
+        let mut evm = Evm::default();
+        let address = evm.create(compile_solidity(&verifier));
+
+        let (_, output) = evm.call(address, encode_calldata(&proof, &public_input));
+        assert_eq!(output, accepted());
+
+        let wrong_input = [Fr::from(7), Fr::from(36 * 81 + 8)];
+        let (_, output) = evm.call(address, encode_calldata(&proof, &wrong_input));
+        assert_ne!(output, accepted());
+    }
+
+    #[test]
+    fn poseidon_verifier_runs_in_evm() {
+        let s = r#"{ "x": [1, 2] }"#;
+        let id = circuit_id("poseidon").unwrap();
+        let circuit = get_circuit(id).unwrap();
+        let params = generate_params(circuit.info(id).k);
+
+        let vk = circuit.keygen_vk(&params, s).unwrap();
+        let pk = circuit.keygen_pk(&params, vk.clone(), s).unwrap();
+        for scheme in [MultiOpenScheme::Gwc, MultiOpenScheme::Shplonk] {
+            let bundle = circuit
+                .prove_json(id, &params, &pk, s, scheme, TranscriptHash::Evm)
+                .unwrap();
+            let public_inputs = &bundle.instances[0];
+
+            let verifier = generate_verifier(&params, &vk, public_inputs.len(), scheme).unwrap();
+            let mut evm = Evm::default();
+            let address = evm.create(compile_solidity(&verifier));
+
+            let (_, output) = evm.call(address, encode_calldata(&bundle.proof, public_inputs));
+            assert_eq!(output, accepted());
+        }
+    }
+}
@@ -0,0 +1,759 @@
+//! A small language of polynomial relations, proven on the [`StandardPlonkChip`].
+//!
+//! A program is a list of statements separated by newlines or `;`:
+//!
+//! - `let t = x * y` names an intermediate value,
+//! - `pub z = t^2 + 7` names a public output,
+//! - a bare expression such as `x - y` is an unnamed public output.
+//!
+//! Expressions are built from constants (decimal or `0x` hex), variables, parentheses,
+//! `+`, `-`, `*` and `^` raised to a constant. Variables that no statement defines are the
+//! private inputs of the program. Each operation takes one row of the chip, with `^`
+//! expanded by square-and-multiply.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::bn256::Fr,
+    halo2curves::ff::Field,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::ProverError,
+    registry::DemoCircuit,
+    standard_plonk::{StandardPlonkChip, StandardPlonkConfig, StandardPlonkInstructions},
+    utils::{hex_to_fr, parse_encoding, str_to_fr, FieldElement},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl BinaryOp {
+    fn apply(self, a: Fr, b: Fr) -> Fr {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+        }
+    }
+}
+
+/// One row of a compiled [`Program`], whose operands are the values of earlier rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Input(usize),
+    Constant(Fr),
+    Binary(BinaryOp, usize, usize),
+}
+
+/// A parsed program, compiled to one [`Op`] per row of the circuit.
+#[derive(Clone, Debug)]
+pub struct Program {
+    inputs: Vec<String>,
+    ops: Vec<Op>,
+    outputs: Vec<usize>,
+}
+
+impl Program {
+    pub fn parse(source: &str) -> Result<Self, ProverError> {
+        Parser::new(source)?.program()
+    }
+
+    /// Names of the private inputs, in order of first use.
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    /// Number of public outputs.
+    pub fn outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Number of rows the program takes in the circuit.
+    pub fn rows(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// The public outputs of the program on `inputs`, given in the order of
+    /// [`Program::inputs`].
+    pub fn evaluate(&self, inputs: &[Fr]) -> Vec<Fr> {
+        assert_eq!(inputs.len(), self.inputs.len());
+        let mut values: Vec<Fr> = Vec::with_capacity(self.ops.len());
+        for op in &self.ops {
+            let value = match *op {
+                Op::Input(i) => inputs[i],
+                Op::Constant(c) => c,
+                Op::Binary(op, a, b) => op.apply(values[a], values[b]),
+            };
+            values.push(value);
+        }
+        self.outputs.iter().map(|&i| values[i]).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Symbol(char),
+    /// A newline or `;`.
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) | Token::Ident(n) => write!(f, "`{}`", n),
+            Token::Symbol(c) => write!(f, "`{}`", c),
+            Token::End => write!(f, "end of statement"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ProverError> {
+    fn take_while(
+        chars: &mut Peekable<CharIndices>,
+        source: &str,
+        start: usize,
+        pred: impl Fn(char) -> bool,
+    ) -> String {
+        let mut end = start;
+        while let Some(&(i, c)) = chars.peek() {
+            if !pred(c) {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        source[start..end].to_string()
+    }
+
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            '\n' | ';' => {
+                chars.next();
+                tokens.push(Token::End);
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '^' | '(' | ')' | '=' => {
+                chars.next();
+                tokens.push(Token::Symbol(c));
+            }
+            c if c.is_ascii_digit() => {
+                let n = take_while(&mut chars, source, start, |c| c.is_ascii_alphanumeric());
+                tokens.push(Token::Number(n));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let name = take_while(&mut chars, source, start, |c| {
+                    c.is_ascii_alphanumeric() || c == '_'
+                });
+                tokens.push(Token::Ident(name));
+            }
+            c => {
+                return Err(ProverError::InvalidInput(format!(
+                    "unexpected character `{}` at {}",
+                    c, start
+                )))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// The constant written `n`, in decimal or `0x` hex.
+fn parse_number(n: &str) -> Result<Fr, ProverError> {
+    if n.starts_with("0x") {
+        hex_to_fr(n)
+    } else if n.bytes().all(|b| b.is_ascii_digit()) {
+        str_to_fr(n)
+    } else {
+        Err(ProverError::InvalidInput(format!(
+            "`{}` is not a decimal or `0x` hex number",
+            n
+        )))
+    }
+}
+
+const KEYWORDS: [&str; 2] = ["let", "pub"];
+
+/// Recursive descent parser, emitting the ops of each subexpression as it is read.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    program: Program,
+    names: HashMap<String, usize>,
+    constants: BTreeMap<Fr, usize>,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, ProverError> {
+        Ok(Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            program: Program {
+                inputs: vec![],
+                ops: vec![],
+                outputs: vec![],
+            },
+            names: HashMap::new(),
+            constants: BTreeMap::new(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ProverError {
+        let found = self
+            .peek()
+            .map_or("end of program".to_string(), |t| t.to_string());
+        ProverError::InvalidInput(format!("expected {}, found {}", expected, found))
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ProverError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", symbol)))
+        }
+    }
+
+    fn push(&mut self, op: Op) -> usize {
+        self.program.ops.push(op);
+        self.program.ops.len() - 1
+    }
+
+    fn constant(&mut self, c: Fr) -> usize {
+        if let Some(&i) = self.constants.get(&c) {
+            return i;
+        }
+        let i = self.push(Op::Constant(c));
+        self.constants.insert(c, i);
+        i
+    }
+
+    fn variable(&mut self, name: String) -> usize {
+        if let Some(&i) = self.names.get(&name) {
+            return i;
+        }
+        let i = self.push(Op::Input(self.program.inputs.len()));
+        self.program.inputs.push(name.clone());
+        self.names.insert(name, i);
+        i
+    }
+
+    fn program(mut self) -> Result<Program, ProverError> {
+        while self.peek().is_some() {
+            if self.peek() != Some(&Token::End) {
+                self.statement()?;
+            }
+            match self.bump() {
+                None | Some(Token::End) => {}
+                Some(_) => {
+                    self.pos -= 1;
+                    return Err(self.unexpected("end of statement"));
+                }
+            }
+        }
+        if self.program.outputs.is_empty() {
+            return Err(ProverError::InvalidInput(
+                "the program has no public output".to_string(),
+            ));
+        }
+        Ok(self.program)
+    }
+
+    fn statement(&mut self) -> Result<(), ProverError> {
+        let keyword = match self.peek() {
+            Some(Token::Ident(name)) if KEYWORDS.contains(&name.as_str()) => name.clone(),
+            _ => {
+                let value = self.expr()?;
+                self.program.outputs.push(value);
+                return Ok(());
+            }
+        };
+        self.pos += 1;
+        let name = match self.bump() {
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => name,
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected("a name"));
+            }
+        };
+        self.expect('=')?;
+        let value = self.expr()?;
+        if self.names.insert(name.clone(), value).is_some() {
+            return Err(ProverError::InvalidInput(format!(
+                "`{}` is defined twice, or used before it is defined",
+                name
+            )));
+        }
+        if keyword == "pub" {
+            self.program.outputs.push(value);
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<usize, ProverError> {
+        let mut a = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Sub
+            } else {
+                return Ok(a);
+            };
+            let b = self.term()?;
+            a = self.push(Op::Binary(op, a, b));
+        }
+    }
+
+    fn term(&mut self) -> Result<usize, ProverError> {
+        let mut a = self.unary()?;
+        while self.eat('*') {
+            let b = self.unary()?;
+            a = self.push(Op::Binary(BinaryOp::Mul, a, b));
+        }
+        Ok(a)
+    }
+
+    fn unary(&mut self) -> Result<usize, ProverError> {
+        if self.eat('-') {
+            let a = self.unary()?;
+            let zero = self.constant(Fr::ZERO);
+            return Ok(self.push(Op::Binary(BinaryOp::Sub, zero, a)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<usize, ProverError> {
+        let base = self.primary()?;
+        if !self.eat('^') {
+            return Ok(base);
+        }
+        let exponent = match self.bump() {
+            Some(Token::Number(n)) => n.parse::<u64>().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            ProverError::InvalidInput("exponents must be integer constants".to_string())
+        })?;
+        if self.peek() == Some(&Token::Symbol('^')) {
+            return Err(ProverError::InvalidInput(
+                "chained exponents need parentheses".to_string(),
+            ));
+        }
+        Ok(self.pow(base, exponent))
+    }
+
+    fn pow(&mut self, base: usize, mut exponent: u64) -> usize {
+        if exponent == 0 {
+            return self.constant(Fr::ONE);
+        }
+        let mut result = None;
+        let mut square = base;
+        loop {
+            if exponent & 1 == 1 {
+                result = Some(match result {
+                    None => square,
+                    Some(r) => self.push(Op::Binary(BinaryOp::Mul, r, square)),
+                });
+            }
+            exponent >>= 1;
+            if exponent == 0 {
+                return result.unwrap();
+            }
+            square = self.push(Op::Binary(BinaryOp::Mul, square, square));
+        }
+    }
+
+    fn primary(&mut self) -> Result<usize, ProverError> {
+        match self.bump() {
+            Some(Token::Number(n)) => Ok(self.constant(parse_number(&n)?)),
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                Ok(self.variable(name))
+            }
+            Some(Token::Symbol('(')) => {
+                let value = self.expr()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("a number, a name or `(`"))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ExpressionCircuit {
+    program: Program,
+    inputs: Vec<Value<Fr>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExpressionConfig {
+    plonk: StandardPlonkConfig,
+    instance: Column<Instance>,
+}
+
+impl Circuit<Fr> for ExpressionCircuit {
+    type Config = ExpressionConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            program: self.program.clone(),
+            inputs: vec![Value::unknown(); self.inputs.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let advice = [(); 3].map(|_| meta.advice_column());
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+
+        ExpressionConfig {
+            plonk: StandardPlonkChip::configure(meta, advice),
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let chip = StandardPlonkChip::construct(config.plonk);
+        let layouter = &mut layouter;
+
+        let mut cells: Vec<AssignedCell<Fr, Fr>> = Vec::with_capacity(self.program.ops.len());
+        for op in &self.program.ops {
+            let cell = match *op {
+                Op::Input(i) => chip.load_private(layouter, self.inputs[i])?,
+                Op::Constant(c) => chip.load_constant(layouter, c)?,
+                Op::Binary(BinaryOp::Add, a, b) => chip.add(layouter, &cells[a], &cells[b])?,
+                Op::Binary(BinaryOp::Sub, a, b) => chip.sub(layouter, &cells[a], &cells[b])?,
+                Op::Binary(BinaryOp::Mul, a, b) => chip.mul(layouter, &cells[a], &cells[b])?,
+            };
+            cells.push(cell);
+        }
+
+        for (row, &i) in self.program.outputs.iter().enumerate() {
+            layouter.constrain_instance(cells[i].cell(), config.instance, row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Smallest `k` whose params fit `program`.
+pub fn expression_k(program: &Program) -> u32 {
+    let mut meta = ConstraintSystem::<Fr>::default();
+    ExpressionCircuit::configure(&mut meta);
+    let rows = program.rows() + meta.minimum_rows();
+    rows.next_power_of_two().trailing_zeros()
+}
+
+/// Input of [`ExpressionDemo`]. The prover gives the private `inputs` of the program; the
+/// verifier only needs the claimed `outputs`, which are checked against `inputs` if both
+/// are given.
+#[derive(Deserialize)]
+pub struct ExpressionInput {
+    pub program: String,
+    #[serde(default)]
+    inputs: Option<BTreeMap<String, FieldElement>>,
+    #[serde(default)]
+    outputs: Option<Vec<FieldElement>>,
+}
+
+/// Shape of [`ExpressionDemo`]: the program, whose constants are fixed in the circuit.
+#[derive(Serialize, Deserialize)]
+pub struct ExpressionShape {
+    pub program: String,
+}
+
+pub fn parse_string(s: &str) -> Result<(Program, ExpressionInput), ProverError> {
+    let input: ExpressionInput = serde_json::from_str(s)?;
+    Ok((Program::parse(&input.program)?, input))
+}
+
+/// The private inputs of `input`, in the order of [`Program::inputs`].
+fn parse_witness(program: &Program, input: &ExpressionInput) -> Result<Vec<Fr>, ProverError> {
+    let empty = BTreeMap::new();
+    let values = input.inputs.as_ref().unwrap_or(&empty);
+    if let Some(name) = values.keys().find(|name| !program.inputs.contains(name)) {
+        return Err(ProverError::InvalidInput(format!(
+            "`{}` is not an input of the program",
+            name
+        )));
+    }
+    program
+        .inputs
+        .iter()
+        .map(|name| {
+            values
+                .get(name)
+                .map(|value| value.0)
+                .ok_or_else(|| ProverError::InvalidInput(format!("missing input `{}`", name)))
+        })
+        .collect()
+}
+
+/// The public outputs of `s`: the claimed `outputs`, or those computed from `inputs`.
+fn parse_outputs(s: &str) -> Result<Vec<Fr>, ProverError> {
+    let (program, input) = parse_string(s)?;
+    let claimed: Option<Vec<Fr>> = input
+        .outputs
+        .as_ref()
+        .map(|outputs| outputs.iter().map(|output| output.0).collect());
+    if let Some(claimed) = &claimed {
+        if claimed.len() != program.outputs() {
+            return Err(ProverError::InvalidInput(format!(
+                "expected {} outputs, got {}",
+                program.outputs(),
+                claimed.len()
+            )));
+        }
+    }
+
+    match claimed {
+        Some(claimed) if input.inputs.is_none() && !program.inputs.is_empty() => Ok(claimed),
+        claimed => {
+            let outputs = program.evaluate(&parse_witness(&program, &input)?);
+            if matches!(&claimed, Some(claimed) if *claimed != outputs) {
+                return Err(ProverError::InvalidInput(
+                    "`outputs` are not the outputs of the program on `inputs`".to_string(),
+                ));
+            }
+            Ok(outputs)
+        }
+    }
+}
+
+fn parse_shape(s: &str) -> Result<(Program, ExpressionShape), ProverError> {
+    let shape: ExpressionShape = serde_json::from_str(s)?;
+    Ok((Program::parse(&shape.program)?, shape))
+}
+
+pub struct ExpressionDemo;
+
+impl DemoCircuit for ExpressionDemo {
+    type Circuit = ExpressionCircuit;
+
+    fn slug(&self) -> &'static str {
+        "expression"
+    }
+
+    fn name(&self) -> &'static str {
+        "Expression"
+    }
+
+    fn description(&self) -> &'static str {
+        "Proves knowledge of private inputs on which a polynomial program has the public outputs"
+    }
+
+    fn input_schema(&self) -> &'static str {
+        r#"{ "program": "pub z = x^2 * y^2 + 7", "inputs": { "x": 6, "y": 9 } }"#
+    }
+
+    fn k(&self) -> u32 {
+        self.k_for(self.input_schema())
+            .expect("the example input is valid")
+    }
+
+    fn k_for(&self, s: &str) -> Result<u32, ProverError> {
+        Ok(expression_k(&parse_shape(s)?.0))
+    }
+
+    fn create_from_json(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        let (program, input) = parse_string(s)?;
+        let inputs = parse_witness(&program, &input)?
+            .into_iter()
+            .map(Value::known)
+            .collect();
+        Ok(ExpressionCircuit { program, inputs })
+    }
+
+    fn empty(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        let (program, _) = parse_shape(s)?;
+        Ok(ExpressionCircuit {
+            inputs: vec![Value::unknown(); program.inputs.len()],
+            program,
+        })
+    }
+
+    fn shape(&self, s: &str) -> Result<String, ProverError> {
+        Ok(serde_json::to_string(&parse_shape(s)?.1)?)
+    }
+
+    fn public_inputs(&self, s: &str) -> Result<Vec<Fr>, ProverError> {
+        parse_outputs(s)
+    }
+
+    fn simulate(&self, s: &str) -> Result<String, ProverError> {
+        let encoding = parse_encoding(s)?;
+        let outputs: Vec<String> = parse_outputs(s)?
+            .iter()
+            .map(|o| encoding.encode(o))
+            .collect();
+        Ok(serde_json::to_string(&outputs)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::dev::MockProver;
+
+    use super::*;
+
+    fn run(source: &str, inputs: &[u64]) -> Vec<Fr> {
+        let inputs: Vec<Fr> = inputs.iter().map(|&x| Fr::from(x)).collect();
+        Program::parse(source).unwrap().evaluate(&inputs)
+    }
+
+    #[test]
+    fn evaluates_natively() {
+        assert_eq!(run("pub z = x^2 * y^2 + 7", &[6, 9]), vec![Fr::from(2923)]);
+        assert_eq!(run("2 * 3 + 4 * 5 - 1", &[]), vec![Fr::from(25)]);
+        assert_eq!(run("-x^2 + (1 - y) * 3", &[2, 1]), vec![-Fr::from(4)]);
+        assert_eq!(run("x^0 + x^1 + x^13", &[2]), vec![Fr::from(8195)]);
+        assert_eq!(
+            run("let t = x + y\nt * t; x - y", &[5, 3]),
+            vec![Fr::from(64), Fr::from(2)]
+        );
+        assert_eq!(run("0x10 * a_1", &[3]), vec![Fr::from(48)]);
+
+        let program = Program::parse("pub z = b * a + b").unwrap();
+        assert_eq!(program.inputs(), ["b", "a"]);
+        assert_eq!(program.outputs(), 1);
+        assert_eq!(program.rows(), 4);
+    }
+
+    #[test]
+    fn malformed_programs_are_errors() {
+        for source in [
+            "",
+            "let t = 1",
+            "x +",
+            "x ^ y",
+            "x^2^3",
+            "(x + 1",
+            "x = 1",
+            "y + $",
+            "1abc + x",
+            "0xzz",
+            "let let = 1; 1",
+            "let x = 1; let x = 2; x",
+            "x + 1; let x = 2",
+        ] {
+            assert!(
+                matches!(Program::parse(source), Err(ProverError::InvalidInput(_))),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn circuit_matches_native() {
+        for (s, outputs) in [
+            (
+                r#"{ "program": "pub z = x^2 * y^2 + 7", "inputs": { "x": 6, "y": 9 } }"#,
+                vec![Fr::from(2923)],
+            ),
+            (
+                r#"{ "program": "let t = x - y; t^5; -t", "inputs": { "x": "2", "y": "0x3" } }"#,
+                vec![-Fr::ONE, Fr::ONE],
+            ),
+            (r#"{ "program": "3 * 4" }"#, vec![Fr::from(12)]),
+        ] {
+            let circuit = ExpressionDemo.create_from_json(s).unwrap();
+            let k = ExpressionDemo.k_for(s).unwrap();
+            assert_eq!(ExpressionDemo.public_inputs(s).unwrap(), outputs);
+
+            MockProver::run(k, &circuit, vec![outputs.clone()])
+                .unwrap()
+                .assert_satisfied();
+            let mut wrong = outputs;
+            wrong[0] += Fr::ONE;
+            let prover = MockProver::run(k, &circuit, vec![wrong]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn verifier_needs_only_outputs() {
+        let program = "pub z = x^2 * y^2 + 7";
+        let claim = serde_json::json!({ "program": program, "outputs": ["2923"] }).to_string();
+        assert_eq!(
+            ExpressionDemo.public_inputs(&claim).unwrap(),
+            vec![Fr::from(2923)]
+        );
+        assert_eq!(
+            ExpressionDemo.shape(&claim).unwrap(),
+            ExpressionDemo.shape(ExpressionDemo.input_schema()).unwrap()
+        );
+        assert_eq!(
+            ExpressionDemo
+                .simulate(ExpressionDemo.input_schema())
+                .unwrap(),
+            r#"["2923"]"#
+        );
+
+        let encoded = serde_json::json!({
+            "program": "pub z = x - y",
+            "inputs": { "x": "Bg==", "y": "0x9" },
+            "encoding": "hex",
+        })
+        .to_string();
+        assert_eq!(
+            ExpressionDemo.simulate(&encoded).unwrap(),
+            format!(r#"["{:?}"]"#, -Fr::from(3))
+        );
+
+        for invalid in [
+            serde_json::json!({ "program": program }),
+            serde_json::json!({ "program": program, "outputs": [1, 2] }),
+            serde_json::json!({ "program": program, "inputs": { "x": 6 } }),
+            serde_json::json!({ "program": program, "inputs": { "x": 6, "y": 9, "w": 1 } }),
+            serde_json::json!({ "program": program, "inputs": { "x": 6, "y": 9 }, "outputs": [1] }),
+        ] {
+            assert!(matches!(
+                ExpressionDemo.public_inputs(&invalid.to_string()),
+                Err(ProverError::InvalidInput(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, G1Affine},
+    plonk::{ProvingKey, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    SerdeFormat,
+};
+
+use crate::{
+    bundle::{bundle_circuit, ProofBundle},
+    error::ProverError,
+    registry::{find_circuit, CircuitRunner},
+    transcript::TranscriptHash,
+    utils::MultiOpenScheme,
+};
+
+/// Format used when exporting keys. Exported keys are persisted by the browser, where load
+/// time matters more than size, so the uncompressed encoding is used.
+pub const KEY_FORMAT: SerdeFormat = SerdeFormat::RawBytes;
+
+/// Identifies the keys of one circuit shape under one set of params.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyId {
+    pub circuit: usize,
+    pub k: u32,
+    pub params_hash: u64,
+    pub shape: String,
+}
+
+/// A 64-bit Blake2b digest of the serialized params. Key ids are persisted by the browser,
+/// so the hash must not change between builds.
+pub fn params_hash(params: &ParamsKZG<Bn256>) -> u64 {
+    let mut buf = vec![];
+    params.write(&mut buf).expect("Should write params");
+
+    let digest = blake2b_simd::Params::new().hash_length(8).hash(&buf);
+    u64::from_le_bytes(digest.as_bytes().try_into().unwrap())
+}
+
+/// Proving and verifying keys generated under a fixed set of params, so repeated proofs
+/// for the same circuit shape skip `keygen_vk` and `keygen_pk`.
+pub struct KeyCache {
+    params: ParamsKZG<Bn256>,
+    params_hash: u64,
+    vks: HashMap<KeyId, VerifyingKey<G1Affine>>,
+    pks: HashMap<KeyId, ProvingKey<G1Affine>>,
+}
+
+impl KeyCache {
+    pub fn new(params: ParamsKZG<Bn256>) -> Self {
+        let params_hash = params_hash(&params);
+        Self {
+            params,
+            params_hash,
+            vks: HashMap::new(),
+            pks: HashMap::new(),
+        }
+    }
+
+    pub fn params(&self) -> &ParamsKZG<Bn256> {
+        &self.params
+    }
+
+    pub fn key_id(&self, circuit: usize, s: &str) -> Result<KeyId, ProverError> {
+        Ok(KeyId {
+            circuit,
+            k: self.params.k(),
+            params_hash: self.params_hash,
+            shape: find_circuit(circuit)?.shape_json(s)?,
+        })
+    }
+
+    pub fn vk(&mut self, circuit: usize, s: &str) -> Result<&VerifyingKey<G1Affine>, ProverError> {
+        Ok(self.params_and_vk(circuit, s)?.1)
+    }
+
+    fn params_and_vk(
+        &mut self,
+        circuit: usize,
+        s: &str,
+    ) -> Result<(&ParamsKZG<Bn256>, &VerifyingKey<G1Affine>), ProverError> {
+        let id = self.key_id(circuit, s)?;
+        let runner = find_circuit(circuit)?;
+        let vk = cached_vk(&mut self.vks, &self.params, runner, id, s)?;
+        Ok((&self.params, vk))
+    }
+
+    pub fn pk(&mut self, circuit: usize, s: &str) -> Result<&ProvingKey<G1Affine>, ProverError> {
+        Ok(self.params_and_pk(circuit, s)?.1)
+    }
+
+    fn params_and_pk(
+        &mut self,
+        circuit: usize,
+        s: &str,
+    ) -> Result<(&ParamsKZG<Bn256>, &ProvingKey<G1Affine>), ProverError> {
+        let id = self.key_id(circuit, s)?;
+        let runner = find_circuit(circuit)?;
+        let pk = match self.pks.entry(id.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let vk = cached_vk(&mut self.vks, &self.params, runner, id, s)?.clone();
+                entry.insert(runner.keygen_pk(&self.params, vk, s)?)
+            }
+        };
+        Ok((&self.params, pk))
+    }
+
+    pub fn prove(
+        &mut self,
+        circuit: usize,
+        s: &str,
+        scheme: MultiOpenScheme,
+        transcript: TranscriptHash,
+    ) -> Result<ProofBundle, ProverError> {
+        let (params, pk) = self.params_and_pk(circuit, s)?;
+        find_circuit(circuit)?.prove_json(circuit, params, pk, s, scheme, transcript)
+    }
+
+    /// Verifies that `bundle` proves `s` with only the verifying key; no proving key is
+    /// generated.
+    pub fn verify(
+        &mut self,
+        circuit: usize,
+        bundle: &ProofBundle,
+        s: &str,
+    ) -> Result<(), ProverError> {
+        let (params, vk) = self.params_and_vk(circuit, s)?;
+        find_circuit(circuit)?.verify_json(params, vk, bundle, s)
+    }
+
+    /// Verifies `bundle` against the instances it carries, with the verifying key of its
+    /// circuit and shape.
+    pub fn verify_bundle(&mut self, bundle: &ProofBundle) -> Result<(), ProverError> {
+        let circuit = bundle_circuit(&self.params, bundle)?;
+        let (params, vk) = self.params_and_vk(bundle.circuit, &bundle.shape)?;
+        circuit.verify_bundle(params, vk, bundle)
+    }
+
+    pub fn export_vk(&mut self, circuit: usize, s: &str) -> Result<Vec<u8>, ProverError> {
+        Ok(self.vk(circuit, s)?.to_bytes(KEY_FORMAT))
+    }
+
+    pub fn import_vk(&mut self, circuit: usize, s: &str, bytes: &[u8]) -> Result<(), ProverError> {
+        let vk = find_circuit(circuit)?.read_vk(bytes, KEY_FORMAT)?;
+        self.vks.insert(self.key_id(circuit, s)?, vk);
+        Ok(())
+    }
+
+    pub fn export_pk(&mut self, circuit: usize, s: &str) -> Result<Vec<u8>, ProverError> {
+        Ok(self.pk(circuit, s)?.to_bytes(KEY_FORMAT))
+    }
+
+    pub fn import_pk(&mut self, circuit: usize, s: &str, bytes: &[u8]) -> Result<(), ProverError> {
+        let pk = find_circuit(circuit)?.read_pk(bytes, KEY_FORMAT)?;
+        let id = self.key_id(circuit, s)?;
+        self.vks.insert(id.clone(), pk.get_vk().clone());
+        self.pks.insert(id, pk);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.vks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vks.is_empty()
+    }
+}
+
+/// The verifying key cached under `id`, generated on first use.
+fn cached_vk<'a>(
+    vks: &'a mut HashMap<KeyId, VerifyingKey<G1Affine>>,
+    params: &ParamsKZG<Bn256>,
+    runner: &dyn CircuitRunner,
+    id: KeyId,
+    s: &str,
+) -> Result<&'a VerifyingKey<G1Affine>, ProverError> {
+    Ok(match vks.entry(id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(runner.keygen_vk(params, s)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::KeyCache;
+    use crate::{
+        error::ProverError,
+        registry::circuit_id,
+        transcript::TranscriptHash,
+        utils::{generate_params, MultiOpenScheme},
+    };
+
+    fn arithmetic_id() -> usize {
+        circuit_id("arithmetic_circuit").unwrap()
+    }
+
+    #[test]
+    fn keys_are_shared_by_shape() {
+        let id = arithmetic_id();
+        let mut cache = KeyCache::new(generate_params(4));
+
+        let a = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#;
+        let b = r#"{ "x": 1, "y": 2, "constant": 7, "z": 11 }"#;
+        let c = r#"{ "x": 1, "y": 2, "constant": 8, "z": 12 }"#;
+
+        let proof = cache
+            .prove(id, a, MultiOpenScheme::default(), TranscriptHash::default())
+            .unwrap();
+        assert!(cache.verify(id, &proof, a).is_ok());
+        cache
+            .prove(id, b, MultiOpenScheme::default(), TranscriptHash::default())
+            .unwrap();
+        assert_eq!(cache.len(), 1);
+        cache
+            .prove(id, c, MultiOpenScheme::default(), TranscriptHash::default())
+            .unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn exported_keys_round_trip() {
+        let id = arithmetic_id();
+        let s = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#;
+
+        let mut cache = KeyCache::new(generate_params(4));
+        let proof = cache
+            .prove(id, s, MultiOpenScheme::default(), TranscriptHash::default())
+            .unwrap();
+        let pk = cache.export_pk(id, s).unwrap();
+        let vk = cache.export_vk(id, s).unwrap();
+
+        let mut fresh = KeyCache::new(cache.params().clone());
+        fresh.import_pk(id, s, &pk).unwrap();
+        assert_eq!(fresh.export_vk(id, s).unwrap(), vk);
+        assert!(fresh.verify(id, &proof, s).is_ok());
+    }
+
+    #[test]
+    fn verify_with_imported_vk_only() {
+        let id = arithmetic_id();
+        let s = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#;
+
+        let mut prover = KeyCache::new(generate_params(4));
+        let proof = prover
+            .prove(id, s, MultiOpenScheme::default(), TranscriptHash::default())
+            .unwrap();
+
+        let mut verifier = KeyCache::new(prover.params().clone());
+        assert!(matches!(
+            verifier.import_vk(id, s, &[1, 2, 3]),
+            Err(ProverError::InvalidKey(_))
+        ));
+        let vk = prover.export_vk(id, s).unwrap();
+        verifier.import_vk(id, s, &vk).unwrap();
+        assert!(verifier.verify(id, &proof, s).is_ok());
+        assert!(verifier.verify_bundle(&proof).is_ok());
+        assert!(verifier.pks.is_empty());
+    }
+}
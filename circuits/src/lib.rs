@@ -1,8 +1,22 @@
 pub mod arithmetic_circuit;
+pub mod bundle;
+pub mod circom;
+pub mod circom_poseidon;
 pub mod collatz;
 pub mod constants;
+pub mod error;
+#[cfg(feature = "evm")]
+pub mod evm;
+pub mod expression;
+pub mod keys;
+pub mod merkle_circuit;
 pub mod poseidon;
+pub mod poseidon2;
 pub mod poseidon_circuit;
+pub mod registry;
+pub mod sparse_merkle;
+pub mod standard_plonk;
+pub mod transcript;
 pub mod unstringify;
 pub mod utils;
 pub mod wasm;
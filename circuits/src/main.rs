@@ -6,28 +6,33 @@ fn main() {
     };
     use halo2_prover::{
         arithmetic_circuit,
-        collatz::{self, collatz_conjecture},
+        collatz::{self, collatz_conjecture, collatz_k, DEFAULT_LIMBS, WIDE_LIMBS},
         poseidon_circuit::{self, PoseidonSpec},
         utils::*,
     };
 
     // Arithmetic Circuit
     let k = 4;
-    let circuit = arithmetic_circuit::empty_circuit(0);
+    let circuit = arithmetic_circuit::empty_circuit(Fr::ZERO);
     draw_graph(k, "img/arithmetic_circuit.svg", &circuit, Some(5));
 
     // Collatz
-    let k = 10;
-    let circuit = collatz::create_circuit(collatz_conjecture(4));
+    let max_steps = 31;
+    let k = collatz_k(max_steps);
+    let circuit =
+        collatz::create_circuit::<DEFAULT_LIMBS>(collatz_conjecture(4u64, max_steps), max_steps);
     draw_graph(k, "img/collatz.svg", &circuit, Some(1 << 6));
+    let circuit =
+        collatz::create_circuit::<WIDE_LIMBS>(collatz_conjecture(4u64, max_steps), max_steps);
+    draw_graph(k, "img/collatz_128.svg", &circuit, Some(1 << 6));
     // Poseidon hash
 
     let k = 6;
-    use rand_core::{OsRng, RngCore};
+    use rand_core::OsRng;
     const L: usize = 11;
     const WIDTH: usize = 12;
     const RATE: usize = 11;
-    let message: Vec<u64> = (0..L).map(|_| OsRng.next_u64()).collect::<Vec<_>>();
+    let message: Vec<Fr> = (0..L).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
     let circuit =
         poseidon_circuit::create_circuit::<PoseidonSpec<WIDTH, RATE>, WIDTH, RATE, L>(message);
 
@@ -0,0 +1,857 @@
+//! Membership proofs for binary Poseidon Merkle trees.
+//!
+//! A node is the Poseidon hash of its two children, with [`MerkleSpec`] and
+//! [`ConstantLength<2>`]. The circuit recomputes the root from a leaf and its
+//! authentication path, swapping each node with its sibling when the node is a right
+//! child, and exposes the root (and optionally the leaf) as public inputs.
+
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Chip, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::bn256::Fr,
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Constraints, Error, Expression, Instance,
+        Selector,
+    },
+    poly::Rotation,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    error::ProverError,
+    poseidon::{
+        primitives::{self as poseidon, ConstantLength, Spec},
+        Hash, Pow5Chip, Pow5Config,
+    },
+    poseidon_circuit::PoseidonSpec,
+    registry::DemoCircuit,
+    utils::{parse_encoding, FieldElement},
+};
+
+pub const MERKLE_WIDTH: usize = 3;
+pub const MERKLE_RATE: usize = 2;
+/// Deepest tree accepted by [`MerkleDemo`].
+pub const MERKLE_MAX_DEPTH: usize = 32;
+
+/// The Poseidon specification hashing the nodes of the tree.
+pub type MerkleSpec = PoseidonSpec<MERKLE_WIDTH, MERKLE_RATE>;
+
+/// Hash of the node whose children are `left` and `right`.
+pub fn hash_pair(left: Fr, right: Fr) -> Fr {
+    poseidon::Hash::<_, MerkleSpec, ConstantLength<2>, MERKLE_WIDTH, MERKLE_RATE>::init()
+        .hash([left, right])
+}
+
+/// A binary Merkle tree, storing the nodes above its given leaves and the roots of the
+/// empty subtrees, so that deep trees with few leaves stay small.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    /// The nodes of each level covering the given leaves, from the leaves up.
+    levels: Vec<Vec<Fr>>,
+    /// Roots of the empty subtrees of each height.
+    empty: Vec<Fr>,
+}
+
+impl MerkleTree {
+    /// Builds the tree of depth `depth` whose first leaves are `leaves`, the others
+    /// being zero.
+    pub fn new(leaves: &[Fr], depth: usize) -> Result<Self, ProverError> {
+        if depth > MERKLE_MAX_DEPTH || leaves.len() as u64 > 1u64 << depth {
+            return Err(ProverError::InvalidInput(format!(
+                "{} leaves do not fit in a tree of depth {}",
+                leaves.len(),
+                depth
+            )));
+        }
+
+        let mut empty = vec![Fr::ZERO];
+        for height in 0..depth {
+            empty.push(hash_pair(empty[height], empty[height]));
+        }
+
+        let mut levels = vec![leaves.to_vec()];
+        for height in 0..depth {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_pair(pair[0], pair.get(1).copied().unwrap_or(empty[height])))
+                .collect();
+            levels.push(level);
+        }
+        Ok(MerkleTree { levels, empty })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// The node at `position` of the level `height` above the leaves.
+    fn node(&self, height: usize, position: usize) -> Fr {
+        self.levels[height]
+            .get(position)
+            .copied()
+            .unwrap_or(self.empty[height])
+    }
+
+    pub fn root(&self) -> Fr {
+        self.node(self.depth(), 0)
+    }
+
+    pub fn leaf(&self, index: usize) -> Fr {
+        self.node(0, index)
+    }
+
+    /// The authentication path of the leaf at `index`, from the leaf up.
+    pub fn path(&self, index: usize) -> Result<MerklePath, ProverError> {
+        if index as u64 >= 1u64 << self.depth() {
+            return Err(ProverError::InvalidInput(format!(
+                "no leaf {} in a tree of depth {}",
+                index,
+                self.depth()
+            )));
+        }
+
+        let nodes = (0..self.depth())
+            .map(|height| {
+                let position = index >> height;
+                PathNode {
+                    sibling: self.node(height, position ^ 1),
+                    is_right: position & 1 == 1,
+                }
+            })
+            .collect();
+        Ok(MerklePath(nodes))
+    }
+}
+
+/// A sibling on the authentication path of a leaf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathNode {
+    /// The sibling, as a hex string or a JSON number.
+    #[serde(serialize_with = "serialize_fr", deserialize_with = "deserialize_fr")]
+    pub sibling: Fr,
+    /// Whether the node on the path is the right child, and its sibling the left one.
+    pub is_right: bool,
+}
+
+/// Authentication path of a leaf, from the leaf up to the children of the root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MerklePath(pub Vec<PathNode>);
+
+impl MerklePath {
+    /// The root of the tree containing `leaf` at the end of this path.
+    pub fn root(&self, leaf: Fr) -> Fr {
+        self.0.iter().fold(leaf, |node, step| {
+            if step.is_right {
+                hash_pair(step.sibling, node)
+            } else {
+                hash_pair(node, step.sibling)
+            }
+        })
+    }
+}
+
+fn serialize_fr<S: Serializer>(n: &Fr, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:?}", n))
+}
+
+/// Field elements are written in hex, and read as any [`FieldElement`].
+fn deserialize_fr<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Fr, D::Error> {
+    Ok(FieldElement::deserialize(deserializer)?.0)
+}
+
+fn deserialize_optional_fr<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Fr>, D::Error> {
+    Ok(Option::<FieldElement>::deserialize(deserializer)?.map(|FieldElement(n)| n))
+}
+
+fn serialize_optional_fr<S: Serializer>(n: &Option<Fr>, serializer: S) -> Result<S::Ok, S::Error> {
+    match n {
+        Some(n) => serialize_fr(n, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MerkleInput {
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_fr",
+        deserialize_with = "deserialize_optional_fr"
+    )]
+    pub leaf: Option<Fr>,
+    /// Authentication path of `leaf`. A verifier may leave it out and only give `depth`.
+    #[serde(default)]
+    pub path: Option<MerklePath>,
+    /// Claimed root, computed from `leaf` and `path` when missing.
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_fr",
+        deserialize_with = "deserialize_optional_fr"
+    )]
+    pub root: Option<Fr>,
+    #[serde(default)]
+    pub depth: Option<usize>,
+    /// Whether the leaf is a public input, next to the root.
+    #[serde(default)]
+    pub public_leaf: bool,
+}
+
+/// The fields of [`MerkleInput`] that determine the circuit.
+#[derive(Serialize, Deserialize)]
+pub struct MerkleShape {
+    pub depth: usize,
+    #[serde(default)]
+    pub public_leaf: bool,
+}
+
+/// The input proving that the leaf at `index` of the tree of `leaves` is a member.
+pub fn merkle_input(
+    leaves: &[Fr],
+    depth: usize,
+    index: usize,
+    public_leaf: bool,
+) -> Result<MerkleInput, ProverError> {
+    let tree = MerkleTree::new(leaves, depth)?;
+    let path = tree.path(index)?;
+    Ok(MerkleInput {
+        leaf: Some(tree.leaf(index)),
+        path: Some(path),
+        root: Some(tree.root()),
+        depth: Some(depth),
+        public_leaf,
+    })
+}
+
+/// Like [`merkle_input`], with `leaves` given as a JSON list and the input serialized.
+pub fn merkle_input_json(
+    leaves: &str,
+    depth: usize,
+    index: usize,
+    public_leaf: bool,
+) -> Result<String, ProverError> {
+    let leaves: Vec<FieldElement> = serde_json::from_str(leaves)?;
+    let leaves: Vec<Fr> = leaves.into_iter().map(|FieldElement(n)| n).collect();
+    Ok(serde_json::to_string(&merkle_input(
+        &leaves,
+        depth,
+        index,
+        public_leaf,
+    )?)?)
+}
+
+pub fn parse_string(s: &str) -> Result<MerkleInput, ProverError> {
+    Ok(serde_json::from_str(s)?)
+}
+
+/// Reads the shape of `s`, which is either a [`MerkleShape`] or a [`MerkleInput`] whose
+/// path length is the depth.
+fn parse_shape(s: &str) -> Result<MerkleShape, ProverError> {
+    let input = parse_string(s)?;
+    let depth = match (input.depth, &input.path) {
+        (Some(depth), Some(path)) if path.0.len() != depth => {
+            return Err(ProverError::InvalidInput(format!(
+                "`depth` is {} but `path` has {} nodes",
+                depth,
+                path.0.len()
+            )))
+        }
+        (Some(depth), _) => depth,
+        (None, Some(path)) => path.0.len(),
+        (None, None) => {
+            return Err(ProverError::InvalidInput(
+                "expected a `path` or its length `depth`".to_string(),
+            ))
+        }
+    };
+    if depth > MERKLE_MAX_DEPTH {
+        return Err(ProverError::InvalidInput(format!(
+            "expected a depth of at most {}, got {}",
+            MERKLE_MAX_DEPTH, depth
+        )));
+    }
+    Ok(MerkleShape {
+        depth,
+        public_leaf: input.public_leaf,
+    })
+}
+
+/// The leaf and path of `s`, which the prover must give.
+fn parse_witness(s: &str) -> Result<(Fr, MerklePath), ProverError> {
+    parse_shape(s)?;
+    let input = parse_string(s)?;
+    match (input.leaf, input.path) {
+        (Some(leaf), Some(path)) => Ok((leaf, path)),
+        _ => Err(ProverError::InvalidInput(
+            "expected a `leaf` and its `path`".to_string(),
+        )),
+    }
+}
+
+/// The root of `s`: the claimed `root`, which must match the one computed from `leaf`
+/// and `path` when they are given.
+fn parse_root(s: &str) -> Result<Fr, ProverError> {
+    let input = parse_string(s)?;
+    let computed = match (input.leaf, &input.path) {
+        (Some(leaf), Some(path)) => Some(path.root(leaf)),
+        _ => None,
+    };
+    match (input.root, computed) {
+        (Some(root), Some(computed)) if root != computed => Err(ProverError::InvalidInput(
+            format!("`root` is {:?} but the path leads to {:?}", root, computed),
+        )),
+        (Some(root), _) | (None, Some(root)) => Ok(root),
+        (None, None) => Err(ProverError::InvalidInput(
+            "expected a `root`, or a `leaf` and its `path`".to_string(),
+        )),
+    }
+}
+
+/// Configuration for a [`CondSwapChip`].
+#[derive(Clone, Debug)]
+pub struct CondSwapConfig {
+    a: Column<Advice>,
+    b: Column<Advice>,
+    swap: Column<Advice>,
+    left: Column<Advice>,
+    right: Column<Advice>,
+    s_swap: Selector,
+}
+
+/// Orders a node and its sibling: `(a, b)` if `swap` is zero, `(b, a)` if it is one.
+#[derive(Clone, Debug)]
+pub struct CondSwapChip<F: Field> {
+    config: CondSwapConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> Chip<F> for CondSwapChip<F> {
+    type Config = CondSwapConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: Field> CondSwapChip<F> {
+    /// Configures this chip on the columns `[a, b, swap, left, right]`.
+    ///
+    /// # Side-effects
+    ///
+    /// All five columns will be equality-enabled.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advices: [Column<Advice>; 5],
+    ) -> CondSwapConfig {
+        let [a, b, swap, left, right] = advices;
+        for column in advices {
+            meta.enable_equality(column);
+        }
+        let s_swap = meta.selector();
+
+        meta.create_gate("conditional swap", |meta| {
+            let s_swap = meta.query_selector(s_swap);
+            let a = meta.query_advice(a, Rotation::cur());
+            let b = meta.query_advice(b, Rotation::cur());
+            let swap = meta.query_advice(swap, Rotation::cur());
+            let left = meta.query_advice(left, Rotation::cur());
+            let right = meta.query_advice(right, Rotation::cur());
+            let one = Expression::Constant(F::ONE);
+
+            Constraints::with_selector(
+                s_swap,
+                [
+                    ("bool", swap.clone() * (one - swap.clone())),
+                    (
+                        "left",
+                        a.clone() + swap.clone() * (b.clone() - a.clone()) - left,
+                    ),
+                    ("right", b.clone() + swap * (a - b) - right),
+                ],
+            )
+        });
+
+        CondSwapConfig {
+            a,
+            b,
+            swap,
+            left,
+            right,
+            s_swap,
+        }
+    }
+
+    pub fn construct(config: CondSwapConfig) -> Self {
+        CondSwapChip {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns `(left, right)`: `(a, b)`, or `(b, a)` when `swap` is true.
+    pub fn swap(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: Value<F>,
+        swap: Value<bool>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let config = self.config();
+        layouter.assign_region(
+            || "conditional swap",
+            |mut region| {
+                config.s_swap.enable(&mut region, 0)?;
+
+                let a = a.copy_advice(|| "a", &mut region, config.a, 0)?;
+                region.assign_advice(|| "b", config.b, 0, || b)?;
+                region.assign_advice(
+                    || "swap",
+                    config.swap,
+                    0,
+                    || swap.map(|swap| if swap { F::ONE } else { F::ZERO }),
+                )?;
+
+                let pair =
+                    a.value().copied().zip(b).zip(swap).map(
+                        |((a, b), swap)| {
+                            if swap {
+                                (b, a)
+                            } else {
+                                (a, b)
+                            }
+                        },
+                    );
+                let left = region.assign_advice(
+                    || "left",
+                    config.left,
+                    0,
+                    || pair.map(|(left, _)| left),
+                )?;
+                let right = region.assign_advice(
+                    || "right",
+                    config.right,
+                    0,
+                    || pair.map(|(_, right)| right),
+                )?;
+                Ok((left, right))
+            },
+        )
+    }
+
+    /// Like [`CondSwapChip::swap`], for a sibling and a swap bit already assigned
+    /// elsewhere, e.g. shared by two paths.
+    pub fn swap_assigned(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+        swap: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let config = self.config();
+        layouter.assign_region(
+            || "conditional swap",
+            |mut region| {
+                config.s_swap.enable(&mut region, 0)?;
+
+                let a = a.copy_advice(|| "a", &mut region, config.a, 0)?;
+                let b = b.copy_advice(|| "b", &mut region, config.b, 0)?;
+                let swap = swap.copy_advice(|| "swap", &mut region, config.swap, 0)?;
+
+                let swap = swap.value().map(|swap| *swap == F::ONE);
+                let pair = a
+                    .value()
+                    .copied()
+                    .zip(b.value().copied())
+                    .zip(swap)
+                    .map(|((a, b), swap)| if swap { (b, a) } else { (a, b) });
+                let left = region.assign_advice(
+                    || "left",
+                    config.left,
+                    0,
+                    || pair.map(|(left, _)| left),
+                )?;
+                let right = region.assign_advice(
+                    || "right",
+                    config.right,
+                    0,
+                    || pair.map(|(_, right)| right),
+                )?;
+                Ok((left, right))
+            },
+        )
+    }
+}
+
+/// Proves that a leaf belongs to the Poseidon Merkle tree whose root is the first public
+/// input. With `public_leaf`, the leaf is the second one.
+#[derive(Clone)]
+pub struct MerkleCircuit {
+    leaf: Value<Fr>,
+    path: Vec<(Value<Fr>, Value<bool>)>,
+    public_leaf: bool,
+}
+
+impl MerkleCircuit {
+    pub fn new(leaf: Fr, path: &MerklePath, public_leaf: bool) -> Self {
+        MerkleCircuit {
+            leaf: Value::known(leaf),
+            path: path
+                .0
+                .iter()
+                .map(|node| (Value::known(node.sibling), Value::known(node.is_right)))
+                .collect(),
+            public_leaf,
+        }
+    }
+
+    pub fn empty(shape: &MerkleShape) -> Self {
+        MerkleCircuit {
+            leaf: Value::unknown(),
+            path: vec![(Value::unknown(), Value::unknown()); shape.depth],
+            public_leaf: shape.public_leaf,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleConfig {
+    leaf: Column<Advice>,
+    instance: Column<Instance>,
+    swap_config: CondSwapConfig,
+    poseidon_config: Pow5Config<Fr, MERKLE_WIDTH, MERKLE_RATE>,
+}
+
+impl Circuit<Fr> for MerkleCircuit {
+    type Config = MerkleConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        MerkleCircuit {
+            leaf: Value::unknown(),
+            path: vec![(Value::unknown(), Value::unknown()); self.path.len()],
+            public_leaf: self.public_leaf,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let state: [Column<Advice>; MERKLE_WIDTH] = (0..MERKLE_WIDTH)
+            .map(|_| meta.advice_column())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let partial_sbox = meta.advice_column();
+        let swap = meta.advice_column();
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+
+        let rc_a = (0..MERKLE_WIDTH)
+            .map(|_| meta.fixed_column())
+            .collect::<Vec<_>>();
+        let rc_b = (0..MERKLE_WIDTH)
+            .map(|_| meta.fixed_column())
+            .collect::<Vec<_>>();
+
+        meta.enable_constant(rc_b[0]);
+
+        MerkleConfig {
+            leaf: state[0],
+            instance,
+            swap_config: CondSwapChip::configure(
+                meta,
+                [state[0], partial_sbox, swap, state[1], state[2]],
+            ),
+            poseidon_config: Pow5Chip::configure::<MerkleSpec>(
+                meta,
+                state,
+                partial_sbox,
+                rc_a.try_into().unwrap(),
+                rc_b.try_into().unwrap(),
+            ),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let swap_chip = CondSwapChip::construct(config.swap_config.clone());
+
+        let leaf = layouter.assign_region(
+            || "load leaf",
+            |mut region| region.assign_advice(|| "leaf", config.leaf, 0, || self.leaf),
+        )?;
+
+        let mut node = leaf.clone();
+        for (level, (sibling, is_right)) in self.path.iter().enumerate() {
+            let (left, right) = swap_chip.swap(
+                layouter.namespace(|| format!("order level {}", level)),
+                &node,
+                *sibling,
+                *is_right,
+            )?;
+
+            let chip = Pow5Chip::construct(config.poseidon_config.clone());
+            let hasher =
+                Hash::<_, _, MerkleSpec, ConstantLength<2>, MERKLE_WIDTH, MERKLE_RATE>::init(
+                    chip,
+                    layouter.namespace(|| format!("init level {}", level)),
+                )?;
+            node = hasher.hash(
+                layouter.namespace(|| format!("hash level {}", level)),
+                [left, right],
+            )?;
+        }
+
+        layouter.constrain_instance(node.cell(), config.instance, 0)?;
+        if self.public_leaf {
+            layouter.constrain_instance(leaf.cell(), config.instance, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Smallest `k` whose params fit [`MerkleCircuit`] for a tree of depth `depth`.
+pub fn merkle_k(depth: usize) -> u32 {
+    let mut meta = ConstraintSystem::<Fr>::default();
+    MerkleCircuit::configure(&mut meta);
+
+    // Each level orders the pair in one row, then hashes it with one block: the initial
+    // state, three rows to add it, and the permutation.
+    let permutation = MerkleSpec::full_rounds() + (MerkleSpec::partial_rounds() + 1) / 2 + 1;
+    let level = 1 + 1 + 3 + permutation;
+    let rows = 1 + depth * level + meta.minimum_rows();
+    rows.next_power_of_two().trailing_zeros()
+}
+
+pub fn simulate_circuit(s: &str) -> Result<String, ProverError> {
+    Ok(parse_encoding(s)?.encode(&parse_root(s)?))
+}
+
+pub struct MerkleDemo;
+
+impl DemoCircuit for MerkleDemo {
+    type Circuit = MerkleCircuit;
+
+    fn slug(&self) -> &'static str {
+        "merkle"
+    }
+
+    fn name(&self) -> &'static str {
+        "Merkle membership"
+    }
+
+    fn description(&self) -> &'static str {
+        "Proves that a leaf belongs to the Poseidon Merkle tree with a public root"
+    }
+
+    fn input_schema(&self) -> &'static str {
+        r#"{ "leaf": "0x...", "path": [{ "sibling": "0x...", "is_right": false }], "public_leaf": true }"#
+    }
+
+    fn k(&self) -> u32 {
+        merkle_k(MERKLE_MAX_DEPTH)
+    }
+
+    fn k_for(&self, s: &str) -> Result<u32, ProverError> {
+        Ok(merkle_k(parse_shape(s)?.depth))
+    }
+
+    fn create_from_json(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        let (leaf, path) = parse_witness(s)?;
+        Ok(MerkleCircuit::new(leaf, &path, parse_shape(s)?.public_leaf))
+    }
+
+    fn empty(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        Ok(MerkleCircuit::empty(&parse_shape(s)?))
+    }
+
+    fn shape(&self, s: &str) -> Result<String, ProverError> {
+        Ok(serde_json::to_string(&parse_shape(s)?)?)
+    }
+
+    /// Uses the claimed `root` when present, so a verifier does not need the path.
+    fn public_inputs(&self, s: &str) -> Result<Vec<Fr>, ProverError> {
+        let mut public_inputs = vec![parse_root(s)?];
+        if parse_shape(s)?.public_leaf {
+            let leaf = parse_string(s)?.leaf.ok_or_else(|| {
+                ProverError::InvalidInput("expected the public `leaf`".to_string())
+            })?;
+            public_inputs.push(leaf);
+        }
+        Ok(public_inputs)
+    }
+
+    fn simulate(&self, s: &str) -> Result<String, ProverError> {
+        simulate_circuit(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::{arithmetic::Field, dev::MockProver, halo2curves::bn256::Fr};
+
+    use super::{
+        hash_pair, merkle_input, merkle_input_json, merkle_k, MerkleCircuit, MerkleDemo,
+        MerklePath, MerkleTree, PathNode, MERKLE_MAX_DEPTH,
+    };
+    use crate::{error::ProverError, registry::DemoCircuit, utils::fr_to_decimal};
+
+    fn leaves() -> Vec<Fr> {
+        (1..=6u64).map(Fr::from).collect()
+    }
+
+    #[test]
+    fn tree_matches_hashes() {
+        let tree = MerkleTree::new(&leaves(), 3).unwrap();
+        let h = |l: u64, r: u64| hash_pair(Fr::from(l), Fr::from(r));
+        let expected = hash_pair(hash_pair(h(1, 2), h(3, 4)), hash_pair(h(5, 6), h(0, 0)));
+        assert_eq!(tree.root(), expected);
+
+        for index in 0..8 {
+            let path = tree.path(index).unwrap();
+            assert_eq!(path.root(tree.leaf(index)), expected);
+        }
+        assert!(tree.path(8).is_err());
+        assert!(MerkleTree::new(&leaves(), 2).is_err());
+    }
+
+    #[test]
+    fn deep_tree_stores_only_its_leaves() {
+        let tree = MerkleTree::new(&leaves(), MERKLE_MAX_DEPTH).unwrap();
+        let shallow = MerkleTree::new(&leaves(), 3).unwrap();
+        let path = tree.path(5).unwrap();
+        assert_eq!(path.0.len(), MERKLE_MAX_DEPTH);
+        assert_eq!(&path.0[..3], &shallow.path(5).unwrap().0[..]);
+        assert_eq!(path.root(tree.leaf(5)), tree.root());
+
+        let last = (1u64 << MERKLE_MAX_DEPTH) - 1;
+        if let Ok(last) = usize::try_from(last) {
+            assert_eq!(tree.path(last).unwrap().root(Fr::ZERO), tree.root());
+        }
+        assert!(MerkleTree::new(&leaves(), MERKLE_MAX_DEPTH + 1).is_err());
+    }
+
+    #[test]
+    fn circuit_proves_membership() {
+        let tree = MerkleTree::new(&leaves(), 3).unwrap();
+        let k = merkle_k(3);
+
+        for index in [0, 3, 5, 7] {
+            let leaf = tree.leaf(index);
+            let path = tree.path(index).unwrap();
+
+            let circuit = MerkleCircuit::new(leaf, &path, false);
+            MockProver::run(k, &circuit, vec![vec![tree.root()]])
+                .unwrap()
+                .assert_satisfied();
+
+            let circuit = MerkleCircuit::new(leaf, &path, true);
+            MockProver::run(k, &circuit, vec![vec![tree.root(), leaf]])
+                .unwrap()
+                .assert_satisfied();
+            let prover =
+                MockProver::run(k, &circuit, vec![vec![tree.root(), leaf + Fr::ONE]]).unwrap();
+            assert!(prover.verify().is_err());
+
+            // Flipping a direction leads to another root.
+            let mut flipped = path.clone();
+            flipped.0[1].is_right = !flipped.0[1].is_right;
+            let circuit = MerkleCircuit::new(leaf, &flipped, false);
+            let prover = MockProver::run(k, &circuit, vec![vec![tree.root()]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn demo_from_json() {
+        let input = merkle_input(&leaves(), 3, 4, true).unwrap();
+        let s = serde_json::to_string(&input).unwrap();
+        let root = MerkleTree::new(&leaves(), 3).unwrap().root();
+
+        assert_eq!(MerkleDemo.simulate(&s).unwrap(), fr_to_decimal(&root));
+        let hex = s.replacen('{', r#"{"encoding":"hex","#, 1);
+        assert_eq!(MerkleDemo.simulate(&hex).unwrap(), format!("{:?}", root));
+        assert_eq!(
+            MerkleDemo.public_inputs(&s).unwrap(),
+            vec![root, Fr::from(5)]
+        );
+        assert_eq!(
+            MerkleDemo.shape(&s).unwrap(),
+            r#"{"depth":3,"public_leaf":true}"#
+        );
+
+        let circuit = MerkleDemo.create_from_json(&s).unwrap();
+        MockProver::run(
+            MerkleDemo.k_for(&s).unwrap(),
+            &circuit,
+            vec![MerkleDemo.public_inputs(&s).unwrap()],
+        )
+        .unwrap()
+        .assert_satisfied();
+
+        // A verifier only needs the root, the leaf and the depth.
+        let claim = serde_json::json!({
+            "root": format!("{:?}", root),
+            "leaf": 5,
+            "depth": 3,
+            "public_leaf": true,
+        })
+        .to_string();
+        assert_eq!(
+            MerkleDemo.public_inputs(&claim).unwrap(),
+            MerkleDemo.public_inputs(&s).unwrap()
+        );
+        assert_eq!(
+            MerkleDemo.shape(&claim).unwrap(),
+            MerkleDemo.shape(&s).unwrap()
+        );
+    }
+
+    #[test]
+    fn input_from_leaves() {
+        let s = merkle_input_json(r#"[1, 2, "0x03"]"#, 2, 2, false).unwrap();
+        let expected = merkle_input(&[1u64, 2, 3].map(Fr::from), 2, 2, false).unwrap();
+        assert_eq!(s, serde_json::to_string(&expected).unwrap());
+        assert!(merkle_input_json("[1, 2]", 2, 4, false).is_err());
+    }
+
+    #[test]
+    fn inconsistent_input_is_an_error() {
+        let path = MerklePath(vec![PathNode {
+            sibling: Fr::from(2),
+            is_right: false,
+        }]);
+        let s = serde_json::json!({ "leaf": 1, "path": path, "root": 3 }).to_string();
+        assert!(matches!(
+            MerkleDemo.public_inputs(&s),
+            Err(ProverError::InvalidInput(_))
+        ));
+
+        let s = serde_json::json!({ "leaf": 1, "path": path, "depth": 2 }).to_string();
+        assert!(matches!(
+            MerkleDemo.shape(&s),
+            Err(ProverError::InvalidInput(_))
+        ));
+
+        let s = serde_json::json!({ "depth": 2 }).to_string();
+        assert!(matches!(
+            MerkleDemo.create_from_json(&s),
+            Err(ProverError::InvalidInput(_))
+        ));
+    }
+}
@@ -14,7 +14,9 @@ mod pow5;
 pub use pow5::{Pow5Chip, Pow5Config, StateWord};
 
 pub mod primitives;
-use primitives::{Absorbing, ConstantLength, Domain, Spec, SpongeMode, Squeezing, State};
+use primitives::{
+    Absorbing, ConstantLength, Domain, Spec, SpongeMode, Squeezing, State, VariableLength,
+};
 
 /// A word from the padded input to a Poseidon sponge.
 #[derive(Clone, Debug)]
@@ -235,6 +237,25 @@ impl<
             )?;
         }
     }
+
+    /// Transitions the sponge back into its absorbing state. The next absorbed block is
+    /// added to the state the squeezed elements were read from.
+    pub fn finish_squeezing(
+        self,
+    ) -> Sponge<F, PoseidonChip, S, Absorbing<PaddedWord<F>, RATE>, D, T, RATE> {
+        Sponge {
+            chip: self.chip,
+            mode: Absorbing(
+                (0..RATE)
+                    .map(|_| None)
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            ),
+            state: self.state,
+            _marker: PhantomData::default(),
+        }
+    }
 }
 
 /// A Poseidon hash function, built around a sponge.
@@ -266,31 +287,120 @@ impl<
 }
 
 impl<
-        F: PrimeField,
-        PoseidonChip: PoseidonSpongeInstructions<F, S, ConstantLength<L>, T, RATE>,
+        F: Field,
+        PoseidonChip: PoseidonSpongeInstructions<F, S, D, T, RATE>,
         S: Spec<F, T, RATE>,
+        D: Domain<F, RATE>,
         const T: usize,
         const RATE: usize,
-        const L: usize,
-    > Hash<F, PoseidonChip, S, ConstantLength<L>, T, RATE>
+    > Hash<F, PoseidonChip, S, D, T, RATE>
 {
-    /// Hashes the given input.
-    pub fn hash(
+    /// Absorbs `message` and its padding, then squeezes `O` outputs.
+    fn hash_message<const O: usize>(
         mut self,
         mut layouter: impl Layouter<F>,
-        message: [AssignedCell<F, F>; L],
-    ) -> Result<AssignedCell<F, F>, Error> {
+        message: Vec<AssignedCell<F, F>>,
+    ) -> Result<[AssignedCell<F, F>; O], Error> {
+        let padding = D::padding(message.len());
         for (i, value) in message
             .into_iter()
             .map(PaddedWord::Message)
-            .chain(<ConstantLength<L> as Domain<F, RATE>>::padding(L).map(PaddedWord::Padding))
+            .chain(padding.into_iter().map(PaddedWord::Padding))
             .enumerate()
         {
             self.sponge
                 .absorb(layouter.namespace(|| format!("absorb_{}", i)), value)?;
         }
-        self.sponge
-            .finish_absorbing(layouter.namespace(|| "finish absorbing"))?
-            .squeeze(layouter.namespace(|| "squeeze"))
+        let mut sponge = self
+            .sponge
+            .finish_absorbing(layouter.namespace(|| "finish absorbing"))?;
+        let outputs: Result<Vec<_>, Error> = (0..O)
+            .map(|i| sponge.squeeze(layouter.namespace(|| format!("squeeze_{}", i))))
+            .collect();
+        Ok(outputs?.try_into().unwrap())
+    }
+}
+
+impl<
+        F: PrimeField,
+        PoseidonChip: PoseidonSpongeInstructions<F, S, ConstantLength<L, O>, T, RATE>,
+        S: Spec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+        const L: usize,
+        const O: usize,
+    > Hash<F, PoseidonChip, S, ConstantLength<L, O>, T, RATE>
+{
+    /// Hashes the given input into `O` outputs.
+    pub fn hash_outputs(
+        self,
+        layouter: impl Layouter<F>,
+        message: [AssignedCell<F, F>; L],
+    ) -> Result<[AssignedCell<F, F>; O], Error> {
+        self.hash_message(layouter, message.into())
+    }
+}
+
+impl<
+        F: PrimeField,
+        PoseidonChip: PoseidonSpongeInstructions<F, S, ConstantLength<L>, T, RATE>,
+        S: Spec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+        const L: usize,
+    > Hash<F, PoseidonChip, S, ConstantLength<L>, T, RATE>
+{
+    /// Hashes the given input.
+    pub fn hash(
+        self,
+        layouter: impl Layouter<F>,
+        message: [AssignedCell<F, F>; L],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let [output] = self.hash_outputs(layouter, message)?;
+        Ok(output)
+    }
+}
+
+impl<
+        F: PrimeField,
+        PoseidonChip: PoseidonSpongeInstructions<F, S, VariableLength<O>, T, RATE>,
+        S: Spec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+        const O: usize,
+    > Hash<F, PoseidonChip, S, VariableLength<O>, T, RATE>
+{
+    /// Hashes the given input into `O` outputs.
+    ///
+    /// The length of `message` is fixed when the circuit is synthesized, so circuits
+    /// hashing messages of different lengths have different keys.
+    pub fn hash_outputs(
+        self,
+        layouter: impl Layouter<F>,
+        message: Vec<AssignedCell<F, F>>,
+    ) -> Result<[AssignedCell<F, F>; O], Error> {
+        self.hash_message(layouter, message)
+    }
+}
+
+impl<
+        F: PrimeField,
+        PoseidonChip: PoseidonSpongeInstructions<F, S, VariableLength, T, RATE>,
+        S: Spec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+    > Hash<F, PoseidonChip, S, VariableLength, T, RATE>
+{
+    /// Hashes the given input.
+    ///
+    /// The length of `message` is fixed when the circuit is synthesized, so circuits
+    /// hashing messages of different lengths have different keys.
+    pub fn hash(
+        self,
+        layouter: impl Layouter<F>,
+        message: Vec<AssignedCell<F, F>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let [output] = self.hash_outputs(layouter, message)?;
+        Ok(output)
     }
 }
@@ -4,14 +4,16 @@ use std::iter;
 use halo2_proofs::{
     arithmetic::Field,
     circuit::{AssignedCell, Cell, Chip, Layouter, Region, Value},
+    halo2curves::ff::PrimeField,
     plonk::{
         Advice, Any, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Selector,
+        VirtualCells,
     },
     poly::Rotation,
 };
 
 use super::{
-    primitives::{Absorbing, Domain, Mds, Spec, Squeezing, State},
+    primitives::{Absorbing, Domain, Mds, Sbox, Spec, Squeezing, State},
     PaddedWord, PoseidonInstructions, PoseidonSpongeInstructions,
 };
 use halo2_gadgets::utilities::Var;
@@ -25,20 +27,23 @@ pub struct Pow5Config<F: Field, const WIDTH: usize, const RATE: usize> {
     rc_b: [Column<Fixed>; WIDTH],
     s_full: Selector,
     s_partial: Selector,
+    /// Enabled on the row of the last partial round, when their number is odd.
+    s_partial_single: Option<Selector>,
     s_pad_and_add: Selector,
 
     half_full_rounds: usize,
     half_partial_rounds: usize,
-    alpha: [u64; 4],
+    sbox: Sbox,
     round_constants: Vec<[F; WIDTH]>,
     m_reg: Mds<F, WIDTH>,
     m_inv: Mds<F, WIDTH>,
 }
 
-/// A Poseidon chip using an $x^5$ S-Box.
+/// A Poseidon chip using an $x^5$ S-Box, or any other [`Sbox`] of its [`Spec`].
 ///
 /// The chip is implemented using a single round per row for full rounds, and two rounds
-/// per row for partial rounds.
+/// per row for partial rounds. An odd number of partial rounds ends with a row holding a
+/// single partial round.
 #[derive(Debug)]
 pub struct Pow5Chip<F: Field, const WIDTH: usize, const RATE: usize> {
     config: Pow5Config<F, WIDTH, RATE>,
@@ -60,12 +65,15 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
         partial_sbox: Column<Advice>,
         rc_a: [Column<Fixed>; WIDTH],
         rc_b: [Column<Fixed>; WIDTH],
-    ) -> Pow5Config<F, WIDTH, RATE> {
-        assert_eq!(RATE, WIDTH - 1);
+    ) -> Pow5Config<F, WIDTH, RATE>
+    where
+        F: PrimeField,
+    {
+        // The capacity holds at least one word.
+        assert!(RATE < WIDTH);
         // Generate constants for the Poseidon permutation.
-        // This gadget requires R_F and R_P to be even.
+        // This gadget requires R_F to be even.
         assert!(S::full_rounds() & 1 == 0);
-        assert!(S::partial_rounds() & 1 == 0);
         let half_full_rounds = S::full_rounds() / 2;
         let half_partial_rounds = S::partial_rounds() / 2;
         let (round_constants, m_reg, m_inv) = S::constants();
@@ -84,12 +92,39 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
 
         let s_full = meta.selector();
         let s_partial = meta.selector();
+        let s_partial_single = (S::partial_rounds() & 1 == 1).then(|| meta.selector());
         let s_pad_and_add = meta.selector();
 
-        let alpha = [5, 0, 0, 0];
-        let pow_5 = |v: Expression<F>| {
-            let v2 = v.clone() * v.clone();
-            v2.clone() * v2 * v
+        let sbox = S::sbox_type();
+        if let Sbox::Pow(alpha) = sbox {
+            assert!(alpha >= 3, "the S-box exponent must be at least 3");
+        }
+        assert!(
+            sbox.is_permutation::<F>(),
+            "the S-box must permute the field"
+        );
+        // Constrains `output` to be the S-box of `input`. The inverse is constrained by
+        // `input * output^2 = output` and `input^2 * output = input`, which also hold for
+        // zero mapped to itself.
+        let sbox_constraints = move |input: Expression<F>, output: Expression<F>| match sbox {
+            Sbox::Pow(alpha) => {
+                let pow = (1..alpha).fold(input.clone(), |acc, _| acc * input.clone());
+                vec![pow - output]
+            }
+            Sbox::Inv => vec![
+                input.clone() * output.clone() * output.clone() - output.clone(),
+                input.clone() * input * output.clone() - output,
+            ],
+        };
+        // The S-box outputs of a round, recovered from the next state as `m_inv * next`.
+        let next = move |idx: usize, meta: &mut VirtualCells<F>| {
+            (0..WIDTH)
+                .map(|next_idx| {
+                    let next = meta.query_advice(state[next_idx], Rotation::next());
+                    next * m_inv[idx][next_idx]
+                })
+                .reduce(|acc, next| acc + next)
+                .expect("WIDTH > 0")
         };
 
         meta.create_gate("full round", |meta| {
@@ -98,17 +133,10 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
             Constraints::with_selector(
                 s_full,
                 (0..WIDTH)
-                    .map(|next_idx| {
-                        let state_next = meta.query_advice(state[next_idx], Rotation::next());
-                        let expr = (0..WIDTH)
-                            .map(|idx| {
-                                let state_cur = meta.query_advice(state[idx], Rotation::cur());
-                                let rc_a = meta.query_fixed(rc_a[idx], Rotation::cur());
-                                pow_5(state_cur + rc_a) * m_reg[next_idx][idx]
-                            })
-                            .reduce(|acc, term| acc + term)
-                            .expect("WIDTH > 0");
-                        expr - state_next
+                    .flat_map(|idx| {
+                        let state_cur = meta.query_advice(state[idx], Rotation::cur());
+                        let rc_a = meta.query_fixed(rc_a[idx], Rotation::cur());
+                        sbox_constraints(state_cur + rc_a, next(idx, meta))
                     })
                     .collect::<Vec<_>>(),
             )
@@ -123,7 +151,6 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
 
             let s_partial = meta.query_selector(s_partial);
 
-            use halo2_proofs::plonk::VirtualCells;
             let mid = |idx: usize, meta: &mut VirtualCells<F>| {
                 let mid = mid_0.clone() * m_reg[idx][0];
                 (1..WIDTH).fold(mid, |acc, cur_idx| {
@@ -133,16 +160,6 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
                 })
             };
 
-            let next = |idx: usize, meta: &mut VirtualCells<F>| {
-                (0..WIDTH)
-                    .map(|next_idx| {
-                        let next = meta.query_advice(state[next_idx], Rotation::next());
-                        next * m_inv[idx][next_idx]
-                    })
-                    .reduce(|acc, next| acc + next)
-                    .expect("WIDTH > 0")
-            };
-
             let partial_round_linear = |idx: usize, meta: &mut VirtualCells<F>| {
                 let rc_b = meta.query_fixed(rc_b[idx], Rotation::cur());
                 mid(idx, meta) + rc_b - next(idx, meta)
@@ -152,18 +169,36 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
                 s_partial,
                 std::iter::empty()
                     // state[0] round a
-                    .chain(Some(pow_5(cur_0 + rc_a0) - mid_0.clone()))
+                    .chain(sbox_constraints(cur_0 + rc_a0, mid_0.clone()))
                     // state[0] round b
-                    .chain(Some(pow_5(mid(0, meta) + rc_b0) - next(0, meta)))
+                    .chain(sbox_constraints(mid(0, meta) + rc_b0, next(0, meta)))
                     .chain((1..WIDTH).map(|idx| partial_round_linear(idx, meta)))
                     .collect::<Vec<_>>(),
             )
         });
 
-        meta.create_gate("pad-and-add", |meta| {
-            let initial_state_rate = meta.query_advice(state[RATE], Rotation::prev());
-            let output_state_rate = meta.query_advice(state[RATE], Rotation::next());
+        if let Some(s_partial_single) = s_partial_single {
+            meta.create_gate("single partial round", |meta| {
+                let s_partial_single = meta.query_selector(s_partial_single);
+
+                Constraints::with_selector(
+                    s_partial_single,
+                    (0..WIDTH)
+                        .flat_map(|idx| {
+                            let state_cur = meta.query_advice(state[idx], Rotation::cur());
+                            let rc_a = meta.query_fixed(rc_a[idx], Rotation::cur());
+                            if idx == 0 {
+                                sbox_constraints(state_cur + rc_a, next(idx, meta))
+                            } else {
+                                vec![state_cur + rc_a - next(idx, meta)]
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            });
+        }
 
+        meta.create_gate("pad-and-add", |meta| {
             let s_pad_and_add = meta.query_selector(s_pad_and_add);
 
             let pad_and_add = |idx: usize| {
@@ -176,12 +211,18 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
                 initial_state + input - output_state
             };
 
+            let keep_capacity = |idx: usize| {
+                let initial_state = meta.query_advice(state[idx], Rotation::prev());
+                let output_state = meta.query_advice(state[idx], Rotation::next());
+                initial_state - output_state
+            };
+
             Constraints::with_selector(
                 s_pad_and_add,
                 (0..RATE)
                     .map(pad_and_add)
-                    // The capacity element is never altered by the input.
-                    .chain(Some(initial_state_rate - output_state_rate))
+                    // The capacity elements are never altered by the input.
+                    .chain((RATE..WIDTH).map(keep_capacity))
                     .collect::<Vec<_>>(),
             )
         });
@@ -193,10 +234,11 @@ impl<F: Field, const WIDTH: usize, const RATE: usize> Pow5Chip<F, WIDTH, RATE> {
             rc_b,
             s_full,
             s_partial,
+            s_partial_single,
             s_pad_and_add,
             half_full_rounds,
             half_partial_rounds,
-            alpha,
+            sbox,
             round_constants,
             m_reg,
             m_inv,
@@ -255,13 +297,32 @@ impl<F: Field, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>
                     })
                 })?;
 
+                let (state, partial_rounds, partial_rows) = match config.s_partial_single {
+                    Some(s_partial_single) => (
+                        state.single_partial_round(
+                            &mut region,
+                            config,
+                            s_partial_single,
+                            config.half_full_rounds + 2 * config.half_partial_rounds,
+                            config.half_full_rounds + config.half_partial_rounds,
+                        )?,
+                        2 * config.half_partial_rounds + 1,
+                        config.half_partial_rounds + 1,
+                    ),
+                    None => (
+                        state,
+                        2 * config.half_partial_rounds,
+                        config.half_partial_rounds,
+                    ),
+                };
+
                 let state = (0..config.half_full_rounds).fold(Ok(state), |res, r| {
                     res.and_then(|state| {
                         state.full_round(
                             &mut region,
                             config,
-                            config.half_full_rounds + 2 * config.half_partial_rounds + r,
-                            config.half_full_rounds + config.half_partial_rounds + r,
+                            config.half_full_rounds + partial_rounds + r,
+                            config.half_full_rounds + partial_rows + r,
                         )
                     })
                 })?;
@@ -305,6 +366,9 @@ impl<
                     load_state_word(i, F::ZERO)?;
                 }
                 load_state_word(RATE, D::initial_capacity_element())?;
+                for i in (RATE + 1)..WIDTH {
+                    load_state_word(i, F::ZERO)?;
+                }
 
                 Ok(state)
             },
@@ -444,7 +508,7 @@ impl<F: Field, const WIDTH: usize> Pow5State<F, WIDTH> {
                     .value()
                     .map(|v| *v + config.round_constants[round][idx])
             });
-            let r: Value<Vec<F>> = q.map(|q| q.map(|q| q.pow(&config.alpha))).collect();
+            let r: Value<Vec<F>> = q.map(|q| q.map(|q| config.sbox.apply(q))).collect();
             let m = &config.m_reg;
             let state = m.iter().map(|m_i| {
                 r.as_ref().map(|r| {
@@ -470,7 +534,7 @@ impl<F: Field, const WIDTH: usize> Pow5State<F, WIDTH> {
             let p: Value<Vec<_>> = self.0.iter().map(|word| word.0.value().cloned()).collect();
 
             let r: Value<Vec<_>> = p.map(|p| {
-                let r_0 = (p[0] + config.round_constants[round][0]).pow(&config.alpha);
+                let r_0 = config.sbox.apply(p[0] + config.round_constants[round][0]);
                 let r_i = p[1..]
                     .iter()
                     .enumerate()
@@ -510,7 +574,9 @@ impl<F: Field, const WIDTH: usize> Pow5State<F, WIDTH> {
             }
 
             let r_mid: Value<Vec<_>> = p_mid.map(|p| {
-                let r_0 = (p[0] + config.round_constants[round + 1][0]).pow(&config.alpha);
+                let r_0 = config
+                    .sbox
+                    .apply(p[0] + config.round_constants[round + 1][0]);
                 let r_i = p[1..]
                     .iter()
                     .enumerate()
@@ -533,6 +599,43 @@ impl<F: Field, const WIDTH: usize> Pow5State<F, WIDTH> {
         })
     }
 
+    fn single_partial_round<const RATE: usize>(
+        self,
+        region: &mut Region<F>,
+        config: &Pow5Config<F, WIDTH, RATE>,
+        s_partial_single: Selector,
+        round: usize,
+        offset: usize,
+    ) -> Result<Self, Error> {
+        Self::round(region, config, round, offset, s_partial_single, |_| {
+            let r: Value<Vec<F>> = self
+                .0
+                .iter()
+                .enumerate()
+                .map(|(idx, word)| {
+                    word.0.value().map(|v| {
+                        let v = *v + config.round_constants[round][idx];
+                        if idx == 0 {
+                            config.sbox.apply(v)
+                        } else {
+                            v
+                        }
+                    })
+                })
+                .collect();
+            let m = &config.m_reg;
+            let state = m.iter().map(|m_i| {
+                r.as_ref().map(|r| {
+                    r.iter()
+                        .enumerate()
+                        .fold(F::ZERO, |acc, (j, r_j)| acc + m_i[j] * r_j)
+                })
+            });
+
+            Ok((round + 1, state.collect::<Vec<_>>().try_into().unwrap()))
+        })
+    }
+
     fn load<const RATE: usize>(
         region: &mut Region<F>,
         config: &Pow5Config<F, WIDTH, RATE>,
@@ -605,7 +708,10 @@ mod tests {
 
     use super::{PoseidonInstructions, Pow5Chip, Pow5Config, StateWord};
     use crate::poseidon::{
-        primitives::{self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier, Spec},
+        primitives::{
+            self as poseidon, generate_constants, ConstantLength, Mds,
+            P128Pow5T3 as OrchardNullifier, Sbox, Spec,
+        },
         Hash,
     };
     use crate::utils::draw_graph;
@@ -720,6 +826,39 @@ mod tests {
         assert_eq!(prover.verify(), Ok(()))
     }
 
+    /// Two capacity words and an odd number of partial rounds.
+    #[derive(Debug)]
+    struct WideCapacitySpec;
+
+    impl Spec<Fp, 5, 3> for WideCapacitySpec {
+        fn full_rounds() -> usize {
+            8
+        }
+
+        fn partial_rounds() -> usize {
+            57
+        }
+
+        fn sbox(val: Fp) -> Fp {
+            val.pow_vartime([5])
+        }
+
+        fn secure_mds() -> usize {
+            0
+        }
+
+        fn constants() -> (Vec<[Fp; 5]>, Mds<Fp, 5>, Mds<Fp, 5>) {
+            generate_constants::<_, Self, 5, 3>()
+        }
+    }
+
+    #[test]
+    fn poseidon_permute_wide_capacity() {
+        let circuit = PermuteCircuit::<WideCapacitySpec, 5, 3>(PhantomData);
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()))
+    }
+
     struct HashCircuit<
         S: Spec<Fp, WIDTH, RATE>,
         const WIDTH: usize,
@@ -870,6 +1009,202 @@ mod tests {
         }
     }
 
+    #[test]
+    fn poseidon_hash_wide_capacity() {
+        let rng = OsRng;
+
+        let message = [Fp::random(rng), Fp::random(rng), Fp::random(rng)];
+        let output =
+            poseidon::Hash::<_, WideCapacitySpec, ConstantLength<3>, 5, 3>::init().hash(message);
+
+        let circuit = HashCircuit::<WideCapacitySpec, 5, 3, 3> {
+            message: Value::known(message),
+            output: Value::known(output),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(7, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()))
+    }
+
+    #[derive(Debug)]
+    struct InverseSpec;
+
+    impl Spec<Fp, 3, 2> for InverseSpec {
+        fn full_rounds() -> usize {
+            8
+        }
+
+        fn partial_rounds() -> usize {
+            57
+        }
+
+        fn sbox_type() -> Sbox {
+            Sbox::Inv
+        }
+
+        fn secure_mds() -> usize {
+            0
+        }
+
+        fn constants() -> (Vec<[Fp; 3]>, Mds<Fp, 3>, Mds<Fp, 3>) {
+            generate_constants::<_, Self, 3, 2>()
+        }
+    }
+
+    #[derive(Debug)]
+    struct Pow7Spec;
+
+    impl Spec<Fp, 3, 2> for Pow7Spec {
+        fn full_rounds() -> usize {
+            8
+        }
+
+        fn partial_rounds() -> usize {
+            56
+        }
+
+        fn sbox_type() -> Sbox {
+            Sbox::Pow(7)
+        }
+
+        fn secure_mds() -> usize {
+            0
+        }
+
+        fn constants() -> (Vec<[Fp; 3]>, Mds<Fp, 3>, Mds<Fp, 3>) {
+            generate_constants::<_, Self, 3, 2>()
+        }
+    }
+
+    fn check_hash<S: Spec<Fp, 3, 2>>(k: u32) {
+        let rng = OsRng;
+
+        let message = [Fp::random(rng), Fp::random(rng)];
+        let output = poseidon::Hash::<_, S, ConstantLength<2>, 3, 2>::init().hash(message);
+
+        let circuit = HashCircuit::<S, 3, 2, 2> {
+            message: Value::known(message),
+            output: Value::known(output),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = HashCircuit::<S, 3, 2, 2> {
+            message: Value::known(message),
+            output: Value::known(output + Fp::ONE),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn poseidon_hash_inverse_sbox() {
+        check_hash::<InverseSpec>(7);
+    }
+
+    #[test]
+    fn poseidon_hash_pow7_sbox() {
+        check_hash::<Pow7Spec>(7);
+    }
+
+    /// Hashes two words into three outputs, one more than fits in one squeeze.
+    struct MultiOutputCircuit {
+        message: Value<[Fp; 2]>,
+        outputs: Value<[Fp; 3]>,
+    }
+
+    impl Circuit<Fp> for MultiOutputCircuit {
+        type Config = Pow5Config<Fp, 3, 2>;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                message: Value::unknown(),
+                outputs: Value::unknown(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Pow5Config<Fp, 3, 2> {
+            HashCircuit::<OrchardNullifier, 3, 2, 2>::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Pow5Config<Fp, 3, 2>,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = Pow5Chip::construct(config.clone());
+
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    let message: Result<Vec<_>, Error> = (0..2)
+                        .map(|i| {
+                            region.assign_advice(
+                                || format!("load message_{}", i),
+                                config.state[i],
+                                0,
+                                || self.message.map(|message| message[i]),
+                            )
+                        })
+                        .collect();
+                    Ok(message?.try_into().unwrap())
+                },
+            )?;
+
+            let hasher = Hash::<_, _, OrchardNullifier, ConstantLength<2, 3>, 3, 2>::init(
+                chip,
+                layouter.namespace(|| "init"),
+            )?;
+            let outputs = hasher.hash_outputs(layouter.namespace(|| "hash"), message)?;
+
+            layouter.assign_region(
+                || "constrain outputs",
+                |mut region| {
+                    for (i, output) in outputs.iter().enumerate() {
+                        let expected = region.assign_advice(
+                            || format!("load output_{}", i),
+                            config.state[i],
+                            0,
+                            || self.outputs.map(|outputs| outputs[i]),
+                        )?;
+                        region.constrain_equal(output.cell(), expected.cell())?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn poseidon_hash_multiple_outputs() {
+        let rng = OsRng;
+
+        let message = [Fp::random(rng), Fp::random(rng)];
+        let outputs = poseidon::Hash::<_, OrchardNullifier, ConstantLength<2, 3>, 3, 2>::init()
+            .hash_outputs(message);
+
+        let circuit = MultiOutputCircuit {
+            message: Value::known(message),
+            outputs: Value::known(outputs),
+        };
+        let prover = MockProver::run(7, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let mut forged = outputs;
+        forged[2] += Fp::ONE;
+        let circuit = MultiOutputCircuit {
+            message: Value::known(message),
+            outputs: Value::known(forged),
+        };
+        let prover = MockProver::run(7, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
     #[test]
     fn print_poseidon_chip() {
         use plotters::prelude::*;
@@ -29,6 +29,57 @@ pub(crate) type SpongeRate<F, const RATE: usize> = [Option<F>; RATE];
 /// The type used to hold the MDS matrix and its inverse.
 pub type Mds<F, const T: usize> = [[F; T]; T];
 
+/// The S-box of a Poseidon permutation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sbox {
+    /// $x^\alpha$ for the given $\alpha$, which must be coprime to $p - 1$ for the S-box
+    /// to be a permutation. [`Sbox::is_permutation`] checks this.
+    Pow(u64),
+    /// $x^{-1}$, mapping zero to itself.
+    Inv,
+}
+
+impl Sbox {
+    /// Applies this S-box to `val`.
+    pub fn apply<F: Field>(&self, val: F) -> F {
+        match self {
+            Sbox::Pow(alpha) => val.pow_vartime([*alpha]),
+            Sbox::Inv => val.invert().unwrap_or(F::ZERO),
+        }
+    }
+
+    /// Whether this S-box permutes `F`: always for $x^{-1}$, and for $x^\alpha$ when
+    /// $\gcd(\alpha, p - 1) = 1$.
+    pub fn is_permutation<F: PrimeField>(&self) -> bool {
+        match *self {
+            Sbox::Pow(0) => false,
+            Sbox::Pow(alpha) => {
+                let alpha = u128::from(alpha);
+                let p_mod_alpha = F::MODULUS
+                    .trim_start_matches("0x")
+                    .chars()
+                    .map(|digit| u128::from(digit.to_digit(16).expect("the modulus is hex")))
+                    .fold(0, |acc, digit| (acc * 16 + digit) % alpha);
+                let (mut a, mut b) = (alpha, (p_mod_alpha + alpha - 1) % alpha);
+                while b != 0 {
+                    (a, b) = (b, a % b);
+                }
+                a == 1
+            }
+            Sbox::Inv => true,
+        }
+    }
+}
+
+impl From<Sbox> for SboxType {
+    fn from(sbox: Sbox) -> SboxType {
+        match sbox {
+            Sbox::Pow(_) => SboxType::Pow,
+            Sbox::Inv => SboxType::Inv,
+        }
+    }
+}
+
 /// A specification for a Poseidon permutation.
 pub trait Spec<F: Field, const T: usize, const RATE: usize>: fmt::Debug {
     /// The number of full rounds for this specification.
@@ -39,18 +90,43 @@ pub trait Spec<F: Field, const T: usize, const RATE: usize>: fmt::Debug {
     /// The number of partial rounds for this specification.
     fn partial_rounds() -> usize;
 
+    /// The S-box type for this specification, used to generate its constants and by
+    /// `Pow5Chip`'s gates.
+    ///
+    /// Defaults to $x^5$.
+    fn sbox_type() -> Sbox {
+        Sbox::Pow(5)
+    }
+
     /// The S-box for this specification.
-    fn sbox(val: F) -> F;
+    ///
+    /// Defaults to applying [`Spec::sbox_type`].
+    fn sbox(val: F) -> F {
+        Self::sbox_type().apply(val)
+    }
 
     /// Side-loaded index of the first correct and secure MDS that will be generated by
     /// the reference implementation.
     ///
-    /// This is used by the default implementation of [`Spec::constants`]. If you are
-    /// hard-coding the constants, you may leave this unimplemented.
-    fn secure_mds() -> usize;
+    /// This is only read by [`generate_constants`]; specifications that hard-code their
+    /// constants, or use no MDS matrix at all, can keep the default of 0.
+    fn secure_mds() -> usize {
+        0
+    }
 
     /// Generates `(round_constants, mds, mds^-1)` corresponding to this specification.
     fn constants() -> (Vec<[F; T]>, Mds<F, T>, Mds<F, T>);
+
+    /// Applies the permutation of this specification to `state`, given the round
+    /// constants and MDS matrix from [`Spec::constants`].
+    ///
+    /// Defaults to the Poseidon permutation.
+    fn permute(state: &mut [F; T], mds: &Mds<F, T>, round_constants: &[[F; T]])
+    where
+        Self: Sized,
+    {
+        permute::<F, Self, T, RATE>(state, mds, round_constants)
+    }
 }
 
 /// Generates `(round_constants, mds, mds^-1)` corresponding to this specification.
@@ -63,7 +139,7 @@ pub fn generate_constants<
     let r_f = S::full_rounds();
     let r_p = S::partial_rounds();
 
-    let mut grain = grain::Grain::new(SboxType::Pow, T as u16, r_f as u16, r_p as u16);
+    let mut grain = grain::Grain::new(S::sbox_type().into(), T as u16, r_f as u16, r_p as u16);
 
     let round_constants = (0..(r_f + r_p))
         .map(|_| {
@@ -83,6 +159,60 @@ pub fn generate_constants<
     (round_constants, mds, mds_inv)
 }
 
+/// Round numbers `(R_F, R_P)` of a Poseidon permutation of width `t` with an $x^\alpha$
+/// S-box, over a field of `field_bits` bits, for `security_bits` bits of security.
+///
+/// This follows `calc_round_numbers.py` of the Poseidon reference implementation: among
+/// the round numbers resisting the statistical, interpolation and Gröbner basis attacks
+/// of [ePrint 2019/458 section 5.5](https://eprint.iacr.org/2019/458.pdf), it picks the
+/// ones with the fewest S-boxes, then adds the security margin of two full rounds and
+/// 7.5% more partial rounds.
+pub fn round_numbers(field_bits: u32, t: usize, alpha: u64, security_bits: u32) -> (usize, usize) {
+    let n = f64::from(field_bits);
+    let m = f64::from(security_bits);
+    let width = t as f64;
+    let alpha_f = alpha as f64;
+    let log_alpha = |x: f64| x.ln() / alpha_f.ln();
+
+    let secure = |r_f: usize, r_p: usize| {
+        let r_p = r_p as f64;
+        let statistical = if m <= (n - (alpha_f - 1.0) / 2.0).floor() * (width + 1.0) {
+            6.0
+        } else {
+            10.0
+        };
+        let interpolation =
+            1.0 + (log_alpha(2.0) * m.min(n)).ceil() + log_alpha(width).ceil() - r_p;
+        let groebner_1 = 1.0 + log_alpha(2.0) * (m / 3.0).min(n / 2.0) - r_p;
+        let groebner_2 =
+            width - 1.0 + (log_alpha(2.0) * m / (width + 1.0)).min(log_alpha(2.0) * n / 2.0) - r_p;
+        let min_full_rounds = [statistical, interpolation, groebner_1, groebner_2]
+            .into_iter()
+            .map(f64::ceil)
+            .fold(f64::MIN, f64::max);
+        r_f as f64 >= min_full_rounds
+    };
+
+    let mut best: Option<(usize, usize)> = None;
+    for r_p in 1..500 {
+        for r_f in (4..100).step_by(2).filter(|&r_f| secure(r_f, r_p)) {
+            let (r_f, r_p) = (r_f + 2, (r_p as f64 * 1.075).ceil() as usize);
+            let cost = r_f * t + r_p;
+            let better = match best {
+                None => true,
+                Some((best_f, best_p)) => {
+                    let best_cost = best_f * t + best_p;
+                    cost < best_cost || (cost == best_cost && r_f < best_f)
+                }
+            };
+            if better {
+                best = Some((r_f, r_p));
+            }
+        }
+    }
+    best.expect("some round numbers are secure")
+}
+
 /// Runs the Poseidon permutation on the given state.
 pub(crate) fn permute<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>(
     state: &mut State<F, T>,
@@ -145,7 +275,7 @@ fn poseidon_sponge<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: us
         }
     }
 
-    permute::<F, S, T, RATE>(state, mds_matrix, round_constants);
+    S::permute(state, mds_matrix, round_constants);
 
     let mut output = [None; RATE];
     for (word, value) in output.iter_mut().zip(state.iter()) {
@@ -186,14 +316,13 @@ impl<F: fmt::Debug, const RATE: usize> Absorbing<F, RATE> {
     }
 }
 
-/// A Poseidon sponge.
-pub(crate) struct Sponge<
-    F: Field,
-    S: Spec<F, T, RATE>,
-    M: SpongeMode,
-    const T: usize,
-    const RATE: usize,
-> {
+/// A Poseidon duplex sponge.
+///
+/// Elements are absorbed in blocks of `RATE`: a block must be complete when the sponge
+/// permutes it, so callers pad their input, for instance with [`Domain::padding`].
+/// Absorbing and squeezing may be interleaved with [`Sponge::finish_absorbing`] and
+/// [`Sponge::finish_squeezing`].
+pub struct Sponge<F: Field, S: Spec<F, T, RATE>, M: SpongeMode, const T: usize, const RATE: usize> {
     mode: M,
     state: State<F, T>,
     mds_matrix: Mds<F, T>,
@@ -205,7 +334,7 @@ impl<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
     Sponge<F, S, Absorbing<F, RATE>, T, RATE>
 {
     /// Constructs a new sponge for the given Poseidon specification.
-    pub(crate) fn new(initial_capacity_element: F) -> Self {
+    pub fn new(initial_capacity_element: F) -> Self {
         let (round_constants, mds_matrix, _) = S::constants();
 
         let mode = Absorbing([None; RATE]);
@@ -222,7 +351,7 @@ impl<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
     }
 
     /// Absorbs an element into the sponge.
-    pub(crate) fn absorb(&mut self, value: F) {
+    pub fn absorb(&mut self, value: F) {
         for entry in self.mode.0.iter_mut() {
             if entry.is_none() {
                 *entry = Some(value);
@@ -241,7 +370,11 @@ impl<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
     }
 
     /// Transitions the sponge into its squeezing state.
-    pub(crate) fn finish_absorbing(mut self) -> Sponge<F, S, Squeezing<F, RATE>, T, RATE> {
+    ///
+    /// # Panics
+    ///
+    /// Panics if the last block is not complete.
+    pub fn finish_absorbing(mut self) -> Sponge<F, S, Squeezing<F, RATE>, T, RATE> {
         let mode = poseidon_sponge::<F, S, T, RATE>(
             &mut self.state,
             Some(&self.mode),
@@ -263,7 +396,7 @@ impl<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
     Sponge<F, S, Squeezing<F, RATE>, T, RATE>
 {
     /// Squeezes an element from the sponge.
-    pub(crate) fn squeeze(&mut self) -> F {
+    pub fn squeeze(&mut self) -> F {
         loop {
             for entry in self.mode.0.iter_mut() {
                 if let Some(e) = entry.take() {
@@ -280,6 +413,18 @@ impl<F: Field, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
             );
         }
     }
+
+    /// Transitions the sponge back into its absorbing state. The next absorbed block is
+    /// added to the state the squeezed elements were read from.
+    pub fn finish_squeezing(self) -> Sponge<F, S, Absorbing<F, RATE>, T, RATE> {
+        Sponge {
+            mode: Absorbing([None; RATE]),
+            state: self.state,
+            mds_matrix: self.mds_matrix,
+            round_constants: self.round_constants,
+            _marker: PhantomData::default(),
+        }
+    }
 }
 
 /// A domain in which a Poseidon hash function is being used.
@@ -297,23 +442,25 @@ pub trait Domain<F: Field, const RATE: usize> {
     fn padding(input_len: usize) -> Self::Padding;
 }
 
-/// A Poseidon hash function used with constant input length.
+/// A Poseidon hash function used with constant input length `L`, producing `O` outputs.
 ///
 /// Domain specified in [ePrint 2019/458 section 4.2](https://eprint.iacr.org/2019/458.pdf).
 #[derive(Clone, Copy, Debug)]
-pub struct ConstantLength<const L: usize>;
+pub struct ConstantLength<const L: usize, const O: usize = 1>;
 
-impl<F: PrimeField, const RATE: usize, const L: usize> Domain<F, RATE> for ConstantLength<L> {
+impl<F: PrimeField, const RATE: usize, const L: usize, const O: usize> Domain<F, RATE>
+    for ConstantLength<L, O>
+{
     type Padding = iter::Take<iter::Repeat<F>>;
 
     fn name() -> String {
-        format!("ConstantLength<{}>", L)
+        format!("ConstantLength<{}, {}>", L, O)
     }
 
     fn initial_capacity_element() -> F {
         // Capacity value is $length \cdot 2^64 + (o-1)$ where o is the output length.
-        // We hard-code an output length of 1.
-        F::from_u128((L as u128) << 64)
+        assert!(O > 0, "a hash has at least one output");
+        F::from_u128(((L as u128) << 64) + (O as u128 - 1))
     }
 
     fn padding(input_len: usize) -> Self::Padding {
@@ -327,6 +474,34 @@ impl<F: PrimeField, const RATE: usize, const L: usize> Domain<F, RATE> for Const
     }
 }
 
+/// A Poseidon hash function used with variable input length, producing `O` outputs.
+///
+/// Domain specified in [ePrint 2019/458 section 4.2](https://eprint.iacr.org/2019/458.pdf).
+#[derive(Clone, Copy, Debug)]
+pub struct VariableLength<const O: usize = 1>;
+
+impl<F: PrimeField, const RATE: usize, const O: usize> Domain<F, RATE> for VariableLength<O> {
+    type Padding = iter::Chain<iter::Once<F>, iter::Take<iter::Repeat<F>>>;
+
+    fn name() -> String {
+        format!("VariableLength<{}>", O)
+    }
+
+    fn initial_capacity_element() -> F {
+        // Capacity value is $2^64 + (o-1)$ where o is the output length.
+        assert!(O > 0, "a hash has at least one output");
+        F::from_u128((1 << 64) + (O as u128 - 1))
+    }
+
+    fn padding(input_len: usize) -> Self::Padding {
+        // The length is not known ahead of time, so the padding itself must be
+        // sponge-compliant: a single one followed by as many zeroes as needed to reach
+        // a multiple of RATE, possibly none.
+        let k = input_len / RATE + 1;
+        iter::once(F::ONE).chain(iter::repeat(F::ZERO).take(k * RATE - input_len - 1))
+    }
+}
+
 /// A Poseidon hash function, built around a sponge.
 pub struct Hash<
     F: Field,
@@ -365,27 +540,90 @@ impl<F: Field, S: Spec<F, T, RATE>, D: Domain<F, RATE>, const T: usize, const RA
     }
 }
 
+impl<F: Field, S: Spec<F, T, RATE>, D: Domain<F, RATE>, const T: usize, const RATE: usize>
+    Hash<F, S, D, T, RATE>
+{
+    /// Absorbs `message` and its padding, leaving the sponge ready to squeeze outputs.
+    fn absorb_message(
+        mut self,
+        message: impl ExactSizeIterator<Item = F>,
+    ) -> Sponge<F, S, Squeezing<F, RATE>, T, RATE> {
+        let padding = D::padding(message.len());
+        for value in message.chain(padding) {
+            self.sponge.absorb(value);
+        }
+        self.sponge.finish_absorbing()
+    }
+}
+
+impl<
+        F: PrimeField,
+        S: Spec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+        const L: usize,
+        const O: usize,
+    > Hash<F, S, ConstantLength<L, O>, T, RATE>
+{
+    /// Hashes the given input into `O` outputs.
+    pub fn hash_outputs(self, message: [F; L]) -> [F; O] {
+        let mut sponge = self.absorb_message(message.into_iter());
+        std::array::from_fn(|_| sponge.squeeze())
+    }
+}
+
 impl<F: PrimeField, S: Spec<F, T, RATE>, const T: usize, const RATE: usize, const L: usize>
     Hash<F, S, ConstantLength<L>, T, RATE>
 {
     /// Hashes the given input.
-    pub fn hash(mut self, message: [F; L]) -> F {
-        for value in message
-            .into_iter()
-            .chain(<ConstantLength<L> as Domain<F, RATE>>::padding(L))
-        {
-            self.sponge.absorb(value);
-        }
-        self.sponge.finish_absorbing().squeeze()
+    pub fn hash(self, message: [F; L]) -> F {
+        let [output] = self.hash_outputs(message);
+        output
+    }
+}
+
+impl<F: PrimeField, S: Spec<F, T, RATE>, const T: usize, const RATE: usize, const O: usize>
+    Hash<F, S, VariableLength<O>, T, RATE>
+{
+    /// Hashes the given input into `O` outputs.
+    pub fn hash_outputs(self, message: &[F]) -> [F; O] {
+        let mut sponge = self.absorb_message(message.iter().copied());
+        std::array::from_fn(|_| sponge.squeeze())
+    }
+}
+
+impl<F: PrimeField, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>
+    Hash<F, S, VariableLength, T, RATE>
+{
+    /// Hashes the given input.
+    pub fn hash(self, message: &[F]) -> F {
+        let [output] = self.hash_outputs(message);
+        output
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use halo2_proofs::halo2curves::ff::PrimeField;
-    use halo2_proofs::halo2curves::pasta::pallas;
+    use halo2_proofs::halo2curves::ff::{Field, PrimeField};
+    use halo2_proofs::halo2curves::{bn256::Fr, pasta::pallas};
 
-    use super::{permute, ConstantLength, Hash, P128Pow5T3 as OrchardNullifier, Spec};
+    use super::{
+        generate_constants, permute, round_numbers, ConstantLength, Domain, Hash, Mds,
+        P128Pow5T3 as OrchardNullifier, Sbox, Spec, Sponge, VariableLength,
+    };
+
+    #[test]
+    fn sbox_permutations() {
+        // p - 1 is a multiple of 3 for both fields, but not of 5 or 7.
+        for sbox in [Sbox::Pow(5), Sbox::Pow(7), Sbox::Inv] {
+            assert!(sbox.is_permutation::<pallas::Base>());
+            assert!(sbox.is_permutation::<Fr>());
+        }
+        for sbox in [Sbox::Pow(0), Sbox::Pow(3), Sbox::Pow(15)] {
+            assert!(!sbox.is_permutation::<pallas::Base>());
+            assert!(!sbox.is_permutation::<Fr>());
+        }
+    }
 
     #[test]
     fn orchard_spec_equivalence() {
@@ -402,4 +640,143 @@ mod tests {
         permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
         assert_eq!(state[0], result);
     }
+
+    #[derive(Debug)]
+    struct InverseSpec;
+
+    impl Spec<pallas::Base, 3, 2> for InverseSpec {
+        fn full_rounds() -> usize {
+            8
+        }
+
+        fn partial_rounds() -> usize {
+            56
+        }
+
+        fn sbox_type() -> Sbox {
+            Sbox::Inv
+        }
+
+        fn secure_mds() -> usize {
+            0
+        }
+
+        fn constants() -> (
+            Vec<[pallas::Base; 3]>,
+            Mds<pallas::Base, 3>,
+            Mds<pallas::Base, 3>,
+        ) {
+            generate_constants::<_, Self, 3, 2>()
+        }
+    }
+
+    #[test]
+    fn inverse_sbox() {
+        let x = pallas::Base::from(7);
+        assert_eq!(InverseSpec::sbox(x) * x, pallas::Base::ONE);
+        assert_eq!(InverseSpec::sbox(pallas::Base::ZERO), pallas::Base::ZERO);
+        assert_eq!(
+            Sbox::Pow(5).apply(x),
+            <OrchardNullifier as Spec<pallas::Base, 3, 2>>::sbox(x)
+        );
+
+        // The S-box type is part of the Grain seed.
+        let (inverse_constants, _, _) = InverseSpec::constants();
+        let (orchard_constants, _, _) = <OrchardNullifier as Spec<pallas::Base, 3, 2>>::constants();
+        assert_ne!(inverse_constants[0], orchard_constants[0]);
+    }
+
+    #[test]
+    fn variable_length_padding() {
+        let hash = |message: &[u64]| {
+            let message: Vec<_> = message.iter().copied().map(pallas::Base::from).collect();
+            Hash::<_, OrchardNullifier, VariableLength, 3, 2>::init().hash(&message)
+        };
+
+        // A single block is the message, the padding one, and the length-free capacity.
+        let (round_constants, mds, _) = OrchardNullifier::constants();
+        let mut state = [
+            pallas::Base::from(6),
+            pallas::Base::ONE,
+            pallas::Base::from_u128(1 << 64),
+        ];
+        permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(state[0], hash(&[6]));
+
+        // Trailing zeroes and the padding one are not confused with the message.
+        assert_ne!(hash(&[6]), hash(&[6, 0]));
+        assert_ne!(hash(&[6, 1]), hash(&[6]));
+        assert_ne!(hash(&[]), hash(&[0]));
+        assert_ne!(
+            hash(&[6, 42]),
+            Hash::<_, OrchardNullifier, ConstantLength<2>, 3, 2>::init()
+                .hash([pallas::Base::from(6), pallas::Base::from(42)])
+        );
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        let message = [pallas::Base::from(6), pallas::Base::from(42)];
+        let (round_constants, mds, _) = OrchardNullifier::constants();
+
+        // The capacity encodes the three outputs, the first two of which are read from the
+        // first permutation and the third from the next one.
+        let outputs =
+            Hash::<_, OrchardNullifier, ConstantLength<2, 3>, 3, 2>::init().hash_outputs(message);
+        let mut state = [
+            message[0],
+            message[1],
+            pallas::Base::from_u128((2 << 64) + 2),
+        ];
+        permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(outputs[..2], state[..2]);
+        permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(outputs[2], state[0]);
+
+        let single = Hash::<_, OrchardNullifier, ConstantLength<2>, 3, 2>::init().hash(message);
+        assert_ne!(outputs[0], single);
+
+        let [first, _] =
+            Hash::<_, OrchardNullifier, VariableLength<2>, 3, 2>::init().hash_outputs(&message);
+        assert_ne!(
+            first,
+            Hash::<_, OrchardNullifier, VariableLength, 3, 2>::init().hash(&message)
+        );
+    }
+
+    #[test]
+    fn duplex_sponge() {
+        let (round_constants, mds, _) = OrchardNullifier::constants();
+        let capacity = <VariableLength as Domain<pallas::Base, 2>>::initial_capacity_element();
+        let words: Vec<_> = (1..=4).map(pallas::Base::from).collect();
+
+        let mut sponge = Sponge::<_, OrchardNullifier, _, 3, 2>::new(capacity);
+        sponge.absorb(words[0]);
+        sponge.absorb(words[1]);
+        let mut sponge = sponge.finish_absorbing();
+        let first = sponge.squeeze();
+        let mut sponge = sponge.finish_squeezing();
+        sponge.absorb(words[2]);
+        sponge.absorb(words[3]);
+        let second = sponge.finish_absorbing().squeeze();
+
+        let mut state = [words[0], words[1], capacity];
+        permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(first, state[0]);
+        state[0] += words[2];
+        state[1] += words[3];
+        permute::<_, OrchardNullifier, 3, 2>(&mut state, &mds, &round_constants);
+        assert_eq!(second, state[0]);
+    }
+
+    #[test]
+    fn round_numbers_match_orchard() {
+        assert_eq!(
+            round_numbers(pallas::Base::NUM_BITS, 3, 5, 128),
+            (
+                OrchardNullifier::full_rounds(),
+                OrchardNullifier::partial_rounds()
+            )
+        );
+    }
 }
@@ -26,11 +26,10 @@ impl FieldType {
 }
 
 #[derive(Debug, Clone, Copy)]
-pub(super) enum SboxType {
+pub(crate) enum SboxType {
     /// x^alpha
     Pow,
     /// x^(-1)
-    #[allow(dead_code)]
     Inv,
 }
 
@@ -43,14 +42,14 @@ impl SboxType {
     }
 }
 
-pub(super) struct Grain<F: Field> {
+pub(crate) struct Grain<F: Field> {
     state: BitArr!(for 80, in u8, Msb0),
     next_bit: usize,
     _field: PhantomData<F>,
 }
 
 impl<F: PrimeField> Grain<F> {
-    pub(super) fn new(sbox: SboxType, t: u16, r_f: u16, r_p: u16) -> Self {
+    pub(crate) fn new(sbox: SboxType, t: u16, r_f: u16, r_p: u16) -> Self {
         // Initialize the LFSR state.
         let mut state = bitarr![u8, Msb0; 1; STATE];
         let mut set_bits = |offset: usize, len, value| {
@@ -109,7 +108,7 @@ impl<F: PrimeField> Grain<F> {
     }
 
     /// Returns the next field element from this Grain instantiation.
-    pub(super) fn next_field_element(&mut self) -> F {
+    pub(crate) fn next_field_element(&mut self) -> F {
         // Loop until we get an element in the field.
         loop {
             let mut bytes = F::Repr::default();
@@ -0,0 +1,263 @@
+//! The Poseidon2 permutation ([ePrint 2023/323](https://eprint.iacr.org/2023/323.pdf)),
+//! usable with the sponge and hash of [`crate::poseidon`] through [`Poseidon2`].
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use halo2_proofs::halo2curves::{
+    bn256::Fr,
+    ff::{Field, PrimeField},
+};
+
+use crate::poseidon::primitives::{grain::Grain, Mds, Sbox, Spec};
+
+mod chip;
+pub use chip::{Poseidon2Chip, Poseidon2Config};
+
+/// A specification for a Poseidon2 permutation.
+pub trait Poseidon2Spec<F: Field, const T: usize, const RATE: usize>: fmt::Debug {
+    /// The number of full rounds for this specification.
+    ///
+    /// This must be an even number.
+    fn full_rounds() -> usize;
+
+    /// The number of partial rounds for this specification.
+    fn partial_rounds() -> usize;
+
+    /// The S-box for this specification, used by [`permute`], to generate its constants,
+    /// and by [`Poseidon2Chip`]'s gates, which only support [`Sbox::Pow`].
+    ///
+    /// Defaults to $x^5$.
+    fn sbox_type() -> Sbox {
+        Sbox::Pow(5)
+    }
+
+    /// The diagonal `d` of the internal matrix `1 + diag(d)`, where `1` is the all-ones
+    /// matrix. This is `mat_internal_diag_m_1` in the reference implementation.
+    fn internal_diagonal() -> [F; T];
+
+    /// Generates the round constants of this specification. Partial rounds only use
+    /// the constant of their first word, the others being zero.
+    fn constants() -> Vec<[F; T]>;
+}
+
+/// Generates the round constants of `S` with the Grain LFSR, as the reference
+/// implementation does: `T` constants for each full round and one for each partial round.
+pub fn generate_constants<
+    F: PrimeField,
+    S: Poseidon2Spec<F, T, RATE>,
+    const T: usize,
+    const RATE: usize,
+>() -> Vec<[F; T]> {
+    let r_f = S::full_rounds();
+    let r_p = S::partial_rounds();
+
+    let mut grain = Grain::new(S::sbox_type().into(), T as u16, r_f as u16, r_p as u16);
+
+    (0..(r_f + r_p))
+        .map(|round| {
+            let mut rc_row = [F::ZERO; T];
+            let words = if is_partial_round::<F, S, T, RATE>(round) {
+                1
+            } else {
+                T
+            };
+            for rc in rc_row.iter_mut().take(words) {
+                *rc = grain.next_field_element();
+            }
+            rc_row
+        })
+        .collect()
+}
+
+fn is_partial_round<F: Field, S: Poseidon2Spec<F, T, RATE>, const T: usize, const RATE: usize>(
+    round: usize,
+) -> bool {
+    let half_full_rounds = S::full_rounds() / 2;
+    round >= half_full_rounds && round < half_full_rounds + S::partial_rounds()
+}
+
+/// The external matrix of Poseidon2 for `T` of 2, 3 or a multiple of 4.
+pub fn external_matrix<F: PrimeField, const T: usize>() -> Mds<F, T> {
+    const M4: [[u64; 4]; 4] = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+
+    let mut matrix = [[F::ZERO; T]; T];
+    for (i, row) in matrix.iter_mut().enumerate() {
+        for (j, entry) in row.iter_mut().enumerate() {
+            let value = match T {
+                // circ(2, 1) and circ(2, 1, 1).
+                2 | 3 => 1 + u64::from(i == j),
+                // M4 itself, or circ(2 M4, M4, ..., M4) for larger widths.
+                4 => M4[i][j],
+                _ if T % 4 == 0 => M4[i % 4][j % 4] << u64::from(i / 4 == j / 4),
+                _ => panic!("Poseidon2 is defined for widths 2, 3 and multiples of 4"),
+            };
+            *entry = F::from(value);
+        }
+    }
+    matrix
+}
+
+/// The internal matrix `1 + diag(diagonal)`.
+pub fn internal_matrix<F: PrimeField, const T: usize>(diagonal: [F; T]) -> Mds<F, T> {
+    let mut matrix = [[F::ONE; T]; T];
+    for (i, d) in diagonal.iter().enumerate() {
+        matrix[i][i] += d;
+    }
+    matrix
+}
+
+/// Runs the Poseidon2 permutation of `S` on the given state.
+pub fn permute<F: PrimeField, S: Poseidon2Spec<F, T, RATE>, const T: usize, const RATE: usize>(
+    state: &mut [F; T],
+    round_constants: &[[F; T]],
+) {
+    let external = external_matrix::<F, T>();
+    let diagonal = S::internal_diagonal();
+    let sbox = S::sbox_type();
+
+    let apply_external = |state: &mut [F; T]| {
+        let mut new_state = [F::ZERO; T];
+        for (word, row) in new_state.iter_mut().zip(external.iter()) {
+            *word = row.iter().zip(state.iter()).map(|(m, s)| *m * s).sum();
+        }
+        *state = new_state;
+    };
+    // The internal matrix only costs a sum and a product per word.
+    let apply_internal = |state: &mut [F; T]| {
+        let sum: F = state.iter().sum();
+        for (word, d) in state.iter_mut().zip(diagonal.iter()) {
+            *word = sum + *word * d;
+        }
+    };
+
+    apply_external(state);
+    for (round, rcs) in round_constants.iter().enumerate() {
+        if is_partial_round::<F, S, T, RATE>(round) {
+            state[0] = sbox.apply(state[0] + rcs[0]);
+            apply_internal(state);
+        } else {
+            for (word, rc) in state.iter_mut().zip(rcs.iter()) {
+                *word = sbox.apply(*word + rc);
+            }
+            apply_external(state);
+        }
+    }
+}
+
+/// The [`Spec`] of the Poseidon2 permutation of `S`, for the sponge and hash of
+/// [`crate::poseidon`] and the [`Poseidon2Chip`].
+///
+/// Its constants are the round constants, the external matrix and the internal matrix,
+/// in place of the MDS matrix and its inverse; it does not work with `Pow5Chip`.
+#[derive(Debug, Clone, Copy)]
+pub struct Poseidon2<S>(PhantomData<S>);
+
+impl<F: PrimeField, S: Poseidon2Spec<F, T, RATE>, const T: usize, const RATE: usize>
+    Spec<F, T, RATE> for Poseidon2<S>
+{
+    fn full_rounds() -> usize {
+        S::full_rounds()
+    }
+
+    fn partial_rounds() -> usize {
+        S::partial_rounds()
+    }
+
+    fn sbox_type() -> Sbox {
+        S::sbox_type()
+    }
+
+    fn constants() -> (Vec<[F; T]>, Mds<F, T>, Mds<F, T>) {
+        (
+            S::constants(),
+            external_matrix::<F, T>(),
+            internal_matrix(S::internal_diagonal()),
+        )
+    }
+
+    fn permute(state: &mut [F; T], _mds: &Mds<F, T>, round_constants: &[[F; T]]) {
+        permute::<F, S, T, RATE>(state, round_constants)
+    }
+}
+
+/// Poseidon2 over BN254 with the parameters of the reference implementation, for `T` of
+/// 2 or 3.
+#[derive(Debug, Clone, Copy)]
+pub struct Poseidon2Bn256<const T: usize, const RATE: usize>;
+
+impl<const T: usize, const RATE: usize> Poseidon2Spec<Fr, T, RATE> for Poseidon2Bn256<T, RATE> {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn internal_diagonal() -> [Fr; T] {
+        let diagonal: &[u64] = match T {
+            2 => &[1, 2],
+            3 => &[1, 1, 2],
+            _ => panic!("Poseidon2Bn256 is defined for widths 2 and 3"),
+        };
+        std::array::from_fn(|i| Fr::from(diagonal[i]))
+    }
+
+    fn constants() -> Vec<[Fr; T]> {
+        generate_constants::<_, Self, T, RATE>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{external_matrix, permute, Poseidon2, Poseidon2Bn256, Poseidon2Spec};
+    use crate::{
+        poseidon::primitives::{ConstantLength, Hash, Spec},
+        utils::hex_to_fr,
+    };
+    use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField};
+
+    type Bn256T3 = Poseidon2Bn256<3, 2>;
+
+    /// Test vector of the reference implementation, `poseidon2_instance_bn256.rs`.
+    #[test]
+    fn permutation_matches_reference() {
+        let round_constants = Bn256T3::constants();
+        assert_eq!(
+            round_constants[0][0],
+            hex_to_fr("0x1d066a255517b7fd8bddd3a93f7804ef7f8fcde48bb4c37a59a09a1a97052816")
+                .unwrap()
+        );
+
+        let mut state = [Fr::from(0), Fr::from(1), Fr::from(2)];
+        permute::<_, Bn256T3, 3, 2>(&mut state, &round_constants);
+        let expected = [
+            "0x0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033",
+            "0x303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570",
+            "0x1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8",
+        ];
+        for (word, expected) in state.iter().zip(expected) {
+            assert_eq!(*word, hex_to_fr(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn external_matrix_of_wide_states() {
+        let m = external_matrix::<Fr, 8>();
+        assert_eq!(m[0][..4], [10, 14, 2, 6].map(Fr::from));
+        assert_eq!(m[0][4..], [5, 7, 1, 3].map(Fr::from));
+        assert_eq!(external_matrix::<Fr, 4>()[3], [1, 1, 4, 6].map(Fr::from));
+    }
+
+    #[test]
+    fn sponge_uses_poseidon2() {
+        let message = [Fr::from(1), Fr::from(2)];
+        let output = Hash::<_, Poseidon2<Bn256T3>, ConstantLength<2>, 3, 2>::init().hash(message);
+
+        let (round_constants, _, _) = Poseidon2::<Bn256T3>::constants();
+        let mut state = [message[0], message[1], Fr::from_u128(2 << 64)];
+        permute::<_, Bn256T3, 3, 2>(&mut state, &round_constants);
+        assert_eq!(output, state[0]);
+    }
+}
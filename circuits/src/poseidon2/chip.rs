@@ -0,0 +1,823 @@
+use std::convert::TryInto;
+use std::iter;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Chip, Layouter, Region, Value},
+    halo2curves::ff::{Field, PrimeField},
+    plonk::{
+        Advice, Any, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Selector,
+        VirtualCells,
+    },
+    poly::Rotation,
+};
+
+use super::{external_matrix, internal_matrix, Poseidon2, Poseidon2Spec};
+use crate::poseidon::{
+    primitives::{Absorbing, Domain, Mds, Sbox, Squeezing, State},
+    PaddedWord, PoseidonInstructions, PoseidonSpongeInstructions, StateWord,
+};
+use halo2_gadgets::utilities::Var;
+
+/// Configuration for a [`Poseidon2Chip`].
+#[derive(Clone, Debug)]
+pub struct Poseidon2Config<F: Field, const WIDTH: usize, const RATE: usize> {
+    pub(crate) state: [Column<Advice>; WIDTH],
+    partial_sbox: Column<Advice>,
+    rc_a: [Column<Fixed>; WIDTH],
+    rc_b: [Column<Fixed>; WIDTH],
+    s_external: Selector,
+    s_full: Selector,
+    s_partial: Selector,
+    /// Enabled on the row of the last partial round, when their number is odd.
+    s_partial_single: Option<Selector>,
+    s_pad_and_add: Selector,
+
+    alpha: u64,
+    half_full_rounds: usize,
+    half_partial_rounds: usize,
+    round_constants: Vec<[F; WIDTH]>,
+    external: Mds<F, WIDTH>,
+    internal: Mds<F, WIDTH>,
+}
+
+/// A Poseidon2 chip using the $x^\alpha$ S-Box of its [`Poseidon2Spec`], $x^5$ by default.
+///
+/// The chip lays out the initial linear layer and each full round on a row, and two
+/// partial rounds per row, like [`Pow5Chip`](crate::poseidon::Pow5Chip) whose columns
+/// it takes. Partial rounds only add a round constant to the first word, and their
+/// internal matrix is `1 + diag(d)`.
+#[derive(Debug)]
+pub struct Poseidon2Chip<F: Field, const WIDTH: usize, const RATE: usize> {
+    config: Poseidon2Config<F, WIDTH, RATE>,
+}
+
+fn pow<F: Field>(v: Expression<F>, alpha: u64) -> Expression<F> {
+    (1..alpha).fold(v.clone(), |acc, _| acc * v.clone())
+}
+
+fn mul_expr<F: Field, const WIDTH: usize>(
+    matrix: &Mds<F, WIDTH>,
+    words: &[Expression<F>],
+) -> Vec<Expression<F>> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(words.iter())
+                .map(|(m, word)| word.clone() * *m)
+                .reduce(|acc, term| acc + term)
+                .expect("WIDTH > 0")
+        })
+        .collect()
+}
+
+fn mul_value<F: Field, const WIDTH: usize>(matrix: &Mds<F, WIDTH>, words: &[F]) -> Vec<F> {
+    matrix
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(words.iter())
+                .map(|(m, word)| *m * word)
+                .sum()
+        })
+        .collect()
+}
+
+impl<F: PrimeField, const WIDTH: usize, const RATE: usize> Poseidon2Chip<F, WIDTH, RATE> {
+    /// Configures this chip for use in a circuit.
+    ///
+    /// # Side-effects
+    ///
+    /// All columns in `state` will be equality-enabled.
+    pub fn configure<S: Poseidon2Spec<F, WIDTH, RATE>>(
+        meta: &mut ConstraintSystem<F>,
+        state: [Column<Advice>; WIDTH],
+        partial_sbox: Column<Advice>,
+        rc_a: [Column<Fixed>; WIDTH],
+        rc_b: [Column<Fixed>; WIDTH],
+    ) -> Poseidon2Config<F, WIDTH, RATE> {
+        // The capacity holds at least one word.
+        assert!(RATE < WIDTH);
+        // This gadget requires R_F to be even.
+        assert!(S::full_rounds() & 1 == 0);
+        let alpha = match S::sbox_type() {
+            Sbox::Pow(alpha) => alpha,
+            Sbox::Inv => panic!("Poseidon2Chip only supports x^alpha S-boxes"),
+        };
+        assert!(alpha >= 3, "the S-box exponent must be at least 3");
+        assert!(
+            S::sbox_type().is_permutation::<F>(),
+            "the S-box must permute the field"
+        );
+        let half_full_rounds = S::full_rounds() / 2;
+        let half_partial_rounds = S::partial_rounds() / 2;
+        let round_constants = S::constants();
+        let external = external_matrix::<F, WIDTH>();
+        let internal = internal_matrix(S::internal_diagonal());
+
+        // As in Pow5Chip, rc_b holds the padding and other fixed values of the sponge.
+        for column in iter::empty()
+            .chain(state.iter().cloned().map(Column::<Any>::from))
+            .chain(rc_b.iter().cloned().map(Column::<Any>::from))
+        {
+            meta.enable_equality(column);
+        }
+
+        let s_external = meta.selector();
+        let s_full = meta.selector();
+        let s_partial = meta.selector();
+        let s_partial_single = (S::partial_rounds() & 1 == 1).then(|| meta.selector());
+        let s_pad_and_add = meta.selector();
+
+        let query_state = |meta: &mut VirtualCells<F>, rotation: Rotation| {
+            state
+                .iter()
+                .map(|column| meta.query_advice(*column, rotation))
+                .collect::<Vec<_>>()
+        };
+        let round_output = |output: Vec<Expression<F>>, next: Vec<Expression<F>>| {
+            output
+                .into_iter()
+                .zip(next)
+                .map(|(output, next)| output - next)
+                .collect::<Vec<_>>()
+        };
+
+        meta.create_gate("external linear layer", |meta| {
+            let s_external = meta.query_selector(s_external);
+            let cur = query_state(meta, Rotation::cur());
+            let next = query_state(meta, Rotation::next());
+
+            Constraints::with_selector(s_external, round_output(mul_expr(&external, &cur), next))
+        });
+
+        meta.create_gate("full round", |meta| {
+            let s_full = meta.query_selector(s_full);
+            let cur = query_state(meta, Rotation::cur());
+            let next = query_state(meta, Rotation::next());
+
+            let words: Vec<_> = cur
+                .into_iter()
+                .zip(rc_a.iter())
+                .map(|(word, rc_a)| pow(word + meta.query_fixed(*rc_a, Rotation::cur()), alpha))
+                .collect();
+
+            Constraints::with_selector(s_full, round_output(mul_expr(&external, &words), next))
+        });
+
+        meta.create_gate("partial rounds", |meta| {
+            let s_partial = meta.query_selector(s_partial);
+            let cur = query_state(meta, Rotation::cur());
+            let next = query_state(meta, Rotation::next());
+            let mid_0 = meta.query_advice(partial_sbox, Rotation::cur());
+            let rc_a0 = meta.query_fixed(rc_a[0], Rotation::cur());
+            let rc_b0 = meta.query_fixed(rc_b[0], Rotation::cur());
+
+            // round a
+            let sbox_a = pow(cur[0].clone() + rc_a0, alpha) - mid_0.clone();
+            let mid = mul_expr(
+                &internal,
+                &iter::once(mid_0)
+                    .chain(cur[1..].iter().cloned())
+                    .collect::<Vec<_>>(),
+            );
+            // round b
+            let words: Vec<_> = iter::once(pow(mid[0].clone() + rc_b0, alpha))
+                .chain(mid[1..].iter().cloned())
+                .collect();
+
+            Constraints::with_selector(
+                s_partial,
+                iter::once(sbox_a)
+                    .chain(round_output(mul_expr(&internal, &words), next))
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        if let Some(s_partial_single) = s_partial_single {
+            meta.create_gate("single partial round", |meta| {
+                let s_partial_single = meta.query_selector(s_partial_single);
+                let cur = query_state(meta, Rotation::cur());
+                let next = query_state(meta, Rotation::next());
+                let rc_a0 = meta.query_fixed(rc_a[0], Rotation::cur());
+
+                let words: Vec<_> = iter::once(pow(cur[0].clone() + rc_a0, alpha))
+                    .chain(cur[1..].iter().cloned())
+                    .collect();
+
+                Constraints::with_selector(
+                    s_partial_single,
+                    round_output(mul_expr(&internal, &words), next),
+                )
+            });
+        }
+
+        meta.create_gate("pad-and-add", |meta| {
+            let s_pad_and_add = meta.query_selector(s_pad_and_add);
+            let initial_state = query_state(meta, Rotation::prev());
+            let input = query_state(meta, Rotation::cur());
+            let output_state = query_state(meta, Rotation::next());
+
+            Constraints::with_selector(
+                s_pad_and_add,
+                (0..WIDTH)
+                    .map(|idx| {
+                        // The capacity elements are never altered by the input.
+                        if idx < RATE {
+                            initial_state[idx].clone() + input[idx].clone()
+                                - output_state[idx].clone()
+                        } else {
+                            initial_state[idx].clone() - output_state[idx].clone()
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        Poseidon2Config {
+            state,
+            partial_sbox,
+            rc_a,
+            rc_b,
+            s_external,
+            s_full,
+            s_partial,
+            s_partial_single,
+            s_pad_and_add,
+            alpha,
+            half_full_rounds,
+            half_partial_rounds,
+            round_constants,
+            external,
+            internal,
+        }
+    }
+
+    /// Construct a [`Poseidon2Chip`].
+    pub fn construct(config: Poseidon2Config<F, WIDTH, RATE>) -> Self {
+        Poseidon2Chip { config }
+    }
+}
+
+impl<F: Field, const WIDTH: usize, const RATE: usize> Chip<F> for Poseidon2Chip<F, WIDTH, RATE> {
+    type Config = Poseidon2Config<F, WIDTH, RATE>;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: PrimeField, S: Poseidon2Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>
+    PoseidonInstructions<F, Poseidon2<S>, WIDTH, RATE> for Poseidon2Chip<F, WIDTH, RATE>
+{
+    type Word = StateWord<F>;
+
+    fn permute(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        initial_state: &State<Self::Word, WIDTH>,
+    ) -> Result<State<Self::Word, WIDTH>, Error> {
+        let config = self.config();
+
+        layouter.assign_region(
+            || "permute state",
+            |mut region| {
+                let mut state = Poseidon2State::load(&mut region, config, initial_state)?
+                    .external_linear_layer(&mut region, config)?;
+                let mut round = 0;
+                let mut offset = 1;
+
+                for _ in 0..config.half_full_rounds {
+                    state = state.full_round(&mut region, config, round, offset)?;
+                    round += 1;
+                    offset += 1;
+                }
+                for _ in 0..config.half_partial_rounds {
+                    state = state.partial_rounds(&mut region, config, round, offset)?;
+                    round += 2;
+                    offset += 1;
+                }
+                if let Some(s_partial_single) = config.s_partial_single {
+                    state = state.single_partial_round(
+                        &mut region,
+                        config,
+                        s_partial_single,
+                        round,
+                        offset,
+                    )?;
+                    round += 1;
+                    offset += 1;
+                }
+                for _ in 0..config.half_full_rounds {
+                    state = state.full_round(&mut region, config, round, offset)?;
+                    round += 1;
+                    offset += 1;
+                }
+
+                Ok(state.0)
+            },
+        )
+    }
+}
+
+impl<
+        F: PrimeField,
+        S: Poseidon2Spec<F, WIDTH, RATE>,
+        D: Domain<F, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+    > PoseidonSpongeInstructions<F, Poseidon2<S>, D, WIDTH, RATE>
+    for Poseidon2Chip<F, WIDTH, RATE>
+{
+    fn initial_state(
+        &self,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<State<Self::Word, WIDTH>, Error> {
+        let config = self.config();
+        let state = layouter.assign_region(
+            || format!("initial state for domain {}", D::name()),
+            |mut region| {
+                let state: Result<Vec<_>, Error> = (0..WIDTH)
+                    .map(|i| {
+                        let value = if i == RATE {
+                            D::initial_capacity_element()
+                        } else {
+                            F::ZERO
+                        };
+                        region
+                            .assign_advice_from_constant(
+                                || format!("state_{}", i),
+                                config.state[i],
+                                0,
+                                value,
+                            )
+                            .map(StateWord::from)
+                    })
+                    .collect();
+                state
+            },
+        )?;
+
+        Ok(state.try_into().unwrap())
+    }
+
+    fn add_input(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        initial_state: &State<Self::Word, WIDTH>,
+        input: &Absorbing<PaddedWord<F>, RATE>,
+    ) -> Result<State<Self::Word, WIDTH>, Error> {
+        let config = self.config();
+        layouter.assign_region(
+            || format!("add input domain {}", D::name()),
+            |mut region| {
+                config.s_pad_and_add.enable(&mut region, 1)?;
+
+                // Load the initial state into this region.
+                let initial_state: Result<Vec<_>, Error> = (0..WIDTH)
+                    .map(|i| {
+                        AssignedCell::from(initial_state[i].clone())
+                            .copy_advice(
+                                || format!("load state_{}", i),
+                                &mut region,
+                                config.state[i],
+                                0,
+                            )
+                            .map(StateWord::from)
+                    })
+                    .collect();
+                let initial_state = initial_state?;
+
+                // Load the input into this region.
+                let input: Result<Vec<_>, Error> = (0..RATE)
+                    .map(|i| {
+                        let constraint_var = match input.0[i].clone() {
+                            Some(PaddedWord::Message(word)) => word,
+                            Some(PaddedWord::Padding(padding_value)) => region.assign_fixed(
+                                || format!("load pad_{}", i),
+                                config.rc_b[i],
+                                1,
+                                || Value::known(padding_value),
+                            )?,
+                            _ => panic!("Input is not padded"),
+                        };
+                        constraint_var
+                            .copy_advice(
+                                || format!("load input_{}", i),
+                                &mut region,
+                                config.state[i],
+                                1,
+                            )
+                            .map(StateWord::from)
+                    })
+                    .collect();
+                let input = input?;
+
+                // Constrain the output.
+                let output: Result<Vec<_>, Error> = (0..WIDTH)
+                    .map(|i| {
+                        let value = initial_state[i].value()
+                            + input
+                                .get(i)
+                                .map(|word| word.value())
+                                // The capacity elements are never altered by the input.
+                                .unwrap_or_else(|| Value::known(F::ZERO));
+                        region
+                            .assign_advice(
+                                || format!("load output_{}", i),
+                                config.state[i],
+                                2,
+                                || value,
+                            )
+                            .map(StateWord::from)
+                    })
+                    .collect();
+                output.map(|output| output.try_into().unwrap())
+            },
+        )
+    }
+
+    fn get_output(state: &State<Self::Word, WIDTH>) -> Squeezing<Self::Word, RATE> {
+        Squeezing(
+            state[..RATE]
+                .iter()
+                .map(|word| Some(word.clone()))
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap(),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Poseidon2State<F: Field, const WIDTH: usize>([StateWord<F>; WIDTH]);
+
+impl<F: PrimeField, const WIDTH: usize> Poseidon2State<F, WIDTH> {
+    fn load<const RATE: usize>(
+        region: &mut Region<F>,
+        config: &Poseidon2Config<F, WIDTH, RATE>,
+        initial_state: &State<StateWord<F>, WIDTH>,
+    ) -> Result<Self, Error> {
+        let state: Result<Vec<_>, Error> = (0..WIDTH)
+            .map(|i| {
+                AssignedCell::from(initial_state[i].clone())
+                    .copy_advice(|| format!("load state_{}", i), region, config.state[i], 0)
+                    .map(StateWord::from)
+            })
+            .collect();
+        state.map(|state| Poseidon2State(state.try_into().unwrap()))
+    }
+
+    fn values(&self) -> Value<Vec<F>> {
+        self.0.iter().map(|word| word.value()).collect()
+    }
+
+    fn external_linear_layer<const RATE: usize>(
+        self,
+        region: &mut Region<F>,
+        config: &Poseidon2Config<F, WIDTH, RATE>,
+    ) -> Result<Self, Error> {
+        config.s_external.enable(region, 0)?;
+
+        let next = self.values().map(|cur| mul_value(&config.external, &cur));
+        Self::next_state(region, config, "linear layer", 0, next)
+    }
+
+    fn full_round<const RATE: usize>(
+        self,
+        region: &mut Region<F>,
+        config: &Poseidon2Config<F, WIDTH, RATE>,
+        round: usize,
+        offset: usize,
+    ) -> Result<Self, Error> {
+        config.s_full.enable(region, offset)?;
+        let rcs = config.round_constants[round];
+        for (i, rc) in rcs.iter().enumerate() {
+            region.assign_fixed(
+                || format!("round_{} rc_{}", round, i),
+                config.rc_a[i],
+                offset,
+                || Value::known(*rc),
+            )?;
+        }
+
+        let next = self.values().map(|cur| {
+            let words: Vec<_> = cur
+                .iter()
+                .zip(rcs.iter())
+                .map(|(word, rc)| (*word + rc).pow_vartime([config.alpha]))
+                .collect();
+            mul_value(&config.external, &words)
+        });
+        Self::next_state(
+            region,
+            config,
+            &format!("round_{}", round + 1),
+            offset,
+            next,
+        )
+    }
+
+    fn partial_rounds<const RATE: usize>(
+        self,
+        region: &mut Region<F>,
+        config: &Poseidon2Config<F, WIDTH, RATE>,
+        round: usize,
+        offset: usize,
+    ) -> Result<Self, Error> {
+        config.s_partial.enable(region, offset)?;
+        let rc_a0 = config.round_constants[round][0];
+        let rc_b0 = config.round_constants[round + 1][0];
+        region.assign_fixed(
+            || format!("round_{} rc_0", round),
+            config.rc_a[0],
+            offset,
+            || Value::known(rc_a0),
+        )?;
+        region.assign_fixed(
+            || format!("round_{} rc_0", round + 1),
+            config.rc_b[0],
+            offset,
+            || Value::known(rc_b0),
+        )?;
+
+        let cur = self.values();
+        let mid_0 = cur
+            .as_ref()
+            .map(|cur| (cur[0] + rc_a0).pow_vartime([config.alpha]));
+        region.assign_advice(
+            || format!("round_{} partial_sbox", round),
+            config.partial_sbox,
+            offset,
+            || mid_0,
+        )?;
+
+        let next = cur.zip(mid_0).map(|(cur, mid_0)| {
+            let mut mid = cur;
+            mid[0] = mid_0;
+            let mut mid = mul_value(&config.internal, &mid);
+            mid[0] = (mid[0] + rc_b0).pow_vartime([config.alpha]);
+            mul_value(&config.internal, &mid)
+        });
+        Self::next_state(
+            region,
+            config,
+            &format!("round_{}", round + 2),
+            offset,
+            next,
+        )
+    }
+
+    fn single_partial_round<const RATE: usize>(
+        self,
+        region: &mut Region<F>,
+        config: &Poseidon2Config<F, WIDTH, RATE>,
+        s_partial_single: Selector,
+        round: usize,
+        offset: usize,
+    ) -> Result<Self, Error> {
+        s_partial_single.enable(region, offset)?;
+        let rc_a0 = config.round_constants[round][0];
+        region.assign_fixed(
+            || format!("round_{} rc_0", round),
+            config.rc_a[0],
+            offset,
+            || Value::known(rc_a0),
+        )?;
+
+        let next = self.values().map(|mut cur| {
+            cur[0] = (cur[0] + rc_a0).pow_vartime([config.alpha]);
+            mul_value(&config.internal, &cur)
+        });
+        Self::next_state(
+            region,
+            config,
+            &format!("round_{}", round + 1),
+            offset,
+            next,
+        )
+    }
+
+    /// Assigns `next` on the row after `offset`.
+    fn next_state<const RATE: usize>(
+        region: &mut Region<F>,
+        config: &Poseidon2Config<F, WIDTH, RATE>,
+        name: &str,
+        offset: usize,
+        next: Value<Vec<F>>,
+    ) -> Result<Self, Error> {
+        let state: Result<Vec<_>, Error> = (0..WIDTH)
+            .map(|i| {
+                region
+                    .assign_advice(
+                        || format!("{} state_{}", name, i),
+                        config.state[i],
+                        offset + 1,
+                        || next.as_ref().map(|next| next[i]),
+                    )
+                    .map(StateWord::from)
+            })
+            .collect();
+        state.map(|state| Poseidon2State(state.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::{bn256::Fr, ff::Field},
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+    use rand_core::OsRng;
+    use std::convert::TryInto;
+    use std::marker::PhantomData;
+
+    use super::{Poseidon2Chip, Poseidon2Config};
+    use crate::poseidon::{
+        primitives::{self as poseidon, ConstantLength, Sbox},
+        Hash,
+    };
+    use crate::poseidon2::{generate_constants, Poseidon2, Poseidon2Bn256, Poseidon2Spec};
+
+    struct HashCircuit<S: Poseidon2Spec<Fr, 3, 2>> {
+        message: Value<[Fr; 2]>,
+        output: Value<Fr>,
+        _spec: PhantomData<S>,
+    }
+
+    impl<S: Poseidon2Spec<Fr, 3, 2>> Circuit<Fr> for HashCircuit<S> {
+        type Config = Poseidon2Config<Fr, 3, 2>;
+        type FloorPlanner = SimpleFloorPlanner;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                message: Value::unknown(),
+                output: Value::unknown(),
+                _spec: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Poseidon2Config<Fr, 3, 2> {
+            let state = (0..3).map(|_| meta.advice_column()).collect::<Vec<_>>();
+            let partial_sbox = meta.advice_column();
+
+            let rc_a = (0..3).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+            let rc_b = (0..3).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+
+            meta.enable_constant(rc_b[0]);
+
+            Poseidon2Chip::configure::<S>(
+                meta,
+                state.try_into().unwrap(),
+                partial_sbox,
+                rc_a.try_into().unwrap(),
+                rc_b.try_into().unwrap(),
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Poseidon2Config<Fr, 3, 2>,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let chip = Poseidon2Chip::construct(config.clone());
+
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    let message: Result<Vec<_>, Error> = (0..2)
+                        .map(|i| {
+                            region.assign_advice(
+                                || format!("load message_{}", i),
+                                config.state[i],
+                                0,
+                                || self.message.map(|message| message[i]),
+                            )
+                        })
+                        .collect();
+                    Ok(message?.try_into().unwrap())
+                },
+            )?;
+
+            let hasher = Hash::<_, _, Poseidon2<S>, ConstantLength<2>, 3, 2>::init(
+                chip,
+                layouter.namespace(|| "init"),
+            )?;
+            let output = hasher.hash(layouter.namespace(|| "hash"), message)?;
+
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    let expected = region.assign_advice(
+                        || "load output",
+                        config.state[0],
+                        0,
+                        || self.output,
+                    )?;
+                    region.constrain_equal(output.cell(), expected.cell())
+                },
+            )
+        }
+    }
+
+    /// [`Poseidon2Bn256`] with an $x^7$ S-box.
+    #[derive(Debug, Clone, Copy)]
+    struct Pow7Spec;
+
+    impl Poseidon2Spec<Fr, 3, 2> for Pow7Spec {
+        fn full_rounds() -> usize {
+            8
+        }
+
+        fn partial_rounds() -> usize {
+            56
+        }
+
+        fn sbox_type() -> Sbox {
+            Sbox::Pow(7)
+        }
+
+        fn internal_diagonal() -> [Fr; 3] {
+            Poseidon2Bn256::<3, 2>::internal_diagonal()
+        }
+
+        fn constants() -> Vec<[Fr; 3]> {
+            generate_constants::<_, Self, 3, 2>()
+        }
+    }
+
+    /// [`Pow7Spec`] with an $x^{-1}$ S-box, which the chip does not support.
+    #[derive(Debug, Clone, Copy)]
+    struct InverseSpec;
+
+    impl Poseidon2Spec<Fr, 3, 2> for InverseSpec {
+        fn full_rounds() -> usize {
+            Pow7Spec::full_rounds()
+        }
+
+        fn partial_rounds() -> usize {
+            Pow7Spec::partial_rounds()
+        }
+
+        fn sbox_type() -> Sbox {
+            Sbox::Inv
+        }
+
+        fn internal_diagonal() -> [Fr; 3] {
+            Pow7Spec::internal_diagonal()
+        }
+
+        fn constants() -> Vec<[Fr; 3]> {
+            generate_constants::<_, Self, 3, 2>()
+        }
+    }
+
+    fn check_hash<S: Poseidon2Spec<Fr, 3, 2>>(k: u32) {
+        let rng = OsRng;
+
+        let message = [Fr::random(rng), Fr::random(rng)];
+        let output =
+            poseidon::Hash::<_, Poseidon2<S>, ConstantLength<2>, 3, 2>::init().hash(message);
+
+        let circuit = HashCircuit::<S> {
+            message: Value::known(message),
+            output: Value::known(output),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let circuit = HashCircuit::<S> {
+            message: Value::known(message),
+            output: Value::known(output + Fr::ONE),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn poseidon2_hash() {
+        check_hash::<Poseidon2Bn256<3, 2>>(6);
+    }
+
+    #[test]
+    fn poseidon2_hash_pow7_sbox() {
+        check_hash::<Pow7Spec>(6);
+    }
+
+    #[test]
+    #[should_panic(expected = "only supports x^alpha S-boxes")]
+    fn poseidon2_chip_rejects_inverse_sbox() {
+        check_hash::<InverseSpec>(6);
+    }
+}
@@ -3,28 +3,35 @@ use halo2_proofs::halo2curves::ff::{Field, PrimeField};
 
 use halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner, Value},
+    dev::MockProver,
     plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
 };
 
-use halo2_gadgets::poseidon::{
-    primitives::{self as poseidon, generate_constants, ConstantLength, Mds, Spec},
-    Hash, Pow5Chip, Pow5Config,
+use crate::poseidon::{
+    primitives::{
+        self as poseidon, generate_constants, round_numbers, Absorbing, ConstantLength, Mds, Spec,
+        VariableLength,
+    },
+    Hash, PaddedWord, PoseidonInstructions, PoseidonSpongeInstructions, Pow5Chip, Pow5Config,
 };
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::marker::PhantomData;
 use std::panic;
-use wasm_bindgen::prelude::wasm_bindgen;
 
-static N_ROUNDS_F: i32 = 8;
-static N_ROUNDS_P: [i32; 16] = [
-    56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
-];
 pub const WASM_POSEIDON_WIDTH: usize = 3;
 pub const WASM_POSEIDON_RATE: usize = 2;
-pub const WASM_POSEIDON_L: usize = 2;
-
-use crate::{constants::constants, unstringify::unstringifyHex};
+/// Longest message accepted by [`PoseidonDemo`].
+pub const WASM_POSEIDON_MAX_LEN: usize = 32;
+
+use crate::{
+    error::ProverError,
+    registry::DemoCircuit,
+    standard_plonk::{StandardPlonkChip, StandardPlonkConfig, StandardPlonkInstructions},
+    utils::{parse_encoding, FieldElement},
+};
 #[derive(Copy, Clone)]
 pub struct PoseidonCircuit<S, const WIDTH: usize, const RATE: usize, const L: usize>
 where
@@ -34,10 +41,18 @@ where
     _spec: PhantomData<S>,
 }
 
+/// Prover-side input of [`PoseidonDemo`]: the secret message.
 #[derive(Serialize, Deserialize)]
-pub struct PoseidonInput<'a> {
-    pub x: Vec<u64>,
-    pub output: Option<&'a str>,
+pub struct PoseidonInput {
+    pub x: Vec<FieldElement>,
+}
+
+/// Verifier-side input of [`PoseidonDemo`]: the claimed hash of a message of `len` words.
+/// It reveals nothing of the message itself.
+#[derive(Serialize, Deserialize)]
+pub struct PoseidonClaim {
+    pub output: FieldElement,
+    pub len: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -123,94 +138,243 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct PoseidonSpec<const WIDTH: usize, const RATE: usize>;
+/// Proves knowledge of a message of at most `MAX_LEN` words whose [`VariableLength`]
+/// Poseidon hash is the first public input. The length of the message is a witness, exposed
+/// as the second public input, so messages of every length share the same keys.
+#[derive(Clone)]
+pub struct VariableLengthPoseidonCircuit<
+    S,
+    const WIDTH: usize,
+    const RATE: usize,
+    const MAX_LEN: usize,
+> where
+    S: Spec<Fr, WIDTH, RATE> + Clone + Copy,
+{
+    message: Value<Vec<Fr>>,
+    _spec: PhantomData<S>,
+}
 
-impl<const WIDTH: usize, const RATE: usize> Spec<Fr, WIDTH, RATE> for PoseidonSpec<WIDTH, RATE> {
-    fn full_rounds() -> usize {
-        8
+impl<S, const WIDTH: usize, const RATE: usize, const MAX_LEN: usize>
+    VariableLengthPoseidonCircuit<S, WIDTH, RATE, MAX_LEN>
+where
+    S: Spec<Fr, WIDTH, RATE> + Copy + Clone,
+{
+    /// Blocks absorbed for every message: those of the longest message, padding included.
+    const BLOCKS: usize = MAX_LEN / RATE + 1;
+
+    pub fn new(message: Vec<Fr>) -> Result<Self, ProverError> {
+        if message.len() > MAX_LEN {
+            return Err(ProverError::InvalidInput(format!(
+                "expected at most {} words in `x`, got {}",
+                MAX_LEN,
+                message.len()
+            )));
+        }
+        Ok(Self {
+            message: Value::known(message),
+            _spec: PhantomData,
+        })
     }
 
-    fn partial_rounds() -> usize {
-        N_ROUNDS_P[WIDTH] as usize
+    pub fn empty() -> Self {
+        Self {
+            message: Value::unknown(),
+            _spec: PhantomData,
+        }
     }
 
-    fn sbox(val: Fr) -> Fr {
-        val.pow_vartime(&[5])
+    /// Word `i` of the padded message: the message, a one at index `len`, then zeroes.
+    fn padded_word(&self, i: usize) -> Value<Fr> {
+        self.message
+            .as_ref()
+            .map(|message| match i.cmp(&message.len()) {
+                Ordering::Less => message[i],
+                Ordering::Equal => Fr::ONE,
+                Ordering::Greater => Fr::ZERO,
+            })
     }
+}
 
-    fn secure_mds() -> usize {
-        0
+#[derive(Debug, Clone)]
+pub struct VariableLengthPoseidonConfig<const WIDTH: usize, const RATE: usize> {
+    plonk: StandardPlonkConfig,
+    instance: Column<Instance>,
+    poseidon_config: Pow5Config<Fr, WIDTH, RATE>,
+}
+
+impl<S, const WIDTH: usize, const RATE: usize, const MAX_LEN: usize> Circuit<Fr>
+    for VariableLengthPoseidonCircuit<S, WIDTH, RATE, MAX_LEN>
+where
+    S: Spec<Fr, WIDTH, RATE> + Copy + Clone,
+{
+    type Config = VariableLengthPoseidonConfig<WIDTH, RATE>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self::empty()
     }
 
-    fn constants() -> (Vec<[Fr; WIDTH]>, Mds<Fr, WIDTH>, Mds<Fr, WIDTH>) {
-        generate_constants::<_, Self, WIDTH, RATE>()
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let state: [Column<Advice>; WIDTH] = (0..WIDTH)
+            .map(|_| meta.advice_column())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let partial_sbox = meta.advice_column();
+
+        let rc_a = (0..WIDTH).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+        let rc_b = (0..WIDTH).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+
+        meta.enable_constant(rc_b[0]);
+
+        let plonk = [(); 3].map(|_| meta.advice_column());
+        Self::Config {
+            plonk: StandardPlonkChip::configure(meta, plonk),
+            instance,
+            poseidon_config: Pow5Chip::configure::<S>(
+                meta,
+                state,
+                partial_sbox,
+                rc_a.try_into().unwrap(),
+                rc_b.try_into().unwrap(),
+            ),
+        }
     }
-}
 
-const K: u32 = 7;
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let plonk = StandardPlonkChip::construct(config.plonk);
+        let chip = Pow5Chip::construct(config.poseidon_config);
+        let layouter = &mut layouter;
+
+        // `is_end[i]` is 1 at the index of the padding one, which is the length, and 0
+        // elsewhere. `ended` is 1 past it, where the padding is zero.
+        let zero = plonk.load_constant(layouter, Fr::ZERO)?;
+        let mut padded = Vec::with_capacity(Self::BLOCKS * RATE);
+        let mut is_end = Vec::with_capacity(MAX_LEN + 1);
+        let mut ended = zero.clone();
+        let mut len = zero.clone();
+        for i in 0..=MAX_LEN {
+            let word = plonk.load_private(layouter, self.padded_word(i))?;
+            let end = plonk.load_private(
+                layouter,
+                self.message
+                    .as_ref()
+                    .map(|message| Fr::from((i == message.len()) as u64)),
+            )?;
+            plonk.assert_bool(layouter, &end)?;
+
+            let one_at_end = plonk.mul(layouter, &end, &word)?;
+            plonk.assert_equal(layouter, &one_at_end, &end)?;
+            let after_end = plonk.mul(layouter, &ended, &word)?;
+            plonk.assert_zero(layouter, &after_end)?;
+
+            ended = plonk.add(layouter, &ended, &end)?;
+            len = plonk.add_scaled(layouter, &len, &end, Fr::from(i as u64))?;
+            padded.push(word);
+            is_end.push(end);
+        }
+        let ends = plonk.add_constant(layouter, &ended, -Fr::ONE)?;
+        plonk.assert_zero(layouter, &ends)?;
+        // Always padding, as the message has at most MAX_LEN words.
+        padded.resize(Self::BLOCKS * RATE, zero.clone());
+
+        // The hash is the first word of the state after the block holding the padding one.
+        let mut state =
+            PoseidonSpongeInstructions::<Fr, S, VariableLength, WIDTH, RATE>::initial_state(
+                &chip, layouter,
+            )?;
+        let mut output = zero.clone();
+        for (block, words) in padded.chunks(RATE).enumerate() {
+            let input = Absorbing(
+                words
+                    .iter()
+                    .map(|word| Some(PaddedWord::Message(word.clone())))
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            );
+            state = PoseidonSpongeInstructions::<Fr, S, VariableLength, WIDTH, RATE>::add_input(
+                &chip, layouter, &state, &input,
+            )?;
+            state = PoseidonInstructions::<Fr, S, WIDTH, RATE>::permute(&chip, layouter, &state)?;
+
+            let mut ends_here = zero.clone();
+            for end in is_end.iter().skip(block * RATE).take(RATE) {
+                ends_here = plonk.add(layouter, &ends_here, end)?;
+            }
+            let word = state[0].clone().into();
+            let selected = plonk.mul(layouter, &ends_here, &word)?;
+            output = plonk.add(layouter, &output, &selected)?;
+        }
 
-fn sbox(x: Fr) -> Fr {
-    let y = x * x;
-    return y * y * x;
+        layouter.constrain_instance(output.cell(), config.instance, 0)?;
+        layouter.constrain_instance(len.cell(), config.instance, 1)
+    }
 }
 
-fn mix(state: Vec<Fr>, M: &Vec<Vec<Fr>>) -> Vec<Fr> {
-    let mut out = vec![];
-    for x in 0..state.len() {
-        let mut o = Fr::from(0);
-        for y in 0..state.len() {
-            o += M[x][y] * state[y];
+/// Poseidon with an $x^5$ S-box over BN254 at 128 bits of security, with the round
+/// numbers given by [`round_numbers`] for any `WIDTH`.
+#[derive(Debug, Clone, Copy)]
+pub struct PoseidonSpec<const WIDTH: usize, const RATE: usize>;
+
+/// `(R_F, R_P)` of [`PoseidonSpec`] for widths 2 to 16, as computed by [`round_numbers`],
+/// which is too slow to run on every permutation.
+const POSEIDON_ROUNDS: [(usize, usize); 15] = [
+    (8, 56),
+    (8, 56),
+    (8, 56),
+    (8, 56),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+    (8, 57),
+];
+
+impl<const WIDTH: usize, const RATE: usize> PoseidonSpec<WIDTH, RATE> {
+    fn rounds() -> (usize, usize) {
+        match POSEIDON_ROUNDS.get(WIDTH.wrapping_sub(2)) {
+            Some(&rounds) => rounds,
+            None => round_numbers(Fr::NUM_BITS, WIDTH, 5, 128),
         }
-        out.push(o);
     }
-    out
 }
 
-fn poseidon(inputs: Vec<Fr>) -> Result<Fr, String> {
-    let n_rounds_f = N_ROUNDS_F;
-    let n_rounds_p = N_ROUNDS_P[0];
-    let t = inputs.len() + 1;
-
-    let (C, M) = constants();
-    #[allow(non_upper_case_globals)]
-    let C: Vec<Fr> = C
-        .iter()
-        .map(|x| Fr::from_str_vartime(&unstringifyHex(x)).unwrap())
-        .collect();
-    let M: Vec<Vec<Fr>> = M
-        .iter()
-        .map(|y| {
-            y.iter()
-                .map(|x| Fr::from_str_vartime(&unstringifyHex(x)).unwrap())
-                .collect()
-        })
-        .collect();
-    println!("{}", format!("{:?}", M[0][0]));
-
-    if M.len() != t {
-        return Err(format!(
-            "invalid `M` length: Expected {} got {}",
-            M.len(),
-            t
-        ));
+impl<const WIDTH: usize, const RATE: usize> Spec<Fr, WIDTH, RATE> for PoseidonSpec<WIDTH, RATE> {
+    fn full_rounds() -> usize {
+        Self::rounds().0
     }
 
-    let mut state = vec![Fr::ZERO];
-    state.extend_from_slice(&inputs);
-    for x in 0..(n_rounds_f + n_rounds_p) {
-        for y in 0..state.len() {
-            state[y] += C[(x as usize) * t + y];
-            if x < n_rounds_f / 2 || x >= n_rounds_f / 2 + n_rounds_p {
-                state[y] = sbox(state[y]);
-            } else if y == 0 {
-                state[y] = sbox(state[y]);
-            }
-        }
-        state = mix(state, &M);
+    fn partial_rounds() -> usize {
+        Self::rounds().1
     }
 
-    Ok(state[0])
+    fn sbox(val: Fr) -> Fr {
+        val.pow_vartime(&[5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+
+    fn constants() -> (Vec<[Fr; WIDTH]>, Mds<Fr, WIDTH>, Mds<Fr, WIDTH>) {
+        generate_constants::<_, Self, WIDTH, RATE>()
+    }
 }
 
 pub fn empty_circuit<S, const WIDTH: usize, const RATE: usize, const L: usize>(
@@ -224,35 +388,40 @@ where
     }
 }
 
-pub fn parse_string(s: &str) -> PoseidonInput {
-    serde_json::from_str(s).unwrap()
+pub fn parse_string(s: &str) -> Result<PoseidonInput, ProverError> {
+    Ok(serde_json::from_str(s)?)
+}
+
+/// The message of `s`, padded with ones to `L` words.
+fn parse_message<const L: usize>(s: &str) -> Result<Vec<Fr>, ProverError> {
+    let mut message = parse_variable_message_unchecked(s)?;
+    if message.len() > L {
+        return Err(ProverError::InvalidInput(format!(
+            "expected at most {} words in `x`, got {}",
+            L,
+            message.len()
+        )));
+    }
+    message.resize(L, Fr::ONE);
+    Ok(message)
 }
 
 pub fn create_circuit_from_string<S, const WIDTH: usize, const RATE: usize, const L: usize>(
     s: &str,
-) -> PoseidonCircuit<S, WIDTH, RATE, L>
+) -> Result<PoseidonCircuit<S, WIDTH, RATE, L>, ProverError>
 where
     S: Spec<Fr, WIDTH, RATE> + Copy + Clone,
 {
-    let v = parse_string(s);
-    let mut sequence = v.x;
-    sequence.resize(L, 1);
-    create_circuit(sequence)
+    Ok(create_circuit(parse_message::<L>(s)?))
 }
 
 pub fn create_circuit<S, const WIDTH: usize, const RATE: usize, const L: usize>(
-    message: Vec<u64>,
+    message: Vec<Fr>,
 ) -> PoseidonCircuit<S, WIDTH, RATE, L>
 where
     S: Spec<Fr, WIDTH, RATE> + Copy + Clone,
 {
-    let message: [Fr; L] = message
-        .clone()
-        .iter()
-        .map(|f| Fr::from(*f))
-        .collect::<Vec<Fr>>()
-        .try_into()
-        .unwrap();
+    let message: [Fr; L] = message.try_into().unwrap();
 
     PoseidonCircuit::<S, WIDTH, RATE, L> {
         message: Value::known(message),
@@ -260,55 +429,194 @@ where
     }
 }
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
+pub fn simulate_circuit(s: &str) -> Result<String, ProverError> {
+    Ok(parse_encoding(s)?.encode(&wasm_hash(s)?))
 }
 
-pub fn simulate_circuit(s: &str) -> String {
-    log(&format!("S: {}", s));
-    let public_inputs = parse_string(s);
-    let message: [Fr; WASM_POSEIDON_L] = public_inputs
-        .x
-        .iter()
-        .map(|k| Fr::from(*k))
-        .collect::<Vec<_>>()
-        .try_into()
-        .unwrap();
-
-    let res: String = format!(
-        "{:?}",
-        wasm_poseidon_solution::<
-            PoseidonSpec<WASM_POSEIDON_WIDTH, WASM_POSEIDON_RATE>,
-            WASM_POSEIDON_WIDTH,
-            WASM_POSEIDON_RATE,
-            WASM_POSEIDON_L,
-        >(message)
-    );
-    res
+/// Poseidon hash of the message of `s`, as constrained by [`PoseidonDemo`].
+fn wasm_hash(s: &str) -> Result<Fr, ProverError> {
+    let message = parse_variable_message(s)?;
+    Ok(wasm_poseidon_solution::<
+        WasmPoseidonSpec,
+        WASM_POSEIDON_WIDTH,
+        WASM_POSEIDON_RATE,
+    >(&message))
 }
 
-pub fn wasm_poseidon_solution<S, const WIDTH: usize, const RATE: usize, const L: usize>(
-    message: [Fr; L],
-) -> Fr
+pub fn wasm_poseidon_solution<S, const WIDTH: usize, const RATE: usize>(message: &[Fr]) -> Fr
 where
     S: Spec<Fr, WIDTH, RATE> + Copy + Clone,
 {
-    poseidon::Hash::<_, S, ConstantLength<L>, WIDTH, RATE>::init().hash(message)
+    poseidon::Hash::<_, S, VariableLength, WIDTH, RATE>::init().hash(message)
+}
+
+type WasmPoseidonSpec = PoseidonSpec<WASM_POSEIDON_WIDTH, WASM_POSEIDON_RATE>;
+
+type WasmPoseidonCircuit = VariableLengthPoseidonCircuit<
+    WasmPoseidonSpec,
+    WASM_POSEIDON_WIDTH,
+    WASM_POSEIDON_RATE,
+    WASM_POSEIDON_MAX_LEN,
+>;
+
+/// The message of `s`, of at most [`WASM_POSEIDON_MAX_LEN`] words.
+fn parse_variable_message(s: &str) -> Result<Vec<Fr>, ProverError> {
+    let message = parse_variable_message_unchecked(s)?;
+    check_len(message.len())?;
+    Ok(message)
+}
+
+fn parse_variable_message_unchecked(s: &str) -> Result<Vec<Fr>, ProverError> {
+    Ok(parse_string(s)?.x.into_iter().map(|word| word.0).collect())
+}
+
+fn check_len(len: usize) -> Result<usize, ProverError> {
+    if len > WASM_POSEIDON_MAX_LEN {
+        return Err(ProverError::InvalidInput(format!(
+            "expected at most {} words in `x`, got {}",
+            WASM_POSEIDON_MAX_LEN, len
+        )));
+    }
+    Ok(len)
+}
+
+/// The public inputs of [`PoseidonDemo`] for `s`: the claimed `output` and `len` of a
+/// [`PoseidonClaim`], or the hash and length of the message of a [`PoseidonInput`], which
+/// must match `output` and `len` if given.
+fn parse_claim(s: &str) -> Result<(Fr, usize), ProverError> {
+    #[derive(Deserialize)]
+    struct InputOrClaim {
+        x: Option<Vec<FieldElement>>,
+        output: Option<FieldElement>,
+        len: Option<usize>,
+    }
+
+    let parsed: InputOrClaim = serde_json::from_str(s)?;
+    match (parsed.x, parsed.output.map(|output| output.0)) {
+        (Some(x), claimed) => {
+            let output = wasm_hash(s)?;
+            if matches!(claimed, Some(claimed) if claimed != output) {
+                return Err(ProverError::InvalidInput(format!(
+                    "`output` is not the hash of `x`, which is {:?}",
+                    output
+                )));
+            }
+            if let Some(len) = parsed.len.filter(|&len| len != x.len()) {
+                return Err(ProverError::InvalidInput(format!(
+                    "`len` is {} but `x` has {} words",
+                    len,
+                    x.len()
+                )));
+            }
+            Ok((output, x.len()))
+        }
+        (None, Some(_)) => {
+            let claim: PoseidonClaim = serde_json::from_str(s)?;
+            Ok((claim.output.0, check_len(claim.len)?))
+        }
+        (None, None) => Err(ProverError::InvalidInput(
+            "expected a message `x` or its hash `output`".to_string(),
+        )),
+    }
+}
+
+/// Smallest `k` whose params fit [`PoseidonDemo`]. The layout does not depend on the
+/// message, so it is found once, by laying out the circuit of the empty message for
+/// increasing `k` until every row fits.
+pub fn wasm_poseidon_k() -> u32 {
+    thread_local! {
+        static K: Cell<Option<u32>> = Cell::new(None);
+    }
+
+    K.with(|cached| {
+        if let Some(k) = cached.get() {
+            return k;
+        }
+
+        let mut meta = ConstraintSystem::<Fr>::default();
+        WasmPoseidonCircuit::configure(&mut meta);
+        let circuit = WasmPoseidonCircuit::new(vec![]).expect("the empty message fits");
+        let k = (meta.minimum_rows().next_power_of_two().trailing_zeros()..)
+            .find(|&k| MockProver::run(k, &circuit, vec![vec![Fr::ZERO; 2]]).is_ok())
+            .expect("some k fits the circuit");
+        cached.set(Some(k));
+        k
+    })
+}
+
+pub struct PoseidonDemo;
+
+impl DemoCircuit for PoseidonDemo {
+    type Circuit = WasmPoseidonCircuit;
+
+    fn slug(&self) -> &'static str {
+        "poseidon"
+    }
+
+    fn name(&self) -> &'static str {
+        "Poseidon"
+    }
+
+    fn description(&self) -> &'static str {
+        "Proves knowledge of a preimage of a variable-length Poseidon hash, without revealing it"
+    }
+
+    fn input_schema(&self) -> &'static str {
+        r#"{ "x": [1, 2] }"#
+    }
+
+    fn k(&self) -> u32 {
+        wasm_poseidon_k()
+    }
+
+    fn create_from_json(&self, s: &str) -> Result<Self::Circuit, ProverError> {
+        WasmPoseidonCircuit::new(parse_variable_message_unchecked(s)?)
+    }
+
+    fn empty(&self, _s: &str) -> Result<Self::Circuit, ProverError> {
+        Ok(WasmPoseidonCircuit::empty())
+    }
+
+    fn public_inputs(&self, s: &str) -> Result<Vec<Fr>, ProverError> {
+        let (output, len) = parse_claim(s)?;
+        Ok(vec![output, Fr::from(len as u64)])
+    }
+
+    fn simulate(&self, s: &str) -> Result<String, ProverError> {
+        simulate_circuit(s)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use halo2_proofs::halo2curves::bn256;
+    use halo2_proofs::{dev::MockProver, halo2curves::bn256};
     use rand_core::OsRng;
 
-    use crate::utils::{
-        generate_keys, generate_params, generate_proof_with_instance, verify_with_instance,
+    use crate::{
+        transcript::TranscriptHash,
+        utils::{generate_keys, generate_params, prove, verify, Encoding, MultiOpenScheme},
     };
 
     use super::*;
 
+    const K: u32 = 7;
+
+    #[test]
+    fn round_table_matches_round_numbers() {
+        for (width, &rounds) in (2..).zip(POSEIDON_ROUNDS.iter()) {
+            assert_eq!(
+                rounds,
+                round_numbers(Fr::NUM_BITS, width, 5, 128),
+                "{}",
+                width
+            );
+        }
+        assert_eq!(
+            PoseidonSpec::<17, 16>::rounds(),
+            round_numbers(Fr::NUM_BITS, 17, 5, 128)
+        );
+    }
+
     fn bench<S, const WIDTH: usize, const RATE: usize, const L: usize>(
         name: &str,
     ) -> Result<(), Error>
@@ -339,8 +647,10 @@ mod test {
             _spec: PhantomData,
         };
 
-        let proof = generate_proof_with_instance(&params, &pk, circuit, &[output]);
-        verify_with_instance(&params, &pk, &proof, &[output])
+        let scheme = MultiOpenScheme::default();
+        let transcript = TranscriptHash::default();
+        let proof = prove(&params, &pk, circuit, &[&[output]], scheme, transcript).unwrap();
+        verify(&params, &vk, &proof, &[&[output]], scheme, transcript)
     }
 
     #[test]
@@ -351,6 +661,127 @@ mod test {
         );
     }
 
+    #[test]
+    fn round_numbers_for_any_width() {
+        assert_eq!(PoseidonSpec::<3, 2>::full_rounds(), 8);
+        assert_eq!(PoseidonSpec::<3, 2>::partial_rounds(), 56);
+        assert_eq!(PoseidonSpec::<24, 16>::partial_rounds(), 57);
+    }
+
+    #[test]
+    fn wide_capacity_and_odd_partial_rounds() {
+        // Two capacity words.
+        bench::<PoseidonSpec<5, 3>, 5, 3, 3>("WIDTH = 5, RATE = 3").unwrap();
+
+        // 57 partial rounds, the last of which has a row of its own.
+        assert_eq!(PoseidonSpec::<6, 5>::partial_rounds(), 57);
+        bench::<PoseidonSpec<6, 5>, 6, 5, 5>("WIDTH = 6, RATE = 5").unwrap();
+    }
+
+    #[test]
+    fn variable_length_circuit_matches_native() {
+        let k = PoseidonDemo.k();
+        for len in [0, 1, 2, 3, 5, WASM_POSEIDON_MAX_LEN] {
+            let x: Vec<u64> = (1..=len as u64).collect();
+            let s = serde_json::json!({ "x": x }).to_string();
+            let circuit = PoseidonDemo.create_from_json(&s).unwrap();
+            let output = wasm_hash(&s).unwrap();
+            let len = Fr::from(len as u64);
+
+            MockProver::run(k, &circuit, vec![vec![output, len]])
+                .unwrap()
+                .assert_satisfied();
+            for wrong in [[output + Fr::ONE, len], [output, len + Fr::ONE]] {
+                let prover = MockProver::run(k, &circuit, vec![wrong.to_vec()]).unwrap();
+                assert!(prover.verify().is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn wasm_poseidon_k_is_smallest() {
+        let k = wasm_poseidon_k();
+        let circuit = WasmPoseidonCircuit::new(vec![Fr::ONE; WASM_POSEIDON_MAX_LEN]).unwrap();
+        let instance = vec![Fr::ZERO; 2];
+        assert!(MockProver::run(k - 1, &circuit, vec![instance.clone()]).is_err());
+        assert!(MockProver::run(k, &circuit, vec![instance]).is_ok());
+    }
+
+    #[test]
+    fn one_circuit_for_every_length() {
+        let shape = PoseidonDemo.shape(r#"{ "x": [1, 2, 3] }"#).unwrap();
+        assert_eq!(shape, PoseidonDemo.shape(r#"{ "x": [1] }"#).unwrap());
+        assert_eq!(
+            shape,
+            PoseidonDemo
+                .shape(r#"{ "output": "42", "len": 5 }"#)
+                .unwrap()
+        );
+
+        let too_long = serde_json::json!({ "x": vec![1; WASM_POSEIDON_MAX_LEN + 1] }).to_string();
+        assert!(matches!(
+            PoseidonDemo.create_from_json(&too_long),
+            Err(ProverError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            PoseidonDemo.public_inputs(r#"{ "x": [1, 2], "len": 3 }"#),
+            Err(ProverError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn claim_needs_no_preimage() {
+        let input = r#"{ "x": [1, 2, 3] }"#;
+        let output = wasm_hash(input).unwrap();
+        let claim = serde_json::json!({ "output": format!("{:?}", output), "len": 3 }).to_string();
+        let public_inputs = vec![output, Fr::from(3)];
+        assert_eq!(PoseidonDemo.public_inputs(input).unwrap(), public_inputs);
+        assert_eq!(PoseidonDemo.public_inputs(&claim).unwrap(), public_inputs);
+
+        let decimal = r#"{ "output": "42", "len": 3 }"#;
+        assert_eq!(
+            PoseidonDemo.public_inputs(decimal).unwrap(),
+            vec![Fr::from(42), Fr::from(3)]
+        );
+
+        for invalid in [
+            r#"{ "x": [1, 2, 3], "output": "42" }"#,
+            r#"{ "output": "42" }"#,
+            r#"{ "output": "0xzz", "len": 3 }"#,
+            r#"{ "output": "42", "len": 33 }"#,
+            r#"{ "len": 3 }"#,
+        ] {
+            assert!(matches!(
+                PoseidonDemo.public_inputs(invalid),
+                Err(ProverError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn message_of_field_elements() {
+        let minus_one = -Fr::ONE;
+        let s = serde_json::json!({
+            "x": [Encoding::Decimal.encode(&minus_one), "0x2a", "Kg==", 42],
+            "encoding": "base64",
+        })
+        .to_string();
+        let output = wasm_poseidon_solution::<
+            WasmPoseidonSpec,
+            WASM_POSEIDON_WIDTH,
+            WASM_POSEIDON_RATE,
+        >(&[minus_one, Fr::from(42), Fr::from(42), Fr::from(42)]);
+        assert_eq!(
+            PoseidonDemo.simulate(&s).unwrap(),
+            Encoding::Base64.encode(&output)
+        );
+
+        let circuit = PoseidonDemo.create_from_json(&s).unwrap();
+        MockProver::run(PoseidonDemo.k(), &circuit, vec![vec![output, Fr::from(4)]])
+            .unwrap()
+            .assert_satisfied();
+    }
+
     #[test]
     fn parse_test() {
         let res: Fr = PrimeField::from_str_vartime(
@@ -0,0 +1,508 @@
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{keygen_pk, keygen_vk, Circuit, ProvingKey, VerifyingKey},
+    poly::{
+        commitment::Params,
+        kzg::commitment::{ParamsKZG, ParamsVerifierKZG},
+    },
+    SerdeFormat,
+};
+use serde::Serialize;
+use std::io::BufReader;
+
+use crate::{
+    arithmetic_circuit::ArithmeticDemo,
+    bundle::ProofBundle,
+    collatz::{COLLATZ, COLLATZ_128},
+    error::ProverError,
+    expression::ExpressionDemo,
+    merkle_circuit::MerkleDemo,
+    poseidon_circuit::PoseidonDemo,
+    transcript::TranscriptHash,
+    utils::{prove, verify, MultiOpenScheme},
+};
+
+/// A circuit that can be proven from the web frontend.
+///
+/// Every method that takes `s` receives the raw JSON string typed by the user, and returns
+/// [`ProverError::InvalidInput`] if it cannot be parsed.
+pub trait DemoCircuit: Sync {
+    /// The halo2 circuit built from the JSON input.
+    type Circuit: Circuit<Fr>;
+
+    /// Short identifier, also used as the file name of the rendered layout.
+    fn slug(&self) -> &'static str;
+
+    /// Display name of the circuit.
+    fn name(&self) -> &'static str;
+
+    /// One-line description of the statement being proven.
+    fn description(&self) -> &'static str;
+
+    /// Example of the JSON input accepted by [`DemoCircuit::create_from_json`].
+    fn input_schema(&self) -> &'static str;
+
+    /// Recommended `k` for the params used with this circuit.
+    fn k(&self) -> u32;
+
+    /// `k` of the params needed to prove `s`, for circuits whose size depends on the
+    /// input.
+    fn k_for(&self, _s: &str) -> Result<u32, ProverError> {
+        Ok(self.k())
+    }
+
+    /// Builds the circuit with its witness.
+    fn create_from_json(&self, s: &str) -> Result<Self::Circuit, ProverError>;
+
+    /// Builds the circuit without a witness, for key generation. Only the fields of `s`
+    /// that change the shape of the circuit are read.
+    fn empty(&self, s: &str) -> Result<Self::Circuit, ProverError>;
+
+    /// The fields of `s` read by [`DemoCircuit::empty`], serialized as JSON. Inputs with
+    /// the same shape share proving and verifying keys.
+    fn shape(&self, _s: &str) -> Result<String, ProverError> {
+        Ok(String::new())
+    }
+
+    /// Public inputs of the circuit, empty if it has no instance column.
+    fn public_inputs(&self, s: &str) -> Result<Vec<Fr>, ProverError>;
+
+    /// Computes the output of the circuit natively.
+    fn simulate(&self, s: &str) -> Result<String, ProverError>;
+}
+
+/// Entry of the registry exposed to JavaScript.
+#[derive(Serialize)]
+pub struct CircuitInfo {
+    pub id: usize,
+    pub slug: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub input_schema: &'static str,
+    pub k: u32,
+}
+
+/// Object-safe view of a [`DemoCircuit`], so circuits with different `Circuit` types can
+/// live in the same registry.
+pub trait CircuitRunner: Sync {
+    fn info(&self, id: usize) -> CircuitInfo;
+
+    fn simulate_json(&self, s: &str) -> Result<String, ProverError>;
+
+    fn shape_json(&self, s: &str) -> Result<String, ProverError>;
+
+    fn k_json(&self, s: &str) -> Result<u32, ProverError>;
+
+    fn keygen_vk(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        s: &str,
+    ) -> Result<VerifyingKey<G1Affine>, ProverError>;
+
+    fn keygen_pk(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        vk: VerifyingKey<G1Affine>,
+        s: &str,
+    ) -> Result<ProvingKey<G1Affine>, ProverError>;
+
+    fn read_vk(
+        &self,
+        bytes: &[u8],
+        format: SerdeFormat,
+    ) -> Result<VerifyingKey<G1Affine>, ProverError>;
+
+    fn read_pk(
+        &self,
+        bytes: &[u8],
+        format: SerdeFormat,
+    ) -> Result<ProvingKey<G1Affine>, ProverError>;
+
+    /// Proves `s`, recording `id` as the circuit of the returned bundle.
+    fn prove_json(
+        &self,
+        id: usize,
+        params: &ParamsKZG<Bn256>,
+        pk: &ProvingKey<G1Affine>,
+        s: &str,
+        scheme: MultiOpenScheme,
+        transcript: TranscriptHash,
+    ) -> Result<ProofBundle, ProverError>;
+
+    /// Verifies `bundle` against the instances it carries.
+    fn verify_bundle(
+        &self,
+        params: &ParamsVerifierKZG<Bn256>,
+        vk: &VerifyingKey<G1Affine>,
+        bundle: &ProofBundle,
+    ) -> Result<(), ProverError>;
+
+    /// Verifies that `bundle` proves the statement of `s`.
+    fn verify_json(
+        &self,
+        params: &ParamsVerifierKZG<Bn256>,
+        vk: &VerifyingKey<G1Affine>,
+        bundle: &ProofBundle,
+        s: &str,
+    ) -> Result<(), ProverError>;
+}
+
+impl<D: DemoCircuit> CircuitRunner for D {
+    fn info(&self, id: usize) -> CircuitInfo {
+        CircuitInfo {
+            id,
+            slug: self.slug(),
+            name: self.name(),
+            description: self.description(),
+            input_schema: self.input_schema(),
+            k: self.k(),
+        }
+    }
+
+    fn simulate_json(&self, s: &str) -> Result<String, ProverError> {
+        self.simulate(s)
+    }
+
+    fn shape_json(&self, s: &str) -> Result<String, ProverError> {
+        self.shape(s)
+    }
+
+    fn k_json(&self, s: &str) -> Result<u32, ProverError> {
+        self.k_for(s)
+    }
+
+    fn keygen_vk(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        s: &str,
+    ) -> Result<VerifyingKey<G1Affine>, ProverError> {
+        keygen_vk(params, &self.empty(s)?).map_err(|e| ProverError::KeygenFailed(e.to_string()))
+    }
+
+    fn keygen_pk(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        vk: VerifyingKey<G1Affine>,
+        s: &str,
+    ) -> Result<ProvingKey<G1Affine>, ProverError> {
+        keygen_pk(params, vk, &self.empty(s)?).map_err(|e| ProverError::KeygenFailed(e.to_string()))
+    }
+
+    fn read_vk(
+        &self,
+        bytes: &[u8],
+        format: SerdeFormat,
+    ) -> Result<VerifyingKey<G1Affine>, ProverError> {
+        VerifyingKey::read::<_, D::Circuit>(&mut BufReader::new(bytes), format)
+            .map_err(|e| ProverError::InvalidKey(e.to_string()))
+    }
+
+    fn read_pk(
+        &self,
+        bytes: &[u8],
+        format: SerdeFormat,
+    ) -> Result<ProvingKey<G1Affine>, ProverError> {
+        ProvingKey::read::<_, D::Circuit>(&mut BufReader::new(bytes), format)
+            .map_err(|e| ProverError::InvalidKey(e.to_string()))
+    }
+
+    fn prove_json(
+        &self,
+        id: usize,
+        params: &ParamsKZG<Bn256>,
+        pk: &ProvingKey<G1Affine>,
+        s: &str,
+        scheme: MultiOpenScheme,
+        transcript: TranscriptHash,
+    ) -> Result<ProofBundle, ProverError> {
+        let circuit = self.create_from_json(s)?;
+        let instances = instance_columns(self.public_inputs(s)?);
+        let proof = prove(
+            params,
+            pk,
+            circuit,
+            &instances.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+            scheme,
+            transcript,
+        )?;
+        Ok(ProofBundle {
+            circuit: id,
+            k: params.k(),
+            scheme,
+            transcript,
+            vk_hash: pk.get_vk().transcript_repr(),
+            shape: self.shape(s)?,
+            instances,
+            proof,
+        })
+    }
+
+    fn verify_bundle(
+        &self,
+        params: &ParamsVerifierKZG<Bn256>,
+        vk: &VerifyingKey<G1Affine>,
+        bundle: &ProofBundle,
+    ) -> Result<(), ProverError> {
+        if params.k() != bundle.k {
+            return Err(ProverError::InvalidParams(format!(
+                "proof was created with k = {}, params have k = {}",
+                bundle.k,
+                params.k()
+            )));
+        }
+        if vk.transcript_repr() != bundle.vk_hash {
+            return Err(ProverError::VerificationFailed(
+                "proof was created with a different verifying key".to_string(),
+            ));
+        }
+        verify(
+            params,
+            vk,
+            &bundle.proof,
+            &bundle.instance_columns(),
+            bundle.scheme,
+            bundle.transcript,
+        )
+        .map_err(|e| ProverError::VerificationFailed(e.to_string()))
+    }
+
+    fn verify_json(
+        &self,
+        params: &ParamsVerifierKZG<Bn256>,
+        vk: &VerifyingKey<G1Affine>,
+        bundle: &ProofBundle,
+        s: &str,
+    ) -> Result<(), ProverError> {
+        if bundle.instances != instance_columns(self.public_inputs(s)?) {
+            return Err(ProverError::VerificationFailed(
+                "proof is for different public inputs".to_string(),
+            ));
+        }
+        self.verify_bundle(params, vk, bundle)
+    }
+}
+
+/// Demo circuits have at most one instance column.
+fn instance_columns(public_inputs: Vec<Fr>) -> Vec<Vec<Fr>> {
+    if public_inputs.is_empty() {
+        vec![]
+    } else {
+        vec![public_inputs]
+    }
+}
+
+/// All circuits available to the frontend, indexed by id.
+pub static CIRCUITS: &[&dyn CircuitRunner] = &[
+    &COLLATZ,
+    &ArithmeticDemo,
+    &PoseidonDemo,
+    &COLLATZ_128,
+    &MerkleDemo,
+    &ExpressionDemo,
+];
+
+pub fn get_circuit(id: usize) -> Option<&'static dyn CircuitRunner> {
+    CIRCUITS.get(id).copied()
+}
+
+/// Like [`get_circuit`], for callers that report unknown ids as errors.
+pub fn find_circuit(id: usize) -> Result<&'static dyn CircuitRunner, ProverError> {
+    get_circuit(id).ok_or(ProverError::UnknownCircuit(id))
+}
+
+/// Id of the circuit with the given slug.
+pub fn circuit_id(slug: &str) -> Option<usize> {
+    circuit_infos()
+        .into_iter()
+        .find(|info| info.slug == slug)
+        .map(|info| info.id)
+}
+
+pub fn circuit_infos() -> Vec<CircuitInfo> {
+    CIRCUITS
+        .iter()
+        .enumerate()
+        .map(|(id, circuit)| circuit.info(id))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{circuit_id, circuit_infos, find_circuit, get_circuit, CIRCUITS};
+    use crate::{
+        error::ProverError,
+        transcript::TranscriptHash,
+        utils::{generate_params, MultiOpenScheme},
+    };
+
+    #[test]
+    fn registry_ids_are_positions() {
+        let infos = circuit_infos();
+        assert_eq!(infos.len(), CIRCUITS.len());
+        for (i, info) in infos.iter().enumerate() {
+            assert_eq!(info.id, i);
+        }
+        assert!(get_circuit(CIRCUITS.len()).is_none());
+        assert_eq!(
+            find_circuit(CIRCUITS.len()).err(),
+            Some(ProverError::UnknownCircuit(CIRCUITS.len()))
+        );
+    }
+
+    #[test]
+    fn prove_and_verify_arithmetic() {
+        let s = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#;
+        let id = circuit_id("arithmetic_circuit").unwrap();
+        let circuit = get_circuit(id).unwrap();
+        let params = generate_params(circuit.info(id).k);
+
+        let vk = circuit.keygen_vk(&params, s).unwrap();
+        let pk = circuit.keygen_pk(&params, vk.clone(), s).unwrap();
+        let proof = circuit
+            .prove_json(
+                id,
+                &params,
+                &pk,
+                s,
+                MultiOpenScheme::Gwc,
+                TranscriptHash::Keccak256,
+            )
+            .unwrap();
+        assert_eq!(proof.scheme, MultiOpenScheme::Gwc);
+        assert_eq!(proof.transcript, TranscriptHash::Keccak256);
+        assert_eq!(proof.circuit, id);
+        assert!(circuit.verify_json(&params, &vk, &proof, s).is_ok());
+
+        let other = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2924 }"#;
+        assert!(matches!(
+            circuit.verify_json(&params, &vk, &proof, other),
+            Err(ProverError::VerificationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn collatz_proof_attests_start_and_steps() {
+        let s = r#"{ "n": 27, "max_steps": 111 }"#;
+        let id = circuit_id("collatz").unwrap();
+        let circuit = get_circuit(id).unwrap();
+        let params = generate_params(circuit.k_json(s).unwrap());
+
+        let vk = circuit.keygen_vk(&params, s).unwrap();
+        let pk = circuit.keygen_pk(&params, vk.clone(), s).unwrap();
+        let proof = circuit
+            .prove_json(
+                id,
+                &params,
+                &pk,
+                s,
+                MultiOpenScheme::default(),
+                TranscriptHash::default(),
+            )
+            .unwrap();
+
+        let claim = r#"{ "n": 27, "steps": 111, "max_steps": 111 }"#;
+        assert!(circuit.verify_json(&params, &vk, &proof, claim).is_ok());
+        for other in [
+            r#"{ "n": 27, "steps": 110, "max_steps": 111 }"#,
+            r#"{ "n": 31, "steps": 111, "max_steps": 111 }"#,
+        ] {
+            assert!(matches!(
+                circuit.verify_json(&params, &vk, &proof, other),
+                Err(ProverError::VerificationFailed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn poseidon_proof_verifies_against_hash_alone() {
+        let s = r#"{ "x": [1, 2, 3] }"#;
+        let id = circuit_id("poseidon").unwrap();
+        let circuit = get_circuit(id).unwrap();
+        let params = generate_params(circuit.k_json(s).unwrap());
+
+        let vk = circuit.keygen_vk(&params, s).unwrap();
+        let pk = circuit.keygen_pk(&params, vk, s).unwrap();
+        let proof = circuit
+            .prove_json(
+                id,
+                &params,
+                &pk,
+                s,
+                MultiOpenScheme::default(),
+                TranscriptHash::default(),
+            )
+            .unwrap();
+
+        // The verifier only knows the hash and the message length.
+        let output = circuit.simulate_json(s).unwrap();
+        let claim = format!(r#"{{ "output": "{}", "len": 3 }}"#, output);
+        let vk = circuit.keygen_vk(&params, &claim).unwrap();
+        assert!(circuit.verify_json(&params, &vk, &proof, &claim).is_ok());
+
+        // The same keys verify every length, and the length is part of the claim.
+        let other_len = format!(r#"{{ "output": "{}", "len": 4 }}"#, output);
+        for other in [r#"{ "output": "42", "len": 3 }"#, other_len.as_str()] {
+            assert!(matches!(
+                circuit.verify_json(&params, &vk, &proof, other),
+                Err(ProverError::VerificationFailed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn expression_proof_verifies_against_outputs() {
+        let s = r#"{ "program": "let t = x * y; pub z = t^3 - x", "inputs": { "x": 2, "y": 3 } }"#;
+        let id = circuit_id("expression").unwrap();
+        let circuit = get_circuit(id).unwrap();
+        let params = generate_params(circuit.k_json(s).unwrap());
+
+        let vk = circuit.keygen_vk(&params, s).unwrap();
+        let pk = circuit.keygen_pk(&params, vk.clone(), s).unwrap();
+        let proof = circuit
+            .prove_json(
+                id,
+                &params,
+                &pk,
+                s,
+                MultiOpenScheme::default(),
+                TranscriptHash::default(),
+            )
+            .unwrap();
+        assert_eq!(circuit.simulate_json(s).unwrap(), r#"["214"]"#);
+
+        let claim = r#"{ "program": "let t = x * y; pub z = t^3 - x", "outputs": [214] }"#;
+        assert!(circuit.verify_json(&params, &vk, &proof, claim).is_ok());
+        let other = r#"{ "program": "let t = x * y; pub z = t^3 - x", "outputs": [215] }"#;
+        assert!(matches!(
+            circuit.verify_json(&params, &vk, &proof, other),
+            Err(ProverError::VerificationFailed(_))
+        ));
+    }
+
+    fn invalid_input<T>(res: Result<T, ProverError>) -> bool {
+        matches!(res, Err(ProverError::InvalidInput(_)))
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let s = r#"{ "x": 6, "y": 9, "constant": 7, "z": 2923 }"#;
+        let id = circuit_id("arithmetic_circuit").unwrap();
+        let circuit = get_circuit(id).unwrap();
+        let params = generate_params(circuit.info(id).k);
+
+        assert!(invalid_input(circuit.simulate_json("{")));
+        assert!(invalid_input(circuit.keygen_vk(&params, r#"{ "x": 6 }"#)));
+
+        let vk = circuit.keygen_vk(&params, s).unwrap();
+        let pk = circuit.keygen_pk(&params, vk, s).unwrap();
+        let missing_z = r#"{ "x": 6, "y": 9, "constant": 7 }"#;
+        assert!(invalid_input(circuit.prove_json(
+            id,
+            &params,
+            &pk,
+            missing_z,
+            MultiOpenScheme::default(),
+            TranscriptHash::default(),
+        )));
+    }
+}
@@ -0,0 +1,765 @@
+//! Sparse Poseidon Merkle trees, with proofs of non-membership, insertion and update.
+//!
+//! The leaf of a key sits at the position given by the little-endian bits of the key, so
+//! a tree of depth `depth` holds keys below `2^depth`. An empty leaf is zero and a leaf
+//! holding `value` is `hash_pair(key, value)`. Nodes are hashed as in
+//! [`crate::merkle_circuit`], an empty subtree of height `h` hashing to `empty[h]`.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::{
+        bn256::Fr,
+        ff::{Field, PrimeField},
+    },
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Instance,
+        Selector,
+    },
+    poly::Rotation,
+};
+
+use crate::{
+    error::ProverError,
+    merkle_circuit::{
+        hash_pair, CondSwapChip, CondSwapConfig, MerkleSpec, MERKLE_RATE, MERKLE_WIDTH,
+    },
+    poseidon::{
+        primitives::{ConstantLength, Spec},
+        Hash, Pow5Chip, Pow5Config,
+    },
+};
+
+/// Deepest sparse tree, keyed by any field element.
+pub const SPARSE_MAX_DEPTH: usize = 256;
+
+/// The statement proven by a [`SparseMerkleCircuit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SparseMerkleOperation {
+    /// The key is absent from the tree with the given root.
+    NonMembership,
+    /// Setting the absent key to a value turns the old root into the new one.
+    Insert,
+    /// Changing the value of the present key turns the old root into the new one.
+    Update,
+}
+
+/// A sparse Merkle tree, storing only its non-empty leaves.
+#[derive(Clone, Debug)]
+pub struct SparseMerkleTree {
+    depth: usize,
+    leaves: BTreeMap<Fr, Fr>,
+    /// Roots of the empty subtrees of each height.
+    empty: Vec<Fr>,
+}
+
+/// An operation on a [`SparseMerkleTree`] with the witness proving it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparseMerkleProof {
+    pub operation: SparseMerkleOperation,
+    pub key: Fr,
+    /// Value of the key before the operation, zero if it was absent.
+    pub old_value: Fr,
+    /// Value of the key after the operation, zero if it is absent.
+    pub new_value: Fr,
+    /// Siblings of the leaf of `key`, from the leaf up.
+    pub siblings: Vec<Fr>,
+    pub old_root: Fr,
+    pub new_root: Fr,
+}
+
+impl SparseMerkleProof {
+    /// `[key, root]` for a non-membership proof, `[key, old_root, new_root]` otherwise.
+    pub fn public_inputs(&self) -> Vec<Fr> {
+        match self.operation {
+            SparseMerkleOperation::NonMembership => vec![self.key, self.old_root],
+            _ => vec![self.key, self.old_root, self.new_root],
+        }
+    }
+}
+
+/// The bits of `key` choosing its leaf, from the leaf up: bit `i` is set when the node
+/// at height `i` on its path is a right child.
+pub fn key_bits(key: Fr, depth: usize) -> Result<Vec<bool>, ProverError> {
+    let repr = key.to_repr();
+    let bit = |i: usize| i < 256 && (repr.as_ref()[i / 8] >> (i % 8)) & 1 == 1;
+    if (depth..256).any(bit) {
+        return Err(ProverError::InvalidInput(format!(
+            "key {:?} does not fit in a tree of depth {}",
+            key, depth
+        )));
+    }
+    Ok((0..depth).map(bit).collect())
+}
+
+impl SparseMerkleTree {
+    pub fn new(depth: usize) -> Result<Self, ProverError> {
+        if depth == 0 || depth > SPARSE_MAX_DEPTH {
+            return Err(ProverError::InvalidInput(format!(
+                "expected a depth between 1 and {}, got {}",
+                SPARSE_MAX_DEPTH, depth
+            )));
+        }
+
+        let mut empty = vec![Fr::ZERO];
+        for height in 0..depth {
+            empty.push(hash_pair(empty[height], empty[height]));
+        }
+        Ok(SparseMerkleTree {
+            depth,
+            leaves: BTreeMap::new(),
+            empty,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn get(&self, key: Fr) -> Option<Fr> {
+        self.leaves.get(&key).copied()
+    }
+
+    pub fn root(&self) -> Fr {
+        self.subtree(self.depth, &self.leaf_nodes())
+    }
+
+    /// The bits and hash of every non-empty leaf.
+    fn leaf_nodes(&self) -> Vec<(Vec<bool>, Fr)> {
+        self.leaves
+            .iter()
+            .map(|(key, value)| {
+                let bits = key_bits(*key, self.depth).expect("keys are checked on insertion");
+                (bits, hash_pair(*key, *value))
+            })
+            .collect()
+    }
+
+    /// Root of the subtree of height `height` holding `leaves`.
+    fn subtree(&self, height: usize, leaves: &[(Vec<bool>, Fr)]) -> Fr {
+        match leaves {
+            [] => self.empty[height],
+            [(_, leaf)] if height == 0 => *leaf,
+            _ => {
+                let (right, left): (Vec<_>, Vec<_>) = leaves
+                    .iter()
+                    .cloned()
+                    .partition(|(bits, _)| bits[height - 1]);
+                hash_pair(
+                    self.subtree(height - 1, &left),
+                    self.subtree(height - 1, &right),
+                )
+            }
+        }
+    }
+
+    /// Siblings of the leaf of `key`, from the leaf up.
+    pub fn siblings(&self, key: Fr) -> Result<Vec<Fr>, ProverError> {
+        let bits = key_bits(key, self.depth)?;
+        let mut leaves = self.leaf_nodes();
+        let mut siblings = Vec::with_capacity(self.depth);
+        for height in (1..=self.depth).rev() {
+            let (same, other): (Vec<_>, Vec<_>) = leaves
+                .into_iter()
+                .partition(|(leaf_bits, _)| leaf_bits[height - 1] == bits[height - 1]);
+            siblings.push(self.subtree(height - 1, &other));
+            leaves = same;
+        }
+        siblings.reverse();
+        Ok(siblings)
+    }
+
+    /// Proves that `key` is absent.
+    pub fn non_membership(&self, key: Fr) -> Result<SparseMerkleProof, ProverError> {
+        if self.get(key).is_some() {
+            return Err(ProverError::InvalidInput(format!(
+                "key {:?} is in the tree",
+                key
+            )));
+        }
+        let root = self.root();
+        Ok(SparseMerkleProof {
+            operation: SparseMerkleOperation::NonMembership,
+            key,
+            old_value: Fr::ZERO,
+            new_value: Fr::ZERO,
+            siblings: self.siblings(key)?,
+            old_root: root,
+            new_root: root,
+        })
+    }
+
+    /// Sets the absent `key` to `value`.
+    pub fn insert(&mut self, key: Fr, value: Fr) -> Result<SparseMerkleProof, ProverError> {
+        if self.get(key).is_some() {
+            return Err(ProverError::InvalidInput(format!(
+                "key {:?} is already in the tree",
+                key
+            )));
+        }
+        self.set(SparseMerkleOperation::Insert, key, Fr::ZERO, value)
+    }
+
+    /// Changes the value of the present `key` to `value`.
+    pub fn update(&mut self, key: Fr, value: Fr) -> Result<SparseMerkleProof, ProverError> {
+        let old_value = self.get(key).ok_or_else(|| {
+            ProverError::InvalidInput(format!("key {:?} is not in the tree", key))
+        })?;
+        self.set(SparseMerkleOperation::Update, key, old_value, value)
+    }
+
+    fn set(
+        &mut self,
+        operation: SparseMerkleOperation,
+        key: Fr,
+        old_value: Fr,
+        new_value: Fr,
+    ) -> Result<SparseMerkleProof, ProverError> {
+        let siblings = self.siblings(key)?;
+        let old_root = self.root();
+        self.leaves.insert(key, new_value);
+        Ok(SparseMerkleProof {
+            operation,
+            key,
+            old_value,
+            new_value,
+            siblings,
+            old_root,
+            new_root: self.root(),
+        })
+    }
+}
+
+/// Configuration for a [`KeyBitsChip`].
+#[derive(Clone, Debug)]
+pub struct KeyBitsConfig {
+    bit: Column<Advice>,
+    acc: Column<Advice>,
+    eq: Column<Advice>,
+    lt: Column<Advice>,
+    modulus_bit: Column<Fixed>,
+    s_decompose: Selector,
+    s_canonical: Selector,
+    s_canonical_end: Selector,
+}
+
+/// Decomposes a field element into little-endian bits.
+///
+/// The bits are accumulated from the most significant one, `acc' = 2 acc + bit`. When
+/// they can exceed the modulus, the chip also checks that they are at most `p - 1`,
+/// tracking whether the bits so far are equal to (`eq`) or below (`lt`) those of `p - 1`.
+#[derive(Clone, Debug)]
+pub struct KeyBitsChip {
+    config: KeyBitsConfig,
+}
+
+impl KeyBitsChip {
+    /// Configures this chip on the columns `[bit, acc, eq, lt]`.
+    ///
+    /// # Side-effects
+    ///
+    /// `bit` and `acc` will be equality-enabled.
+    pub fn configure(
+        meta: &mut ConstraintSystem<Fr>,
+        advices: [Column<Advice>; 4],
+        modulus_bit: Column<Fixed>,
+    ) -> KeyBitsConfig {
+        let [bit, acc, eq, lt] = advices;
+        meta.enable_equality(bit);
+        meta.enable_equality(acc);
+        let s_decompose = meta.selector();
+        let s_canonical = meta.selector();
+        let s_canonical_end = meta.selector();
+        let one = || Expression::Constant(Fr::ONE);
+
+        meta.create_gate("decompose", |meta| {
+            let s_decompose = meta.query_selector(s_decompose);
+            let bit = meta.query_advice(bit, Rotation::cur());
+            let acc = meta.query_advice(acc, Rotation::cur());
+            let acc_next = meta.query_advice(acc, Rotation::next());
+
+            Constraints::with_selector(
+                s_decompose,
+                [
+                    ("bool", bit.clone() * (one() - bit.clone())),
+                    ("accumulate", acc * Fr::from(2) + bit - acc_next),
+                ],
+            )
+        });
+
+        meta.create_gate("canonical", |meta| {
+            let s_canonical = meta.query_selector(s_canonical);
+            let bit = meta.query_advice(bit, Rotation::cur());
+            let modulus_bit = meta.query_fixed(modulus_bit, Rotation::cur());
+            let eq = meta.query_advice(eq, Rotation::cur());
+            let lt = meta.query_advice(lt, Rotation::cur());
+            let eq_next = meta.query_advice(eq, Rotation::next());
+            let lt_next = meta.query_advice(lt, Rotation::next());
+
+            let same_bit = modulus_bit.clone() * bit.clone()
+                + (one() - modulus_bit.clone()) * (one() - bit.clone());
+            Constraints::with_selector(
+                s_canonical,
+                [
+                    ("eq", eq.clone() * same_bit - eq_next),
+                    ("lt", lt + eq * modulus_bit * (one() - bit) - lt_next),
+                ],
+            )
+        });
+
+        meta.create_gate("canonical end", |meta| {
+            let s_canonical_end = meta.query_selector(s_canonical_end);
+            let eq = meta.query_advice(eq, Rotation::cur());
+            let lt = meta.query_advice(lt, Rotation::cur());
+
+            Constraints::with_selector(s_canonical_end, Some(eq + lt - one()))
+        });
+
+        KeyBitsConfig {
+            bit,
+            acc,
+            eq,
+            lt,
+            modulus_bit,
+            s_decompose,
+            s_canonical,
+            s_canonical_end,
+        }
+    }
+
+    pub fn construct(config: KeyBitsConfig) -> Self {
+        KeyBitsChip { config }
+    }
+
+    /// The `len` little-endian bits of `value`, constrained to sum to it. Bits from
+    /// [`Fr::NUM_BITS`] on are zero.
+    pub fn decompose(
+        &self,
+        mut layouter: impl Layouter<Fr>,
+        value: &AssignedCell<Fr, Fr>,
+        len: usize,
+    ) -> Result<Vec<AssignedCell<Fr, Fr>>, Error> {
+        let config = &self.config;
+        let num_bits = len.min(Fr::NUM_BITS as usize);
+        // Fewer bits than the modulus always give a canonical value.
+        let canonical = num_bits == Fr::NUM_BITS as usize;
+        let max_repr = (-Fr::ONE).to_repr();
+        let max_bit = |i: usize| (max_repr.as_ref()[i / 8] >> (i % 8)) & 1 == 1;
+
+        layouter.assign_region(
+            || "decompose key",
+            |mut region| {
+                let bits = value.value().map(|value| {
+                    let repr = value.to_repr();
+                    (0..num_bits)
+                        .map(|i| (repr.as_ref()[i / 8] >> (i % 8)) & 1 == 1)
+                        .collect::<Vec<_>>()
+                });
+
+                let mut acc =
+                    region.assign_advice_from_constant(|| "acc", config.acc, 0, Fr::ZERO)?;
+                let mut acc_value = Value::known(Fr::ZERO);
+                let mut eq_value = Value::known(Fr::ONE);
+                let mut lt_value = Value::known(Fr::ZERO);
+                if canonical {
+                    region.assign_advice_from_constant(|| "eq", config.eq, 0, Fr::ONE)?;
+                    region.assign_advice_from_constant(|| "lt", config.lt, 0, Fr::ZERO)?;
+                }
+
+                // Most significant bit first.
+                let mut assigned = Vec::with_capacity(len);
+                for (row, i) in (0..num_bits).rev().enumerate() {
+                    config.s_decompose.enable(&mut region, row)?;
+                    let bit = bits.as_ref().map(|bits| bits[i]);
+                    let bit_value = bit.map(|bit| if bit { Fr::ONE } else { Fr::ZERO });
+                    assigned.push(region.assign_advice(
+                        || format!("bit {}", i),
+                        config.bit,
+                        row,
+                        || bit_value,
+                    )?);
+
+                    acc_value = acc_value
+                        .zip(bit_value)
+                        .map(|(acc, bit)| acc.double() + bit);
+                    acc = region.assign_advice(|| "acc", config.acc, row + 1, || acc_value)?;
+
+                    if canonical {
+                        config.s_canonical.enable(&mut region, row)?;
+                        let modulus_bit = if max_bit(i) { Fr::ONE } else { Fr::ZERO };
+                        region.assign_fixed(
+                            || format!("modulus bit {}", i),
+                            config.modulus_bit,
+                            row,
+                            || Value::known(modulus_bit),
+                        )?;
+                        lt_value = lt_value
+                            .zip(eq_value)
+                            .zip(bit_value)
+                            .map(|((lt, eq), bit)| lt + eq * modulus_bit * (Fr::ONE - bit));
+                        eq_value = eq_value.zip(bit_value).map(|(eq, bit)| {
+                            eq * (modulus_bit * bit + (Fr::ONE - modulus_bit) * (Fr::ONE - bit))
+                        });
+                        region.assign_advice(|| "eq", config.eq, row + 1, || eq_value)?;
+                        region.assign_advice(|| "lt", config.lt, row + 1, || lt_value)?;
+                    }
+                }
+                if canonical {
+                    config.s_canonical_end.enable(&mut region, num_bits)?;
+                }
+                region.constrain_equal(acc.cell(), value.cell())?;
+                assigned.reverse();
+
+                // The bits beyond the field size.
+                for i in num_bits..len {
+                    assigned.push(region.assign_advice_from_constant(
+                        || format!("bit {}", i),
+                        config.bit,
+                        i + 1,
+                        Fr::ZERO,
+                    )?);
+                }
+                Ok(assigned)
+            },
+        )
+    }
+}
+
+/// Proves a [`SparseMerkleOperation`] on a sparse tree whose depth is the length of
+/// `siblings`. The public inputs are those of [`SparseMerkleProof::public_inputs`].
+#[derive(Clone)]
+pub struct SparseMerkleCircuit {
+    operation: SparseMerkleOperation,
+    key: Value<Fr>,
+    old_value: Value<Fr>,
+    new_value: Value<Fr>,
+    siblings: Vec<Value<Fr>>,
+}
+
+impl SparseMerkleCircuit {
+    pub fn new(proof: &SparseMerkleProof) -> Self {
+        SparseMerkleCircuit {
+            operation: proof.operation,
+            key: Value::known(proof.key),
+            old_value: Value::known(proof.old_value),
+            new_value: Value::known(proof.new_value),
+            siblings: proof.siblings.iter().copied().map(Value::known).collect(),
+        }
+    }
+
+    pub fn empty(operation: SparseMerkleOperation, depth: usize) -> Self {
+        SparseMerkleCircuit {
+            operation,
+            key: Value::unknown(),
+            old_value: Value::unknown(),
+            new_value: Value::unknown(),
+            siblings: vec![Value::unknown(); depth],
+        }
+    }
+
+    /// Root of the tree holding `leaf` at the position of `bits`.
+    fn root(
+        &self,
+        config: &SparseMerkleConfig,
+        mut layouter: impl Layouter<Fr>,
+        leaf: AssignedCell<Fr, Fr>,
+        bits: &[AssignedCell<Fr, Fr>],
+        siblings: &[AssignedCell<Fr, Fr>],
+    ) -> Result<AssignedCell<Fr, Fr>, Error> {
+        let swap_chip = CondSwapChip::construct(config.swap_config.clone());
+        bits.iter().zip(siblings.iter()).enumerate().try_fold(
+            leaf,
+            |node, (height, (bit, sibling))| {
+                let (left, right) = swap_chip.swap_assigned(
+                    layouter.namespace(|| format!("order height {}", height)),
+                    &node,
+                    sibling,
+                    bit,
+                )?;
+                hash(
+                    config,
+                    layouter.namespace(|| format!("hash height {}", height)),
+                    left,
+                    right,
+                )
+            },
+        )
+    }
+}
+
+fn hash(
+    config: &SparseMerkleConfig,
+    mut layouter: impl Layouter<Fr>,
+    left: AssignedCell<Fr, Fr>,
+    right: AssignedCell<Fr, Fr>,
+) -> Result<AssignedCell<Fr, Fr>, Error> {
+    let chip = Pow5Chip::construct(config.poseidon_config.clone());
+    let hasher = Hash::<_, _, MerkleSpec, ConstantLength<2>, MERKLE_WIDTH, MERKLE_RATE>::init(
+        chip,
+        layouter.namespace(|| "init"),
+    )?;
+    hasher.hash(layouter.namespace(|| "hash"), [left, right])
+}
+
+#[derive(Debug, Clone)]
+pub struct SparseMerkleConfig {
+    state: [Column<Advice>; MERKLE_WIDTH],
+    sibling: Column<Advice>,
+    instance: Column<Instance>,
+    key_bits_config: KeyBitsConfig,
+    swap_config: CondSwapConfig,
+    poseidon_config: Pow5Config<Fr, MERKLE_WIDTH, MERKLE_RATE>,
+}
+
+impl Circuit<Fr> for SparseMerkleCircuit {
+    type Config = SparseMerkleConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        Self::empty(self.operation, self.siblings.len())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        let state: [Column<Advice>; MERKLE_WIDTH] = (0..MERKLE_WIDTH)
+            .map(|_| meta.advice_column())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let partial_sbox = meta.advice_column();
+        let swap = meta.advice_column();
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+
+        let rc_a = (0..MERKLE_WIDTH)
+            .map(|_| meta.fixed_column())
+            .collect::<Vec<_>>();
+        let rc_b = (0..MERKLE_WIDTH)
+            .map(|_| meta.fixed_column())
+            .collect::<Vec<_>>();
+        let modulus_bit = meta.fixed_column();
+
+        meta.enable_constant(rc_b[0]);
+
+        SparseMerkleConfig {
+            state,
+            sibling: partial_sbox,
+            instance,
+            key_bits_config: KeyBitsChip::configure(
+                meta,
+                [swap, state[0], state[1], state[2]],
+                modulus_bit,
+            ),
+            swap_config: CondSwapChip::configure(
+                meta,
+                [state[0], partial_sbox, swap, state[1], state[2]],
+            ),
+            poseidon_config: Pow5Chip::configure::<MerkleSpec>(
+                meta,
+                state,
+                partial_sbox,
+                rc_a.try_into().unwrap(),
+                rc_b.try_into().unwrap(),
+            ),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let (key, old_value, new_value, siblings) = layouter.assign_region(
+            || "load witness",
+            |mut region| {
+                let key = region.assign_advice(|| "key", config.state[0], 0, || self.key)?;
+                let old_value =
+                    region.assign_advice(|| "old value", config.state[1], 0, || self.old_value)?;
+                let new_value =
+                    region.assign_advice(|| "new value", config.state[2], 0, || self.new_value)?;
+                let siblings = self
+                    .siblings
+                    .iter()
+                    .enumerate()
+                    .map(|(height, sibling)| {
+                        region.assign_advice(
+                            || format!("sibling {}", height),
+                            config.sibling,
+                            height,
+                            || *sibling,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok((key, old_value, new_value, siblings))
+            },
+        )?;
+
+        let key_bits = KeyBitsChip::construct(config.key_bits_config.clone());
+        let bits =
+            key_bits.decompose(layouter.namespace(|| "key bits"), &key, self.siblings.len())?;
+
+        let old_leaf = match self.operation {
+            SparseMerkleOperation::NonMembership | SparseMerkleOperation::Insert => layouter
+                .assign_region(
+                    || "empty leaf",
+                    |mut region| {
+                        region.assign_advice_from_constant(
+                            || "empty leaf",
+                            config.state[0],
+                            0,
+                            Fr::ZERO,
+                        )
+                    },
+                )?,
+            SparseMerkleOperation::Update => hash(
+                &config,
+                layouter.namespace(|| "old leaf"),
+                key.clone(),
+                old_value,
+            )?,
+        };
+        let old_root = self.root(
+            &config,
+            layouter.namespace(|| "old root"),
+            old_leaf,
+            &bits,
+            &siblings,
+        )?;
+
+        layouter.constrain_instance(key.cell(), config.instance, 0)?;
+        layouter.constrain_instance(old_root.cell(), config.instance, 1)?;
+
+        if self.operation != SparseMerkleOperation::NonMembership {
+            let new_leaf = hash(
+                &config,
+                layouter.namespace(|| "new leaf"),
+                key.clone(),
+                new_value,
+            )?;
+            let new_root = self.root(
+                &config,
+                layouter.namespace(|| "new root"),
+                new_leaf,
+                &bits,
+                &siblings,
+            )?;
+            layouter.constrain_instance(new_root.cell(), config.instance, 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// Smallest `k` whose params fit a [`SparseMerkleCircuit`] of depth `depth`.
+pub fn sparse_merkle_k(operation: SparseMerkleOperation, depth: usize) -> u32 {
+    let mut meta = ConstraintSystem::<Fr>::default();
+    SparseMerkleCircuit::configure(&mut meta);
+
+    // A hash takes its initial state, three rows to add the input and the permutation.
+    let permutation = MerkleSpec::full_rounds() + (MerkleSpec::partial_rounds() + 1) / 2 + 1;
+    let hash = 1 + 3 + permutation;
+    let (leaves, paths) = match operation {
+        SparseMerkleOperation::NonMembership => (0, 1),
+        SparseMerkleOperation::Insert => (1, 2),
+        SparseMerkleOperation::Update => (2, 2),
+    };
+    // The witness, the key bits and an empty leaf, then the hashes.
+    let rows = depth.max(1)
+        + (depth + 1)
+        + 1
+        + leaves * hash
+        + paths * depth * (1 + hash)
+        + meta.minimum_rows();
+    rows.next_power_of_two().trailing_zeros()
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::{
+        dev::MockProver,
+        halo2curves::{bn256::Fr, ff::Field},
+    };
+
+    use super::{
+        key_bits, sparse_merkle_k, SparseMerkleCircuit, SparseMerkleOperation, SparseMerkleProof,
+        SparseMerkleTree,
+    };
+    use crate::merkle_circuit::hash_pair;
+
+    fn check(proof: &SparseMerkleProof) {
+        let k = sparse_merkle_k(proof.operation, proof.siblings.len());
+        let circuit = SparseMerkleCircuit::new(proof);
+        MockProver::run(k, &circuit, vec![proof.public_inputs()])
+            .unwrap()
+            .assert_satisfied();
+
+        let mut public_inputs = proof.public_inputs();
+        *public_inputs.last_mut().unwrap() += Fr::ONE;
+        let prover = MockProver::run(k, &circuit, vec![public_inputs]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn tree_matches_hashes() {
+        let mut tree = SparseMerkleTree::new(2).unwrap();
+        let empty = hash_pair(Fr::ZERO, Fr::ZERO);
+        assert_eq!(tree.root(), hash_pair(empty, empty));
+
+        tree.insert(Fr::from(2), Fr::from(7)).unwrap();
+        tree.insert(Fr::from(3), Fr::from(8)).unwrap();
+        let right = hash_pair(
+            hash_pair(Fr::from(2), Fr::from(7)),
+            hash_pair(Fr::from(3), Fr::from(8)),
+        );
+        assert_eq!(tree.root(), hash_pair(empty, right));
+        assert_eq!(tree.siblings(Fr::from(0)).unwrap(), vec![Fr::ZERO, right]);
+
+        assert!(tree.insert(Fr::from(2), Fr::from(1)).is_err());
+        assert!(tree.update(Fr::from(1), Fr::from(1)).is_err());
+        assert!(tree.non_membership(Fr::from(3)).is_err());
+        assert!(tree.insert(Fr::from(4), Fr::from(1)).is_err());
+        assert!(key_bits(-Fr::ONE, 256).is_ok());
+    }
+
+    #[test]
+    fn depth_32() {
+        let mut tree = SparseMerkleTree::new(32).unwrap();
+        check(&tree.insert(Fr::from(5), Fr::from(50)).unwrap());
+        check(&tree.insert(Fr::from(0xffff_ffff), Fr::from(60)).unwrap());
+        check(&tree.update(Fr::from(5), Fr::from(51)).unwrap());
+        check(&tree.non_membership(Fr::from(6)).unwrap());
+    }
+
+    #[test]
+    fn depth_256() {
+        let mut tree = SparseMerkleTree::new(256).unwrap();
+        // The largest key, whose bits are those of p - 1.
+        check(&tree.insert(-Fr::ONE, Fr::from(1)).unwrap());
+        check(&tree.update(-Fr::ONE, Fr::from(2)).unwrap());
+        check(&tree.non_membership(Fr::from(1 << 40)).unwrap());
+    }
+
+    #[test]
+    fn present_key_is_not_absent() {
+        let mut tree = SparseMerkleTree::new(32).unwrap();
+        tree.insert(Fr::from(9), Fr::from(90)).unwrap();
+
+        // Claim the leaf of the present key is empty.
+        let proof = SparseMerkleProof {
+            operation: SparseMerkleOperation::NonMembership,
+            key: Fr::from(9),
+            old_value: Fr::ZERO,
+            new_value: Fr::ZERO,
+            siblings: tree.siblings(Fr::from(9)).unwrap(),
+            old_root: tree.root(),
+            new_root: tree.root(),
+        };
+        let circuit = SparseMerkleCircuit::new(&proof);
+        let k = sparse_merkle_k(proof.operation, 32);
+        let prover = MockProver::run(k, &circuit, vec![proof.public_inputs()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
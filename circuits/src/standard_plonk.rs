@@ -0,0 +1,629 @@
+//! The standard PLONK gate `sl·l + sr·r + sm·l·r - so·o + sc = 0` on three advice
+//! columns, as a chip whose operations take and return [`AssignedCell`]s.
+
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Chip, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error, Fixed},
+    poly::Rotation,
+};
+
+/// Operations of the [`StandardPlonkChip`]. Each takes one row of the gate, except where
+/// noted, and copies its operands into the row.
+pub trait StandardPlonkInstructions<F: Field>: Chip<F> {
+    /// Witnesses `value` in an unconstrained cell.
+    fn load_private(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    /// A cell fixed to `constant`.
+    fn load_constant(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    fn add(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    fn sub(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    fn mul(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    fn add_constant(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    fn mul_constant(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    /// `a + constant·b`.
+    fn add_scaled(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    /// Constrains `a = b` with the permutation argument, without using a row.
+    fn assert_equal(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<(), Error>;
+
+    fn assert_zero(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+    ) -> Result<(), Error>;
+
+    /// Constrains `a` to be 0 or 1.
+    fn assert_bool(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+    ) -> Result<(), Error>;
+
+    /// `a` if `cond` is 1 and `b` if it is 0, constraining `cond` to be a bit. Takes four
+    /// rows.
+    fn select(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        cond: &AssignedCell<F, F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    /// 1 if `a` is zero and 0 otherwise. Takes three rows.
+    fn is_zero(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+}
+
+#[derive(Clone, Debug)]
+pub struct StandardPlonkConfig {
+    pub l: Column<Advice>,
+    pub r: Column<Advice>,
+    pub o: Column<Advice>,
+    pub sl: Column<Fixed>,
+    pub sr: Column<Fixed>,
+    pub so: Column<Fixed>,
+    pub sm: Column<Fixed>,
+    pub sc: Column<Fixed>,
+}
+
+/// The coefficients of one row of the gate.
+#[derive(Clone, Copy, Default)]
+struct Selectors<F: Field> {
+    l: F,
+    r: F,
+    m: F,
+    o: F,
+    c: F,
+}
+
+/// An advice cell of a row: a copy of an assigned cell, or a fresh witness.
+enum Operand<'a, F: Field> {
+    Assigned(&'a AssignedCell<F, F>),
+    Witness(Value<F>),
+}
+
+impl<'a, F: Field> From<&'a AssignedCell<F, F>> for Operand<'a, F> {
+    fn from(cell: &'a AssignedCell<F, F>) -> Self {
+        Operand::Assigned(cell)
+    }
+}
+
+impl<F: Field> Operand<'_, F> {
+    fn value(&self) -> Value<F> {
+        match self {
+            Operand::Assigned(cell) => cell.value().copied(),
+            Operand::Witness(value) => *value,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StandardPlonkChip<F: Field> {
+    config: StandardPlonkConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> StandardPlonkChip<F> {
+    /// Configures the gate on `l`, `r` and `o`, enabling equality on them.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        [l, r, o]: [Column<Advice>; 3],
+    ) -> StandardPlonkConfig {
+        meta.enable_equality(l);
+        meta.enable_equality(r);
+        meta.enable_equality(o);
+
+        let sm = meta.fixed_column();
+        let sl = meta.fixed_column();
+        let sr = meta.fixed_column();
+        let so = meta.fixed_column();
+        let sc = meta.fixed_column();
+
+        meta.create_gate("plonk", |meta| {
+            let l = meta.query_advice(l, Rotation::cur());
+            let r = meta.query_advice(r, Rotation::cur());
+            let o = meta.query_advice(o, Rotation::cur());
+
+            let sl = meta.query_fixed(sl, Rotation::cur());
+            let sr = meta.query_fixed(sr, Rotation::cur());
+            let so = meta.query_fixed(so, Rotation::cur());
+            let sm = meta.query_fixed(sm, Rotation::cur());
+            let sc = meta.query_fixed(sc, Rotation::cur());
+
+            vec![l.clone() * sl + r.clone() * sr + l * r * sm + (o * so * (-F::ONE)) + sc]
+        });
+
+        StandardPlonkConfig {
+            l,
+            r,
+            o,
+            sl,
+            sr,
+            so,
+            sm,
+            sc,
+        }
+    }
+
+    pub fn construct(config: StandardPlonkConfig) -> Self {
+        StandardPlonkChip {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Assigns one row of the gate, returning its `l`, `r` and `o` cells. `o` is witnessed
+    /// as `out`.
+    fn assign_row<'a>(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        name: &'static str,
+        lhs: impl Into<Operand<'a, F>>,
+        rhs: impl Into<Operand<'a, F>>,
+        out: Value<F>,
+        selectors: Selectors<F>,
+    ) -> Result<[AssignedCell<F, F>; 3], Error>
+    where
+        F: 'a,
+    {
+        let config = &self.config;
+        let (lhs, rhs) = (lhs.into(), rhs.into());
+        layouter.assign_region(
+            || name,
+            |mut region| {
+                let mut assign = |name: &'static str,
+                                  column: Column<Advice>,
+                                  operand: &Operand<F>| {
+                    match operand {
+                        Operand::Assigned(cell) => {
+                            cell.copy_advice(|| name, &mut region, column, 0)
+                        }
+                        Operand::Witness(value) => {
+                            region.assign_advice(|| name, column, 0, || *value)
+                        }
+                    }
+                };
+                let l = assign("lhs", config.l, &lhs)?;
+                let r = assign("rhs", config.r, &rhs)?;
+                let o = assign("out", config.o, &Operand::Witness(out))?;
+
+                for (column, value) in [
+                    (config.sl, selectors.l),
+                    (config.sr, selectors.r),
+                    (config.sm, selectors.m),
+                    (config.so, selectors.o),
+                    (config.sc, selectors.c),
+                ] {
+                    region.assign_fixed(|| "selector", column, 0, || Value::known(value))?;
+                }
+
+                Ok([l, r, o])
+            },
+        )
+    }
+
+    /// One row computing `o = sl·l + sr·r + sm·l·r + sc`.
+    fn assign_output<'a>(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        name: &'static str,
+        lhs: impl Into<Operand<'a, F>>,
+        rhs: impl Into<Operand<'a, F>>,
+        selectors: Selectors<F>,
+    ) -> Result<AssignedCell<F, F>, Error>
+    where
+        F: 'a,
+    {
+        let (lhs, rhs) = (lhs.into(), rhs.into());
+        let out = lhs
+            .value()
+            .zip(rhs.value())
+            .map(|(l, r)| selectors.l * l + selectors.r * r + selectors.m * l * r + selectors.c);
+        let selectors = Selectors {
+            o: F::ONE,
+            ..selectors
+        };
+        let [_, _, out] = self.assign_row(layouter, name, lhs, rhs, out, selectors)?;
+        Ok(out)
+    }
+}
+
+impl<F: Field> Chip<F> for StandardPlonkChip<F> {
+    type Config = StandardPlonkConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: Field> StandardPlonkInstructions<F> for StandardPlonkChip<F> {
+    fn load_private(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let zero = Operand::Witness(Value::known(F::ZERO));
+        let [l, _, _] = self.assign_row(
+            layouter,
+            "load private",
+            Operand::Witness(value),
+            zero,
+            Value::known(F::ZERO),
+            Selectors::default(),
+        )?;
+        Ok(l)
+    }
+
+    fn load_constant(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let zero = || Operand::Witness(Value::known(F::ZERO));
+        let selectors = Selectors {
+            c: constant,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "load constant", zero(), zero(), selectors)
+    }
+
+    fn add(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let selectors = Selectors {
+            l: F::ONE,
+            r: F::ONE,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "add", a, b, selectors)
+    }
+
+    fn sub(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let selectors = Selectors {
+            l: F::ONE,
+            r: -F::ONE,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "sub", a, b, selectors)
+    }
+
+    fn mul(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let selectors = Selectors {
+            m: F::ONE,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "mul", a, b, selectors)
+    }
+
+    fn add_constant(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let zero = Operand::Witness(Value::known(F::ZERO));
+        let selectors = Selectors {
+            l: F::ONE,
+            c: constant,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "add constant", a, zero, selectors)
+    }
+
+    fn mul_constant(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let zero = Operand::Witness(Value::known(F::ZERO));
+        let selectors = Selectors {
+            l: constant,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "mul constant", a, zero, selectors)
+    }
+
+    fn add_scaled(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let selectors = Selectors {
+            l: F::ONE,
+            r: constant,
+            ..Default::default()
+        };
+        self.assign_output(layouter, "add scaled", a, b, selectors)
+    }
+
+    fn assert_equal(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "assert equal",
+            |mut region| region.constrain_equal(a.cell(), b.cell()),
+        )
+    }
+
+    fn assert_zero(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        let zero = Operand::Witness(Value::known(F::ZERO));
+        let selectors = Selectors {
+            l: F::ONE,
+            ..Default::default()
+        };
+        let out = Value::known(F::ZERO);
+        self.assign_row(layouter, "assert zero", a, zero, out, selectors)?;
+        Ok(())
+    }
+
+    fn assert_bool(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        // a·a - a = 0
+        let selectors = Selectors {
+            l: -F::ONE,
+            m: F::ONE,
+            ..Default::default()
+        };
+        let out = Value::known(F::ZERO);
+        self.assign_row(layouter, "assert bool", a, a, out, selectors)?;
+        Ok(())
+    }
+
+    fn select(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        cond: &AssignedCell<F, F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        // b + cond·(a - b)
+        self.assert_bool(layouter, cond)?;
+        let diff = self.sub(layouter, a, b)?;
+        let diff = self.mul(layouter, cond, &diff)?;
+        self.add(layouter, b, &diff)
+    }
+
+    fn is_zero(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        // With inv = 1/a, or 0 if a = 0, out = 1 - a·inv is a bit and a·out = 0 rules out
+        // out = 1 for a nonzero a.
+        let inv = a.value().map(|a| a.invert().unwrap_or(F::ZERO));
+        let product = self.assign_output(
+            layouter,
+            "is zero",
+            a,
+            Operand::Witness(inv),
+            Selectors {
+                m: F::ONE,
+                ..Default::default()
+            },
+        )?;
+        let zero = Operand::Witness(Value::known(F::ZERO));
+        let out = self.assign_output(
+            layouter,
+            "is zero",
+            &product,
+            zero,
+            Selectors {
+                l: -F::ONE,
+                c: F::ONE,
+                ..Default::default()
+            },
+        )?;
+        let selectors = Selectors {
+            m: F::ONE,
+            ..Default::default()
+        };
+        let zero = Value::known(F::ZERO);
+        self.assign_row(layouter, "is zero", a, &out, zero, selectors)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+
+    use super::*;
+
+    /// Exposes, for private `a` and `b`: a + b, a - b, a·b, a + 5, 3a, a == 0, b == 0,
+    /// (a == 0 ? a : b) and a + 3b, and asserts that b - b is zero and that 2a = a + a.
+    #[derive(Default)]
+    struct TestCircuit {
+        a: Value<Fr>,
+        b: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (StandardPlonkConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let advice = [(); 3].map(|_| meta.advice_column());
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (StandardPlonkChip::configure(meta, advice), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let chip = StandardPlonkChip::construct(config);
+            let layouter = &mut layouter;
+
+            let a = chip.load_private(layouter, self.a)?;
+            let b = chip.load_private(layouter, self.b)?;
+            let five = chip.load_constant(layouter, Fr::from(5))?;
+            let a_is_zero = chip.is_zero(layouter, &a)?;
+            let outputs = [
+                chip.add(layouter, &a, &b)?,
+                chip.sub(layouter, &a, &b)?,
+                chip.mul(layouter, &a, &b)?,
+                chip.add(layouter, &a, &five)?,
+                chip.mul_constant(layouter, &a, Fr::from(3))?,
+                a_is_zero.clone(),
+                chip.is_zero(layouter, &b)?,
+                chip.select(layouter, &a_is_zero, &a, &b)?,
+                chip.add_scaled(layouter, &a, &b, Fr::from(3))?,
+            ];
+
+            let b_minus_b = chip.sub(layouter, &b, &b)?;
+            chip.assert_zero(layouter, &b_minus_b)?;
+            let double = chip.add(layouter, &a, &a)?;
+            let twice = chip.mul_constant(layouter, &a, Fr::from(2))?;
+            chip.assert_equal(layouter, &double, &twice)?;
+            let plus_five = chip.add_constant(layouter, &a, Fr::from(5))?;
+            chip.assert_equal(layouter, &plus_five, &outputs[3])?;
+
+            for (row, cell) in outputs.iter().enumerate() {
+                layouter.constrain_instance(cell.cell(), instance, row)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn expected(a: u64, b: u64) -> Vec<Fr> {
+        let (a, b) = (Fr::from(a), Fr::from(b));
+        let bit = |x: Fr| if x == Fr::ZERO { Fr::ONE } else { Fr::ZERO };
+        vec![
+            a + b,
+            a - b,
+            a * b,
+            a + Fr::from(5),
+            a * Fr::from(3),
+            bit(a),
+            bit(b),
+            if a == Fr::ZERO { a } else { b },
+            a + b * Fr::from(3),
+        ]
+    }
+
+    #[test]
+    fn operations_match_native() {
+        for (a, b) in [(6, 9), (0, 9), (7, 0), (0, 0)] {
+            let circuit = TestCircuit {
+                a: Value::known(Fr::from(a)),
+                b: Value::known(Fr::from(b)),
+            };
+            MockProver::run(6, &circuit, vec![expected(a, b)])
+                .unwrap()
+                .assert_satisfied();
+        }
+    }
+
+    #[test]
+    fn wrong_outputs_fail() {
+        let circuit = TestCircuit {
+            a: Value::known(Fr::from(6)),
+            b: Value::known(Fr::from(0)),
+        };
+        let good = expected(6, 0);
+        for i in 0..good.len() {
+            let mut wrong = good.clone();
+            wrong[i] += Fr::ONE;
+            let prover = MockProver::run(6, &circuit, vec![wrong]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use halo2_proofs::{
+    arithmetic::CurveAffine,
+    halo2curves::{
+        bn256::{Fr, G1Affine},
+        ff::{Field, PrimeField},
+        group::GroupEncoding,
+        Coordinates,
+    },
+    transcript::{
+        Challenge255, EncodedChallenge, Transcript, TranscriptRead, TranscriptReadBuffer,
+        TranscriptWrite, TranscriptWriterBuffer,
+    },
+};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::poseidon::primitives::{
+    generate_constants, Absorbing, Domain, Mds, Spec, Sponge, Squeezing, VariableLength,
+};
+
+/// Hash used to derive the Fiat-Shamir challenges of a proof. A proof only verifies with
+/// the transcript it was created with.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TranscriptHash {
+    #[default]
+    Blake2b = 0,
+    /// Cheap to recompute in the EVM.
+    Keccak256 = 1,
+    /// Cheap to recompute in a circuit, for recursive verification.
+    Poseidon = 2,
+    /// Keccak256 with the challenge derivation of the generated Solidity verifiers. Only
+    /// available with the `evm` feature.
+    Evm = 3,
+}
+
+impl TranscriptHash {
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(TranscriptHash::Blake2b),
+            1 => Some(TranscriptHash::Keccak256),
+            2 => Some(TranscriptHash::Poseidon),
+            3 => Some(TranscriptHash::Evm),
+            _ => None,
+        }
+    }
+}
+
+const WIDTH: usize = 3;
+const RATE: usize = 2;
+
+/// Poseidon specification used by [`PoseidonRead`] and [`PoseidonWrite`]: the $x^5$ S-box
+/// with $R_F = 8, R_P = 56$. Changing the rounds changes every challenge, so proofs made
+/// with one specification do not verify with another.
+#[derive(Debug, Clone, Copy)]
+pub struct TranscriptSpec;
+
+impl Spec<Fr, WIDTH, RATE> for TranscriptSpec {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn sbox(val: Fr) -> Fr {
+        val.pow_vartime([5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+
+    fn constants() -> (Vec<[Fr; WIDTH]>, Mds<Fr, WIDTH>, Mds<Fr, WIDTH>) {
+        generate_constants::<_, Self, WIDTH, RATE>()
+    }
+}
+
+/// Phase of a [`TranscriptSponge`], with the number of elements absorbed since the last
+/// challenge while absorbing.
+enum Phase<S: Spec<Fr, WIDTH, RATE>> {
+    Absorbing(Sponge<Fr, S, Absorbing<Fr, RATE>, WIDTH, RATE>, usize),
+    Squeezing(Sponge<Fr, S, Squeezing<Fr, RATE>, WIDTH, RATE>),
+}
+
+/// Poseidon [`Sponge`] in the [`VariableLength`] domain, alternating between absorbing
+/// proof elements and squeezing challenges.
+///
+/// Before the first challenge after a run of absorbed elements, the run is padded as
+/// [`VariableLength`] input: a one followed by zeroes up to a full block. Different runs
+/// therefore never reach the same state, and every challenge depends on all elements
+/// absorbed before it. Challenges are then squeezed from the rate, permuting when it runs
+/// out, and the next absorbed element starts a new block on the squeezed state. An
+/// in-circuit verifier mirrors this with [`crate::poseidon::Sponge`] over the same
+/// specification and domain, absorbing each run followed by its padding words and
+/// calling `finish_absorbing`, `squeeze` and `finish_squeezing` at the same points.
+struct TranscriptSponge<S: Spec<Fr, WIDTH, RATE>> {
+    // Only `None` while switching phases.
+    phase: Option<Phase<S>>,
+}
+
+impl<S: Spec<Fr, WIDTH, RATE>> fmt::Debug for TranscriptSponge<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TranscriptSponge").finish_non_exhaustive()
+    }
+}
+
+impl<S: Spec<Fr, WIDTH, RATE>> TranscriptSponge<S> {
+    fn new() -> Self {
+        let sponge = Sponge::new(<VariableLength as Domain<Fr, RATE>>::initial_capacity_element());
+        TranscriptSponge {
+            phase: Some(Phase::Absorbing(sponge, 0)),
+        }
+    }
+
+    fn absorb(&mut self, value: Fr) {
+        let (mut sponge, absorbed) = match self.phase.take().expect("sponge has a phase") {
+            Phase::Absorbing(sponge, absorbed) => (sponge, absorbed),
+            Phase::Squeezing(sponge) => (sponge.finish_squeezing(), 0),
+        };
+        sponge.absorb(value);
+        self.phase = Some(Phase::Absorbing(sponge, absorbed + 1));
+    }
+
+    fn squeeze(&mut self) -> Fr {
+        let mut sponge = match self.phase.take().expect("sponge has a phase") {
+            Phase::Absorbing(mut sponge, absorbed) => {
+                for word in <VariableLength as Domain<Fr, RATE>>::padding(absorbed) {
+                    sponge.absorb(word);
+                }
+                sponge.finish_absorbing()
+            }
+            Phase::Squeezing(sponge) => sponge,
+        };
+        let value = sponge.squeeze();
+        self.phase = Some(Phase::Squeezing(sponge));
+        value
+    }
+
+    fn absorb_point(&mut self, point: &G1Affine) {
+        // Coordinates live in the base field, which is larger than Fr, so each one is
+        // absorbed as two 128-bit limbs. The identity is absorbed as (0, 0).
+        let (x, y) = Option::<Coordinates<G1Affine>>::from(point.coordinates())
+            .map(|c| (*c.x(), *c.y()))
+            .unwrap_or_default();
+        for coordinate in [x, y] {
+            let repr = coordinate.to_repr();
+            for limb in repr.as_ref().chunks(16) {
+                let mut bytes = [0u8; 16];
+                bytes[..limb.len()].copy_from_slice(limb);
+                self.absorb(Fr::from_u128(u128::from_le_bytes(bytes)));
+            }
+        }
+    }
+
+    fn squeeze_challenge(&mut self) -> Challenge255<G1Affine> {
+        // The squeezed element is already canonical, so reducing it as 64 uniform bytes
+        // yields the same element.
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(self.squeeze().to_repr().as_ref());
+        Challenge255::<G1Affine>::new(&bytes)
+    }
+}
+
+/// Transcript reader using the Poseidon hash.
+#[derive(Debug)]
+pub struct PoseidonRead<R: Read, S: Spec<Fr, WIDTH, RATE> = TranscriptSpec> {
+    sponge: TranscriptSponge<S>,
+    reader: R,
+}
+
+impl<R: Read, S: Spec<Fr, WIDTH, RATE>> TranscriptReadBuffer<R, G1Affine, Challenge255<G1Affine>>
+    for PoseidonRead<R, S>
+{
+    fn init(reader: R) -> Self {
+        PoseidonRead {
+            sponge: TranscriptSponge::new(),
+            reader,
+        }
+    }
+}
+
+impl<R: Read, S: Spec<Fr, WIDTH, RATE>> Transcript<G1Affine, Challenge255<G1Affine>>
+    for PoseidonRead<R, S>
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<G1Affine> {
+        self.sponge.squeeze_challenge()
+    }
+
+    fn common_point(&mut self, point: G1Affine) -> io::Result<()> {
+        self.sponge.absorb_point(&point);
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: Fr) -> io::Result<()> {
+        self.sponge.absorb(scalar);
+        Ok(())
+    }
+}
+
+impl<R: Read, S: Spec<Fr, WIDTH, RATE>> TranscriptRead<G1Affine, Challenge255<G1Affine>>
+    for PoseidonRead<R, S>
+{
+    fn read_point(&mut self) -> io::Result<G1Affine> {
+        let mut compressed = <G1Affine as GroupEncoding>::Repr::default();
+        self.reader.read_exact(compressed.as_mut())?;
+        let point: G1Affine = Option::from(G1Affine::from_bytes(&compressed)).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "invalid point encoding in proof")
+        })?;
+        self.common_point(point)?;
+
+        Ok(point)
+    }
+
+    fn read_scalar(&mut self) -> io::Result<Fr> {
+        let mut data = <Fr as PrimeField>::Repr::default();
+        self.reader.read_exact(data.as_mut())?;
+        let scalar: Fr = Option::from(Fr::from_repr(data)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "invalid field element encoding in proof",
+            )
+        })?;
+        self.common_scalar(scalar)?;
+
+        Ok(scalar)
+    }
+}
+
+/// Transcript writer using the Poseidon hash.
+#[derive(Debug)]
+pub struct PoseidonWrite<W: Write, S: Spec<Fr, WIDTH, RATE> = TranscriptSpec> {
+    sponge: TranscriptSponge<S>,
+    writer: W,
+}
+
+impl<W: Write, S: Spec<Fr, WIDTH, RATE>> TranscriptWriterBuffer<W, G1Affine, Challenge255<G1Affine>>
+    for PoseidonWrite<W, S>
+{
+    fn init(writer: W) -> Self {
+        PoseidonWrite {
+            sponge: TranscriptSponge::new(),
+            writer,
+        }
+    }
+
+    fn finalize(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, S: Spec<Fr, WIDTH, RATE>> Transcript<G1Affine, Challenge255<G1Affine>>
+    for PoseidonWrite<W, S>
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<G1Affine> {
+        self.sponge.squeeze_challenge()
+    }
+
+    fn common_point(&mut self, point: G1Affine) -> io::Result<()> {
+        self.sponge.absorb_point(&point);
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: Fr) -> io::Result<()> {
+        self.sponge.absorb(scalar);
+        Ok(())
+    }
+}
+
+impl<W: Write, S: Spec<Fr, WIDTH, RATE>> TranscriptWrite<G1Affine, Challenge255<G1Affine>>
+    for PoseidonWrite<W, S>
+{
+    fn write_point(&mut self, point: G1Affine) -> io::Result<()> {
+        self.common_point(point)?;
+        let compressed = point.to_bytes();
+        self.writer.write_all(compressed.as_ref())
+    }
+
+    fn write_scalar(&mut self, scalar: Fr) -> io::Result<()> {
+        self.common_scalar(scalar)?;
+        let data = scalar.to_repr();
+        self.writer.write_all(data.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_proofs::{
+        halo2curves::{
+            bn256::{Fr, G1Affine},
+            ff::Field,
+            group::{prime::PrimeCurveAffine, Curve},
+        },
+        transcript::{
+            Challenge255, EncodedChallenge, Transcript, TranscriptRead, TranscriptReadBuffer,
+            TranscriptWrite, TranscriptWriterBuffer,
+        },
+    };
+    use rand_core::OsRng;
+
+    use super::{PoseidonRead, PoseidonWrite, TranscriptHash};
+    use crate::{
+        arithmetic_circuit::{create_circuit, empty_circuit},
+        utils::{generate_keys, generate_params, prove, verify, MultiOpenScheme},
+    };
+
+    #[test]
+    fn poseidon_transcript_round_trip() {
+        let point = (G1Affine::generator() * Fr::random(OsRng)).to_affine();
+        let scalar = Fr::random(OsRng);
+
+        let mut writer = PoseidonWrite::<Vec<u8>>::init(vec![]);
+        writer.write_point(point).unwrap();
+        writer.write_point(G1Affine::identity()).unwrap();
+        let c0: Challenge255<G1Affine> = writer.squeeze_challenge();
+        writer.write_scalar(scalar).unwrap();
+        let c1: Challenge255<G1Affine> = writer.squeeze_challenge();
+        let c2: Challenge255<G1Affine> = writer.squeeze_challenge();
+        let proof = writer.finalize();
+
+        let mut reader = PoseidonRead::<&[u8]>::init(&proof[..]);
+        assert_eq!(reader.read_point().unwrap(), point);
+        assert_eq!(reader.read_point().unwrap(), G1Affine::identity());
+        let r0: Challenge255<G1Affine> = reader.squeeze_challenge();
+        assert_eq!(reader.read_scalar().unwrap(), scalar);
+        let r1: Challenge255<G1Affine> = reader.squeeze_challenge();
+        let r2: Challenge255<G1Affine> = reader.squeeze_challenge();
+
+        assert_eq!(c0.get_scalar(), r0.get_scalar());
+        assert_eq!(c1.get_scalar(), r1.get_scalar());
+        assert_eq!(c2.get_scalar(), r2.get_scalar());
+        assert_ne!(c1.get_scalar(), c2.get_scalar());
+    }
+
+    #[test]
+    fn prove_and_verify_with_each_transcript() {
+        let params = generate_params(4);
+        let (pk, vk) = generate_keys(&params, &empty_circuit(Fr::from(7)));
+        let public_input = [Fr::from(7), Fr::from(36 * 81 + 7)];
+        let transcripts = [
+            TranscriptHash::Blake2b,
+            TranscriptHash::Keccak256,
+            TranscriptHash::Poseidon,
+            TranscriptHash::Evm,
+        ];
+
+        for scheme in [MultiOpenScheme::Gwc, MultiOpenScheme::Shplonk] {
+            let available = transcripts
+                .into_iter()
+                .filter(|t| cfg!(feature = "evm") || *t != TranscriptHash::Evm);
+            for transcript in available {
+                let circuit = create_circuit(Fr::from(6), Fr::from(9), Fr::from(7));
+                let proof =
+                    prove(&params, &pk, circuit, &[&public_input], scheme, transcript).unwrap();
+                assert!(verify(&params, &vk, &proof, &[&public_input], scheme, transcript).is_ok());
+
+                for other in transcripts.into_iter().filter(|t| *t != transcript) {
+                    assert!(verify(&params, &vk, &proof, &[&public_input], scheme, other).is_err());
+                }
+            }
+        }
+    }
+}
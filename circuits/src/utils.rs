@@ -1,3 +1,4 @@
+use base64::{engine::general_purpose, Engine as _};
 use halo2_proofs::halo2curves::ff::PrimeField;
 use halo2_proofs::{arithmetic::Field, dev::CircuitLayout};
 use halo2_proofs::{
@@ -9,18 +10,28 @@ use halo2_proofs::{
     poly::{
         commitment::ParamsProver,
         kzg::{
-            commitment::{KZGCommitmentScheme, ParamsKZG},
+            commitment::{KZGCommitmentScheme, ParamsKZG, ParamsVerifierKZG},
             multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
             strategy::SingleStrategy,
         },
     },
     transcript::{
-        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        Blake2bRead, Blake2bWrite, Challenge255, EncodedChallenge, Keccak256Read, Keccak256Write,
+        TranscriptReadBuffer, TranscriptWriterBuffer,
     },
 };
-use num::{BigInt, Num};
+#[cfg(feature = "evm")]
+use halo2_solidity_verifier::Keccak256Transcript;
+use num::{BigUint, Num};
 use plotters::prelude::*;
 use rand_core::OsRng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    error::ProverError,
+    transcript::{PoseidonRead, PoseidonWrite, TranscriptHash},
+};
 
 #[cfg(not(target_family = "wasm"))]
 pub fn draw_graph<F: Field>(k: u32, name: &str, circuit: &impl Circuit<F>, rows: Option<usize>) {
@@ -69,97 +80,390 @@ pub fn generate_keys(
     (pk, vk)
 }
 
-pub fn generate_proof(
+/// Multi-open argument used to batch the KZG openings of a proof. A proof only verifies
+/// under the scheme it was created with.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MultiOpenScheme {
+    Gwc = 0,
+    #[default]
+    Shplonk = 1,
+}
+
+impl MultiOpenScheme {
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(MultiOpenScheme::Gwc),
+            1 => Some(MultiOpenScheme::Shplonk),
+            _ => None,
+        }
+    }
+}
+
+/// The EVM transcript comes from `halo2_solidity_verifier`, behind the `evm` feature.
+#[cfg(not(feature = "evm"))]
+const EVM_DISABLED: &str = "the EVM transcript needs the `evm` feature";
+
+/// Creates a proof for `circuit`, with one entry of `instances` per instance column.
+pub fn prove(
     params: &ParamsKZG<Bn256>,
     pk: &ProvingKey<G1Affine>,
     circuit: impl Circuit<Fr>,
-) -> Vec<u8> {
-    println!("Generating proof...");
-
-    let mut transcript: Blake2bWrite<Vec<u8>, _, Challenge255<_>> =
-        Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-
-    let instances: &[&[Fr]] = &[&[]];
-    create_proof::<
-        KZGCommitmentScheme<Bn256>,
-        ProverSHPLONK<'_, Bn256>,
-        Challenge255<_>,
-        _,
-        Blake2bWrite<Vec<u8>, G1Affine, _>,
-        _,
-    >(params, pk, &[circuit], &[&[]], OsRng, &mut transcript)
-    .expect("Prover should not fail");
-    transcript.finalize()
-}
-
-pub fn generate_proof_with_instance(
+    instances: &[&[Fr]],
+    scheme: MultiOpenScheme,
+    transcript: TranscriptHash,
+) -> Result<Vec<u8>, ProverError> {
+    match transcript {
+        TranscriptHash::Blake2b => prove_with::<_, Blake2bWrite<_, _, Challenge255<_>>>(
+            params, pk, circuit, instances, scheme,
+        ),
+        TranscriptHash::Keccak256 => prove_with::<_, Keccak256Write<_, _, Challenge255<_>>>(
+            params, pk, circuit, instances, scheme,
+        ),
+        TranscriptHash::Poseidon => {
+            prove_with::<_, PoseidonWrite<_>>(params, pk, circuit, instances, scheme)
+        }
+        #[cfg(feature = "evm")]
+        TranscriptHash::Evm => {
+            prove_with::<_, Keccak256Transcript<_, _>>(params, pk, circuit, instances, scheme)
+        }
+        #[cfg(not(feature = "evm"))]
+        TranscriptHash::Evm => Err(ProverError::ProofFailed(EVM_DISABLED.to_string())),
+    }
+}
+
+fn prove_with<E, T>(
     params: &ParamsKZG<Bn256>,
     pk: &ProvingKey<G1Affine>,
     circuit: impl Circuit<Fr>,
-    public_input: &[Fr],
-) -> Vec<u8> {
-    println!("Generating proof...");
-    println!("Public input: {:?}", public_input);
-    let mut transcript: Blake2bWrite<Vec<u8>, G1Affine, Challenge255<G1Affine>> =
-        Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
-    create_proof::<
-        KZGCommitmentScheme<Bn256>,
-        ProverGWC<'_, Bn256>,
-        Challenge255<G1Affine>,
-        _,
-        Blake2bWrite<Vec<u8>, G1Affine, Challenge255<_>>,
-        _,
-    >(
-        &params,
-        &pk,
-        &[circuit],
-        &[&[&public_input]],
-        OsRng,
-        &mut transcript,
-    )
-    .expect("proof generation should not fail");
-    transcript.finalize()
-    // Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..])
+    instances: &[&[Fr]],
+    scheme: MultiOpenScheme,
+) -> Result<Vec<u8>, ProverError>
+where
+    E: EncodedChallenge<G1Affine>,
+    T: TranscriptWriterBuffer<Vec<u8>, G1Affine, E>,
+{
+    let mut transcript = T::init(vec![]);
+    match scheme {
+        MultiOpenScheme::Gwc => {
+            create_proof::<KZGCommitmentScheme<Bn256>, ProverGWC<'_, Bn256>, E, _, T, _>(
+                params,
+                pk,
+                &[circuit],
+                &[instances],
+                OsRng,
+                &mut transcript,
+            )
+        }
+        MultiOpenScheme::Shplonk => {
+            create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, E, _, T, _>(
+                params,
+                pk,
+                &[circuit],
+                &[instances],
+                OsRng,
+                &mut transcript,
+            )
+        }
+    }
+    .map_err(|e| ProverError::ProofFailed(e.to_string()))?;
+    Ok(transcript.finalize())
 }
 
+/// Verifies a proof created by [`prove`] with the same `instances`, `scheme` and
+/// `transcript`.
 pub fn verify(
-    params: &ParamsKZG<Bn256>,
-    pk: &ProvingKey<G1Affine>,
-    proof: &Vec<u8>,
+    params: &ParamsVerifierKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &[u8],
+    instances: &[&[Fr]],
+    scheme: MultiOpenScheme,
+    transcript: TranscriptHash,
 ) -> Result<(), Error> {
-    println!("Verifying proof...");
-    let strategy = SingleStrategy::new(&params);
-    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-    verify_proof::<
-        KZGCommitmentScheme<Bn256>,
-        VerifierSHPLONK<'_, Bn256>,
-        Challenge255<G1Affine>,
-        Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
-        SingleStrategy<'_, Bn256>,
-    >(params, pk.get_vk(), strategy, &[&[]], &mut transcript)
-}
-pub fn verify_with_instance(
-    params: &ParamsKZG<Bn256>,
-    pk: &ProvingKey<G1Affine>,
-    proof: &Vec<u8>,
-    public_input: &[Fr],
-) -> Result<(), Error> {
-    println!("Verifying proof...");
-    let transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-    let strategy = SingleStrategy::new(&params);
-    Ok(verify_proof::<_, VerifierGWC<Bn256>, _, _, _>(
-        &params,
-        pk.get_vk(),
-        strategy.clone(),
-        &[&[&public_input]],
-        &mut transcript.clone(),
-    )
-    .unwrap())
-}
-
-pub fn hex_to_fr(mut n: &str) -> Fr {
+    match transcript {
+        TranscriptHash::Blake2b => verify_with::<_, Blake2bRead<_, _, Challenge255<_>>>(
+            params, vk, proof, instances, scheme,
+        ),
+        TranscriptHash::Keccak256 => verify_with::<_, Keccak256Read<_, _, Challenge255<_>>>(
+            params, vk, proof, instances, scheme,
+        ),
+        TranscriptHash::Poseidon => {
+            verify_with::<_, PoseidonRead<_>>(params, vk, proof, instances, scheme)
+        }
+        #[cfg(feature = "evm")]
+        TranscriptHash::Evm => {
+            verify_with::<_, Keccak256Transcript<_, _>>(params, vk, proof, instances, scheme)
+        }
+        #[cfg(not(feature = "evm"))]
+        TranscriptHash::Evm => Err(Error::Transcript(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            EVM_DISABLED,
+        ))),
+    }
+}
+
+fn verify_with<'a, E, T>(
+    params: &ParamsVerifierKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &'a [u8],
+    instances: &[&[Fr]],
+    scheme: MultiOpenScheme,
+) -> Result<(), Error>
+where
+    E: EncodedChallenge<G1Affine>,
+    T: TranscriptReadBuffer<&'a [u8], G1Affine, E>,
+{
+    let strategy = SingleStrategy::new(params);
+    let mut transcript = T::init(proof);
+    match scheme {
+        MultiOpenScheme::Gwc => verify_proof::<
+            KZGCommitmentScheme<Bn256>,
+            VerifierGWC<'_, Bn256>,
+            E,
+            T,
+            SingleStrategy<'_, Bn256>,
+        >(params, vk, strategy, &[instances], &mut transcript),
+        MultiOpenScheme::Shplonk => verify_proof::<
+            KZGCommitmentScheme<Bn256>,
+            VerifierSHPLONK<'_, Bn256>,
+            E,
+            T,
+            SingleStrategy<'_, Bn256>,
+        >(params, vk, strategy, &[instances], &mut transcript),
+    }
+}
+
+pub fn hex_to_fr(mut n: &str) -> Result<Fr, ProverError> {
     n = n.trim_start_matches("0x");
-    let res: Fr =
-        PrimeField::from_str_vartime(&BigInt::from_str_radix(n, 16).unwrap().to_string()).unwrap();
-    res
+    let invalid = || ProverError::InvalidInput(format!("{} is not a field element", n));
+    let n = BigUint::from_str_radix(n, 16).map_err(|_| invalid())?;
+    biguint_to_fr(&n).ok_or_else(invalid)
+}
+
+/// Parses a field element written in hex with a `0x` prefix, in decimal, or as the base64
+/// encoding of its big-endian bytes. A string made only of digits is always read as decimal.
+pub fn str_to_fr(s: &str) -> Result<Fr, ProverError> {
+    if s.starts_with("0x") {
+        return hex_to_fr(s);
+    }
+    let invalid = || ProverError::InvalidInput(format!("{} is not a field element", s));
+    if s.is_empty() {
+        return Err(invalid());
+    }
+    let n = if s.bytes().all(|b| b.is_ascii_digit()) {
+        BigUint::from_str_radix(s, 10).map_err(|_| invalid())?
+    } else {
+        let bytes = general_purpose::STANDARD.decode(s).map_err(|_| invalid())?;
+        BigUint::from_bytes_be(&bytes)
+    };
+    biguint_to_fr(&n).ok_or_else(invalid)
+}
+
+/// The field element `n`, or `None` when `n` is not below the modulus.
+fn biguint_to_fr(n: &BigUint) -> Option<Fr> {
+    let bytes = n.to_bytes_le();
+    if bytes.len() > 32 {
+        return None;
+    }
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    repr.as_mut()[..bytes.len()].copy_from_slice(&bytes);
+    Fr::from_repr(repr).into()
+}
+
+/// Decimal representation of `f`, as read back by [`str_to_fr`].
+pub fn fr_to_decimal(f: &Fr) -> String {
+    BigUint::from_bytes_le(f.to_repr().as_ref()).to_string()
+}
+
+/// How field elements are written in JSON outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Decimal,
+    /// Big-endian hex with a `0x` prefix.
+    Hex,
+    /// Standard base64 of the 32 big-endian bytes.
+    Base64,
+}
+
+impl Encoding {
+    /// Writes `f` so that [`str_to_fr`] reads it back.
+    pub fn encode(self, f: &Fr) -> String {
+        match self {
+            Encoding::Decimal => fr_to_decimal(f),
+            Encoding::Hex => format!("{:?}", f),
+            Encoding::Base64 => {
+                let mut bytes = f.to_repr();
+                bytes.as_mut().reverse();
+                general_purpose::STANDARD.encode(bytes)
+            }
+        }
+    }
+}
+
+/// Reads the optional `encoding` field of a JSON input, decimal by default.
+pub fn parse_encoding(s: &str) -> Result<Encoding, ProverError> {
+    #[derive(Deserialize)]
+    struct WithEncoding {
+        #[serde(default)]
+        encoding: Encoding,
+    }
+    Ok(serde_json::from_str::<WithEncoding>(s)?.encoding)
+}
+
+/// A field element in JSON input: either a number or a string read by [`str_to_fr`]. It is
+/// serialized as a decimal string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FieldElement(pub Fr);
+
+impl From<u64> for FieldElement {
+    fn from(n: u64) -> Self {
+        FieldElement(Fr::from(n))
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Element {
+            Int(u64),
+            Str(String),
+        }
+        match Element::deserialize(deserializer)? {
+            Element::Int(n) => Ok(n.into()),
+            Element::Str(s) => str_to_fr(&s).map(FieldElement).map_err(de::Error::custom),
+        }
+    }
+}
+
+impl Serialize for FieldElement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&fr_to_decimal(&self.0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        fr_to_decimal, generate_keys, generate_params, hex_to_fr, parse_encoding, prove, str_to_fr,
+        verify, Encoding, FieldElement, MultiOpenScheme,
+    };
+    use crate::{
+        arithmetic_circuit::{create_circuit, empty_circuit},
+        transcript::TranscriptHash,
+    };
+    use halo2_proofs::halo2curves::{bn256::Fr, ff::Field};
+
+    #[test]
+    fn schemes_must_match() {
+        let params = generate_params(4);
+        let (pk, vk) = generate_keys(&params, &empty_circuit(Fr::from(7)));
+        let public_input = [Fr::from(7), Fr::from(36 * 81 + 7)];
+
+        for scheme in [MultiOpenScheme::Gwc, MultiOpenScheme::Shplonk] {
+            let proof = prove(
+                &params,
+                &pk,
+                create_circuit(Fr::from(6), Fr::from(9), Fr::from(7)),
+                &[&public_input],
+                scheme,
+                TranscriptHash::default(),
+            )
+            .unwrap();
+            assert!(verify(
+                &params,
+                &vk,
+                &proof,
+                &[&public_input],
+                scheme,
+                TranscriptHash::default()
+            )
+            .is_ok());
+
+            let other = match scheme {
+                MultiOpenScheme::Gwc => MultiOpenScheme::Shplonk,
+                MultiOpenScheme::Shplonk => MultiOpenScheme::Gwc,
+            };
+            assert!(verify(
+                &params,
+                &vk,
+                &proof,
+                &[&public_input],
+                other,
+                TranscriptHash::default()
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn hex_to_fr_rejects_invalid_input() {
+        assert_eq!(hex_to_fr("0x1a"), Ok(Fr::from(26)));
+        assert!(hex_to_fr("0xzz").is_err());
+        // The modulus itself is not a canonical field element.
+        assert!(
+            hex_to_fr("0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001")
+                .is_err()
+        );
+        assert!(hex_to_fr(&format!("0x1{}", "0".repeat(64))).is_err());
+        assert!(hex_to_fr("0x-1").is_err());
+        assert_eq!(hex_to_fr(&format!("{:?}", -Fr::ONE)), Ok(-Fr::ONE));
+    }
+
+    #[test]
+    fn field_elements_as_strings() {
+        assert_eq!(str_to_fr("0x1a"), Ok(Fr::from(26)));
+        assert_eq!(str_to_fr("26"), Ok(Fr::from(26)));
+        assert_eq!(fr_to_decimal(&Fr::from(26)), "26");
+        let minus_one = -Fr::ONE;
+        assert_eq!(str_to_fr(&fr_to_decimal(&minus_one)), Ok(minus_one));
+        assert!(str_to_fr("1a").is_err());
+        assert!(str_to_fr("-1").is_err());
+        assert!(str_to_fr("").is_err());
+        assert!(str_to_fr(
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let minus_one = -Fr::ONE;
+        for encoding in [Encoding::Decimal, Encoding::Hex, Encoding::Base64] {
+            assert_eq!(str_to_fr(&encoding.encode(&minus_one)), Ok(minus_one));
+        }
+        assert_eq!(
+            Encoding::Hex.encode(&Fr::from(26)),
+            format!("0x{:064x}", 26)
+        );
+        // 32 big-endian bytes ending in 0x1a.
+        assert_eq!(
+            Encoding::Base64.encode(&Fr::from(26)),
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABo="
+        );
+        assert_eq!(str_to_fr("Gg=="), Ok(Fr::from(26)));
+        assert!(str_to_fr("Gg").is_err());
+
+        assert_eq!(parse_encoding("{}"), Ok(Encoding::Decimal));
+        assert_eq!(
+            parse_encoding(r#"{"encoding": "base64"}"#),
+            Ok(Encoding::Base64)
+        );
+        assert!(parse_encoding(r#"{"encoding": "octal"}"#).is_err());
+    }
+
+    #[test]
+    fn field_elements_from_json() {
+        let elements: Vec<FieldElement> =
+            serde_json::from_str(r#"[26, "26", "0x1a", "Gg=="]"#).unwrap();
+        assert!(elements.iter().all(|e| *e == FieldElement::from(26)));
+        assert!(serde_json::from_str::<FieldElement>("-1").is_err());
+        assert!(serde_json::from_str::<FieldElement>(r#""-1""#).is_err());
+        assert_eq!(
+            serde_json::to_string(&FieldElement(-Fr::ONE)).unwrap(),
+            format!("\"{}\"", fr_to_decimal(&-Fr::ONE))
+        );
+    }
 }
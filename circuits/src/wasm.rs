@@ -1,36 +1,23 @@
 use crate::{
-    arithmetic_circuit::{self, parse_string, ArithmeticInput},
-    collatz,
-    poseidon::{
-        self,
-        primitives::{ConstantLength, Spec},
-    },
-    poseidon_circuit::{
-        self, wasm_poseidon_solution, PoseidonInput, PoseidonSpec, WASM_POSEIDON_L,
-        WASM_POSEIDON_RATE, WASM_POSEIDON_WIDTH,
-    },
-    utils::{
-        generate_keys, generate_params, generate_proof, generate_proof_with_instance, hex_to_fr,
-        verify, verify_with_instance,
-    },
+    bundle::{self, ProofBundle},
+    circom::{self, R1cs},
+    error::ProverError,
+    keys::{KeyCache, KEY_FORMAT},
+    merkle_circuit,
+    registry::{self, find_circuit},
+    transcript::TranscriptHash,
+    utils::{fr_to_decimal, generate_params, FieldElement, MultiOpenScheme},
 };
 use halo2_proofs::{
-    circuit::Value,
-    halo2curves::{
-        bn256::{Bn256, Fr, G1Affine},
-        ff::PrimeField,
+    halo2curves::bn256::{Bn256, Fr},
+    poly::{
+        commitment::Params,
+        kzg::commitment::{ParamsKZG, ParamsVerifierKZG},
     },
-    plonk::{keygen_pk, keygen_vk, Circuit, ProvingKey, VerifyingKey},
-    poly::{commitment::Params, kzg::commitment::ParamsKZG},
 };
 use js_sys::Uint8Array;
-use std::{
-    cmp::min,
-    io::{empty, BufReader},
-    panic,
-};
+use std::{io::BufReader, panic, sync::Arc};
 
-// Use a struct that impl's these functions and has a `getCurrentCircuit` function
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -46,139 +33,244 @@ fn to_uint8_array(a: Vec<u8>) -> Uint8Array {
 }
 
 #[wasm_bindgen]
-pub fn setup(k: u32) -> Uint8Array {
+pub fn setup(k: u32) -> Result<Uint8Array, JsValue> {
     let params = generate_params(k);
     let mut buf = vec![];
-    params.write(&mut buf).expect("Should write params");
+    params
+        .write(&mut buf)
+        .map_err(|e| ProverError::InvalidParams(e.to_string()))?;
 
-    to_uint8_array(buf)
+    Ok(to_uint8_array(buf))
 }
 
-pub fn wasm_generate_keys(
-    params: &ParamsKZG<Bn256>,
-    circuit: impl Circuit<Fr>,
-) -> (ProvingKey<G1Affine>, VerifyingKey<G1Affine>) {
-    panic::set_hook(Box::new(console_error_panic_hook::hook));
-    let vk = keygen_vk(params, &circuit).expect("vk should not fail");
-    let pk = keygen_pk(params, vk.clone(), &circuit).expect("keygen_pk should not fail");
-    (pk, vk)
+#[wasm_bindgen]
+pub fn wasm_simulate_circuit(s: &str, circuit: usize) -> Result<String, JsValue> {
+    Ok(find_circuit(circuit)?.simulate_json(s)?)
 }
 
+/// `k` of the params to pass to [`setup`] for proving `s`.
 #[wasm_bindgen]
-pub fn wasm_simulate_circuit(s: &str, circuit: i32) -> String {
-    match circuit {
-        0 => collatz::simulate_circuit(),
-        1 => arithmetic_circuit::simulate_circuit(s),
-        _ => poseidon_circuit::simulate_circuit(s),
-    }
+pub fn wasm_circuit_k(s: &str, circuit: usize) -> Result<u32, JsValue> {
+    Ok(find_circuit(circuit)?.k_json(s)?)
 }
 
+/// Input of the Merkle circuit proving that leaf `index` of the tree of depth `depth`
+/// holding `leaves`, a JSON list of field elements, is a member.
 #[wasm_bindgen]
-pub fn wasm_generate_proof(_params: &[u8], s: &str, circuit: i32) -> Uint8Array {
+pub fn wasm_merkle_input(
+    leaves: &str,
+    depth: usize,
+    index: usize,
+    public_leaf: bool,
+) -> Result<String, JsValue> {
+    Ok(merkle_circuit::merkle_input_json(
+        leaves,
+        depth,
+        index,
+        public_leaf,
+    )?)
+}
+
+/// `k` of the params to pass to [`setup`] for proving the circom circuit `r1cs`.
+#[wasm_bindgen]
+pub fn wasm_circom_k(r1cs: &[u8]) -> Result<u32, JsValue> {
+    Ok(circom::circom_k(&R1cs::read(r1cs)?))
+}
+
+/// Proves the circom circuit `r1cs`, a `.r1cs` file, with the witness `wtns`, a `.wtns`
+/// file.
+#[wasm_bindgen]
+pub fn wasm_circom_prove(
+    _params: &[u8],
+    r1cs: &[u8],
+    wtns: &[u8],
+    scheme: MultiOpenScheme,
+    transcript: TranscriptHash,
+) -> Result<Uint8Array, JsValue> {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
-    let params = ParamsKZG::<Bn256>::read(&mut BufReader::new(_params))
-        .expect("should be able to read params");
-
-    let proof = match circuit {
-        0 => {
-            let circuit = crate::collatz::create_circuit_from_string(s);
-            let empty_circuit = collatz::empty_circuit();
-            let (pk, vk) = wasm_generate_keys(&params, empty_circuit);
-            generate_proof(&params, &pk, circuit)
-        }
-        1 => {
-            let public_inputs: ArithmeticInput = parse_string(s);
-            let circuit = crate::arithmetic_circuit::create_circuit_from_string(s);
-            let empty_circuit = arithmetic_circuit::empty_circuit(public_inputs.constant);
-            let public_inputs =
-                [public_inputs.constant, public_inputs.z.unwrap()].map(|k| Fr::from(k));
-            let (pk, vk) = wasm_generate_keys(&params, empty_circuit);
-            generate_proof_with_instance(&params, &pk, circuit, &public_inputs)
-        }
-        _ => {
-            log(&format!("raw public inputs - {:?}", s));
-            let public_inputs: PoseidonInput = poseidon_circuit::parse_string(s);
-            log(&format!("public inputs - {:?}", public_inputs.output));
-            let circuit = poseidon_circuit::create_circuit_from_string::<
-                PoseidonSpec<WASM_POSEIDON_WIDTH, WASM_POSEIDON_RATE>,
-                WASM_POSEIDON_WIDTH,
-                WASM_POSEIDON_RATE,
-                WASM_POSEIDON_L,
-            >(s);
-            let empty_circuit = poseidon_circuit::empty_circuit::<
-                PoseidonSpec<WASM_POSEIDON_WIDTH, WASM_POSEIDON_RATE>,
-                WASM_POSEIDON_WIDTH,
-                WASM_POSEIDON_RATE,
-                WASM_POSEIDON_L,
-            >();
-            let (pk, _vk) = generate_keys(&params, &empty_circuit);
-
-            let public_inputs: [Fr; 1] = [public_inputs.output.unwrap()].map(|k| hex_to_fr(k));
-            generate_proof_with_instance(&params, &pk, circuit, &public_inputs)
-        }
-    };
+    let params = read_params(_params)?;
 
-    to_uint8_array(proof)
+    let r1cs = Arc::new(R1cs::read(r1cs)?);
+    let witness = circom::read_witness(wtns)?;
+    let proof = circom::prove_circom(&params, r1cs, &witness, scheme, transcript)?;
+    Ok(to_uint8_array(proof))
 }
 
+/// Public signals of the witness `wtns` of `r1cs`, as a JSON list of decimal strings like
+/// snarkjs's `public.json`.
 #[wasm_bindgen]
-pub fn wasm_verify_proof(_params: &[u8], proof: &[u8], s: &str, circuit: i32) -> bool {
-    let params = ParamsKZG::<Bn256>::read(&mut BufReader::new(_params))
-        .expect("should be able to read params");
-    let res = match circuit {
-        0 => {
-            log("Collatz");
-            let empty_circuit = collatz::empty_circuit();
-            let (pk, _vk) = generate_keys(&params, &empty_circuit);
-            verify(&params, &pk, &proof.to_vec())
-        }
-        1 => {
-            log("Arithmetic Circuit");
-            let public_inputs: ArithmeticInput = arithmetic_circuit::parse_string(s);
-            let empty_circuit = arithmetic_circuit::empty_circuit(public_inputs.constant);
-            let (pk, _vk) = generate_keys(&params, &empty_circuit);
-            let public_inputs =
-                [public_inputs.constant, public_inputs.z.unwrap()].map(|k| Fr::from(k));
-            verify_with_instance(&params, &pk, &proof.to_vec(), &public_inputs)
-        }
-        _ => {
-            log("Poseidon Circuit");
-            let public_inputs: PoseidonInput = poseidon_circuit::parse_string(s);
-            let empty_circuit = poseidon_circuit::empty_circuit::<
-                PoseidonSpec<WASM_POSEIDON_WIDTH, WASM_POSEIDON_RATE>,
-                WASM_POSEIDON_WIDTH,
-                WASM_POSEIDON_RATE,
-                WASM_POSEIDON_L,
-            >();
-            let (pk, _vk) = generate_keys(&params, &empty_circuit);
-            let message: [Fr; WASM_POSEIDON_L] = public_inputs
-                .x
-                .iter()
-                .map(|k| Fr::from(*k))
-                .collect::<Vec<_>>()
-                .try_into()
-                .unwrap();
-
-            let output = wasm_poseidon_solution::<
-                PoseidonSpec<WASM_POSEIDON_WIDTH, WASM_POSEIDON_RATE>,
-                WASM_POSEIDON_WIDTH,
-                WASM_POSEIDON_RATE,
-                WASM_POSEIDON_L,
-            >(message);
-            verify_with_instance(&params, &pk, &proof.to_vec(), &[output])
-        }
-    };
+pub fn wasm_circom_public_signals(r1cs: &[u8], wtns: &[u8]) -> Result<String, JsValue> {
+    let r1cs = R1cs::read(r1cs)?;
+    let witness = circom::read_witness(wtns)?;
+    r1cs.check_witness(&witness)?;
+    let signals: Vec<String> = r1cs
+        .public_inputs(&witness)
+        .iter()
+        .map(fr_to_decimal)
+        .collect();
+    Ok(serde_json::to_string(&signals).map_err(ProverError::from)?)
+}
+
+/// Verifies a proof of [`wasm_circom_prove`] against `public_signals`, a JSON list of
+/// field elements.
+#[wasm_bindgen]
+pub fn wasm_circom_verify(
+    _params: &[u8],
+    r1cs: &[u8],
+    proof: &[u8],
+    public_signals: &str,
+    scheme: MultiOpenScheme,
+    transcript: TranscriptHash,
+) -> Result<bool, JsValue> {
+    let params = read_params(_params)?;
+
+    let r1cs = Arc::new(R1cs::read(r1cs)?);
+    let signals: Vec<FieldElement> =
+        serde_json::from_str(public_signals).map_err(ProverError::from)?;
+    let public_inputs: Vec<Fr> = signals.into_iter().map(|signal| signal.0).collect();
+    log_verification(circom::verify_circom(
+        &params,
+        r1cs,
+        proof,
+        &public_inputs,
+        scheme,
+        transcript,
+    ))
+}
+
+/// Verifies a proof bundle using only the params, regenerating the verifying key from the
+/// circuit and shape recorded in the bundle.
+#[wasm_bindgen]
+pub fn wasm_verify_bundle(_params: &[u8], bundle: &[u8]) -> Result<bool, JsValue> {
+    let params = read_params(_params)?;
+
+    let bundle = read_bundle(bundle)?;
+    log_verification(bundle::verify_bundle(&params, &bundle))
+}
+
+/// JSON encoding of a binary proof bundle, with the proof in base64.
+#[wasm_bindgen]
+pub fn bundle_to_json(bundle: &[u8]) -> Result<String, JsValue> {
+    Ok(read_bundle(bundle)?.to_json())
+}
+
+#[wasm_bindgen]
+pub fn bundle_from_json(json: &str) -> Result<Uint8Array, JsValue> {
+    let bundle =
+        ProofBundle::from_json(json).map_err(|e| ProverError::InvalidProof(e.to_string()))?;
+    Ok(to_uint8_array(bundle.to_bytes()))
+}
 
+/// Verifies a proof against a verifying key exported with [`ProverSession::export_vk`],
+/// without generating any keys.
+#[wasm_bindgen]
+pub fn wasm_verify_proof_with_vk(
+    _params: &[u8],
+    vk: &[u8],
+    proof: &[u8],
+    s: &str,
+    circuit: usize,
+) -> Result<bool, JsValue> {
+    let params = read_params(_params)?;
+
+    let circuit = find_circuit(circuit)?;
+    let vk = circuit.read_vk(vk, KEY_FORMAT)?;
+    let proof = read_bundle(proof)?;
+    log_verification(circuit.verify_json(&params, &vk, &proof, s))
+}
+
+fn read_params(bytes: &[u8]) -> Result<ParamsVerifierKZG<Bn256>, ProverError> {
+    ParamsKZG::<Bn256>::read(&mut BufReader::new(bytes))
+        .map_err(|e| ProverError::InvalidParams(e.to_string()))
+}
+
+fn read_bundle(bytes: &[u8]) -> Result<ProofBundle, ProverError> {
+    ProofBundle::from_bytes(bytes).map_err(|e| ProverError::InvalidProof(e.to_string()))
+}
+
+/// A proof that fails to verify is reported as `false`; any other error is thrown.
+fn log_verification(res: Result<(), ProverError>) -> Result<bool, JsValue> {
     match res {
-        Err(e) => {
+        Ok(()) => Ok(true),
+        Err(e @ ProverError::VerificationFailed(_)) => {
             log(&format!("{}", e));
-            false
+            Ok(false)
         }
-        _ => true,
+        Err(e) => Err(e.into()),
     }
 }
 
 #[wasm_bindgen]
-pub fn get_circuit_count() -> i32 {
-    3
+pub fn get_circuit_count() -> usize {
+    registry::CIRCUITS.len()
+}
+
+/// JSON list of `{id, slug, name, description, input_schema, k}` for every circuit.
+#[wasm_bindgen]
+pub fn get_circuits() -> String {
+    serde_json::to_string(&registry::circuit_infos()).expect("circuit infos serialize")
+}
+
+/// Holds params and the keys generated from them across calls, so keygen runs once per
+/// circuit shape. Keys can be exported and imported to persist them across page loads.
+#[wasm_bindgen]
+pub struct ProverSession {
+    cache: KeyCache,
+}
+
+#[wasm_bindgen]
+impl ProverSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(params: &[u8]) -> Result<ProverSession, JsValue> {
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+        Ok(ProverSession {
+            cache: KeyCache::new(read_params(params)?),
+        })
+    }
+
+    pub fn generate_proof(
+        &mut self,
+        s: &str,
+        circuit: usize,
+        scheme: MultiOpenScheme,
+        transcript: TranscriptHash,
+    ) -> Result<Uint8Array, JsValue> {
+        let bundle = self.cache.prove(circuit, s, scheme, transcript)?;
+        Ok(to_uint8_array(bundle.to_bytes()))
+    }
+
+    pub fn verify_proof(&mut self, proof: &[u8], s: &str, circuit: usize) -> Result<bool, JsValue> {
+        let proof = read_bundle(proof)?;
+        log_verification(self.cache.verify(circuit, &proof, s))
+    }
+
+    pub fn verify_bundle(&mut self, bundle: &[u8]) -> Result<bool, JsValue> {
+        let bundle = read_bundle(bundle)?;
+        log_verification(self.cache.verify_bundle(&bundle))
+    }
+
+    pub fn export_vk(&mut self, s: &str, circuit: usize) -> Result<Uint8Array, JsValue> {
+        Ok(to_uint8_array(self.cache.export_vk(circuit, s)?))
+    }
+
+    pub fn import_vk(&mut self, s: &str, circuit: usize, vk: &[u8]) -> Result<(), JsValue> {
+        Ok(self.cache.import_vk(circuit, s, vk)?)
+    }
+
+    pub fn export_pk(&mut self, s: &str, circuit: usize) -> Result<Uint8Array, JsValue> {
+        Ok(to_uint8_array(self.cache.export_pk(circuit, s)?))
+    }
+
+    pub fn import_pk(&mut self, s: &str, circuit: usize, pk: &[u8]) -> Result<(), JsValue> {
+        Ok(self.cache.import_pk(circuit, s, pk)?)
+    }
+
+    /// Identifier of the keys used for `s`, suitable as a storage key for exported keys.
+    pub fn key_id(&self, s: &str, circuit: usize) -> Result<String, JsValue> {
+        let id = self.cache.key_id(circuit, s)?;
+        Ok(format!(
+            "{}-{}-{:x}-{}",
+            id.circuit, id.k, id.params_hash, id.shape
+        ))
+    }
 }
@@ -1,9 +1,19 @@
-import { useContext, useState, useEffect } from "react";
+import { useContext, useState, useEffect, useRef } from "react";
 import { WASMContext } from "../context/wasm";
+import { loadKey, storeKey } from "../lib/keyStore";
 import Image from "next/image";
-import ArithmeticCircuit from "../../public/arithmetic_circuit.svg";
-import CollatzCircuit from "../../public/collatz.svg";
-import PoseidonCircuit from "../../public/poseidon.svg";
+
+export interface CircuitInfo {
+  id: number;
+  slug: string;
+  name: string;
+  description: string;
+  input_schema: string;
+  k: number;
+}
+
+export const getCircuits = (wasm: typeof import("wasm")): CircuitInfo[] =>
+  JSON.parse(wasm.get_circuits());
 
 interface CircuitContextProps {
   setCircuitIndex: Function;
@@ -21,27 +31,41 @@ interface ProofProps {
 
 export const CircuitContext = (props: CircuitContextProps) => {
   const ctx = useContext(WASMContext);
-  const wasm = ctx.wasm!;
+  // Slugs without a layout image in public/, which show their name instead.
+  const [missingImages, setMissingImages] = useState<string[]>([]);
+  if (!ctx.wasm) {
+    return <div className="h-full columns-1 items-start ">Loading...</div>;
+  }
+  const wasm = ctx.wasm;
   let circuitIndex = props.circuitIndex;
   const setCircuitIndex = props.setCircuitIndex;
   const clear = props.clear;
 
-  const images = [CollatzCircuit, ArithmeticCircuit, PoseidonCircuit];
+  const circuits = getCircuits(wasm);
+  const circuit = circuits[circuitIndex];
 
   const handleSwitch = (e: number) => {
     clear();
-    setCircuitIndex(
-      (circuitIndex + e + wasm.get_circuit_count()) % wasm.get_circuit_count()
-    );
+    setCircuitIndex((circuitIndex + e + circuits.length) % circuits.length);
   };
 
   return (
     <div className="h-full columns-1 items-start ">
-      <Image
-        className="shadow-slate-600 shadow-sm m-1"
-        src={images[circuitIndex]}
-        alt="Collatz Arithmetic Circuit"
-      />
+      {missingImages.includes(circuit.slug) ? (
+        <div className="flex justify-center items-center h-64 shadow-slate-600 shadow-sm m-1">
+          {circuit.name}
+        </div>
+      ) : (
+        <Image
+          className="shadow-slate-600 shadow-sm m-1"
+          src={`/${circuit.slug}.svg`}
+          width={1024}
+          height={768}
+          alt={`${circuit.name} circuit layout`}
+          onError={() => setMissingImages([...missingImages, circuit.slug])}
+        />
+      )}
+      <div className="flex justify-center m-1">{circuit.description}</div>
       <div className="flex justify-center">
         <button
           className="rounded-md bg-orange-300 m-2 py-1.5 px-3 text-slate-950"
@@ -50,7 +74,7 @@ export const CircuitContext = (props: CircuitContextProps) => {
           &lt;-
         </button>
         <div className="flex justify-center m-2 py-1.5 px-3">
-          {circuitIndex}
+          {circuit.name}
         </div>
         <button
           className="rounded-md bg-orange-300 m-2 py-1.5 px-3 text-slate-950"
@@ -77,8 +101,19 @@ export const Proof = (props: ProofProps) => {
   const circuitIndex = props.circuitIndex;
 
   const ctx = useContext(WASMContext);
+  const [scheme, setScheme] = useState(1);
+  const [transcript, setTranscript] = useState(0);
+  // One session per set of params, so its in-memory key cache survives across clicks.
+  const session = useRef<{
+    params: string;
+    session: any;
+    keys: Set<string>;
+  } | null>(null);
+  if (!ctx.wasm) {
+    return null;
+  }
 
-  const wasm = ctx.wasm!;
+  const wasm = ctx.wasm;
 
   const getLocalItem = (s: string) => {
     return Uint8Array.from(
@@ -86,8 +121,61 @@ export const Proof = (props: ProofProps) => {
     );
   };
 
+  const circuit = getCircuits(wasm)[circuitIndex];
+
+  // Recreates the session when the params change.
+  const getSession = () => {
+    const params = localStorage.getItem("setup_params") as string;
+    if (session.current?.params !== params) {
+      session.current?.session.free();
+      session.current = {
+        params,
+        session: new wasm.ProverSession(getLocalItem("setup_params")),
+        keys: new Set(),
+      };
+    }
+    return session.current;
+  };
+
+  // Loads the key `kind` of `witness` into the session once, from IndexedDB if an
+  // earlier page load stored it, and stores it otherwise.
+  const loadSessionKey = async (kind: "pk" | "vk", witness: string) => {
+    const { session: current, keys } = getSession();
+    const keyId = `${kind}-${current.key_id(witness, circuitIndex)}`;
+    if (!keys.has(keyId)) {
+      const key = await loadKey(keyId);
+      if (key && kind === "pk") {
+        current.import_pk(witness, circuitIndex, key);
+      } else if (key) {
+        current.import_vk(witness, circuitIndex, key);
+      } else if (kind === "pk") {
+        await storeKey(keyId, current.export_pk(witness, circuitIndex));
+      } else {
+        await storeKey(keyId, current.export_vk(witness, circuitIndex));
+      }
+      keys.add(keyId);
+    }
+    return current;
+  };
+
+  // Proving needs the proving key.
+  const getProvingSession = (witness: string) => loadSessionKey("pk", witness);
+
+  // Verifying only needs the verifying key, which is much cheaper to generate.
+  const getVerifyingSession = (witness: string) =>
+    loadSessionKey("vk", witness);
+
+  // Errors thrown by the wasm API carry the failure kind in `name`.
+  const describeError = (e: unknown) =>
+    e instanceof Error ? `${e.name}: ${e.message}` : String(e);
+
   const setupParams = async () => {
-    await localStorage.setItem("setup_params", wasm.setup(10).join(","));
+    try {
+      const k = input ? wasm.wasm_circuit_k(input as string, circuitIndex) : circuit.k;
+      await localStorage.setItem("setup_params", wasm.setup(k).join(","));
+    } catch (e) {
+      alert(describeError(e));
+    }
   };
 
   const simulateCircuit = async () => {
@@ -97,49 +185,40 @@ export const Proof = (props: ProofProps) => {
         circuitIndex
       );
       alert(output);
-    } catch {
-      alert("Invalid params...");
+    } catch (e) {
+      alert(describeError(e));
     }
     // console.log(wasm);
   };
 
   const wasmGenerateProof = async () => {
     try {
-      const setup_params = getLocalItem("setup_params");
       console.log("ts input: " + input);
       const witness = JSON.stringify(JSON.parse(input as string));
+      const session = await getProvingSession(witness);
+      console.time("generate_proof");
       localStorage.setItem(
         "proof",
-        await wasm
-          .wasm_generate_proof(setup_params, witness, circuitIndex)
+        session
+          .generate_proof(witness, circuitIndex, scheme, transcript)
           .join(",")
       );
+      console.timeEnd("generate_proof");
     } catch (e) {
       console.error(e);
-      alert("Invalid params...");
+      alert(describeError(e));
     }
   };
 
   const wasmVerifyProof = async () => {
     try {
-      const setup_params = getLocalItem("setup_params");
       const proof = getLocalItem("proof");
       const sequence = JSON.stringify(JSON.parse(input as string));
-      const isValid = async () => {
-        try {
-          return wasm.wasm_verify_proof(
-            setup_params,
-            proof,
-            sequence,
-            circuitIndex
-          );
-        } catch {
-          return false;
-        }
-      };
-      setIsValidProof((await isValid()) ? "Valid Proof" : "Invalid Proof");
-    } catch {
-      alert("Invalid params...");
+      const session = await getVerifyingSession(sequence);
+      const isValid = session.verify_proof(proof, sequence, circuitIndex);
+      setIsValidProof(isValid ? "Valid Proof" : "Invalid Proof");
+    } catch (e) {
+      alert(describeError(e));
     }
   };
 
@@ -153,9 +232,32 @@ export const Proof = (props: ProofProps) => {
             setInput(e.target.value);
             console.log(input);
           }}
-          placeholder='{ "x": [5, 16, 8, 4, 2, 1, 1]}'
+          placeholder={circuit.input_schema}
         ></textarea>
       </div>
+      <div className="mb-2">
+        <label htmlFor="scheme">Multi-open scheme </label>
+        <select
+          id="scheme"
+          className="bg-gray-700 rounded-md p-1"
+          value={scheme}
+          onChange={(e) => setScheme(parseInt(e.target.value))}
+        >
+          <option value={wasm.MultiOpenScheme.Gwc}>GWC</option>
+          <option value={wasm.MultiOpenScheme.Shplonk}>SHPLONK</option>
+        </select>
+        <label htmlFor="transcript"> Transcript </label>
+        <select
+          id="transcript"
+          className="bg-gray-700 rounded-md p-1"
+          value={transcript}
+          onChange={(e) => setTranscript(parseInt(e.target.value))}
+        >
+          <option value={wasm.TranscriptHash.Blake2b}>Blake2b</option>
+          <option value={wasm.TranscriptHash.Keccak256}>Keccak256</option>
+          <option value={wasm.TranscriptHash.Poseidon}>Poseidon</option>
+        </select>
+      </div>
       <div id="proofResult">{isValidProof}</div>
       <div className="columns-2">
         <div className="container mx-auto">